@@ -1,10 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use chrono::NaiveDate;
-use pulldown_cmark::{Options, Parser, html};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use clap::{Args, Parser as _, Subcommand};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd, html};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use tera::{Context as TeraContext, Tera};
 use walkdir::WalkDir;
@@ -12,614 +18,9369 @@ use walkdir::WalkDir;
 // Configuration paths
 const CONFIG_PATH: &str = "site.toml";
 const CONTENT_DIR: &str = "content";
-const TEMPLATES_GLOB: &str = "templates/**/*";
+const TEMPLATES_DIR: &str = "templates";
 const STATIC_DIR: &str = "static";
 const OUTPUT_DIR: &str = "public";
+/// Build caches, next to the config file, with one subdirectory per output directory and config
+const CACHE_DIR: &str = ".cache";
+const FEED_SECTION: &str = "writing";
+/// Section whose pages the home page lists unless `[extra] home_section` names another
+const DEFAULT_HOME_SECTION: &str = "writing";
+const RSS_FEED_PATH: &str = "rss/feed.xml";
+const ATOM_FEED_PATH: &str = "atom.xml";
+/// Per-term feeds written inside each term's directory with `[taxonomies] feeds`
+const TERM_RSS_FILE: &str = "rss.xml";
+const TERM_ATOM_FILE: &str = "atom.xml";
+const JSON_FEED_PATH: &str = "feed.json";
+const SITEMAP_PATH: &str = "sitemap.xml";
+const ROBOTS_PATH: &str = "robots.txt";
+const SEARCH_INDEX_PATH: &str = "search-index.json";
+/// Per-section data file written next to each section's `index.html`
+const SECTION_JSON_FILE: &str = "index.json";
+const SYNTAX_CSS_PATH: &str = "css/syntax.css";
+const TAGS: Taxonomy = Taxonomy { name: "tags", singular: "tag" };
+const ARCHIVE_DIR: &str = "archive";
+const CATEGORIES: Taxonomy = Taxonomy { name: "categories", singular: "category" };
+const DEFAULT_HIGHLIGHT_THEME: &str = "InspiredGitHub";
+/// Deepest heading level included in a page's table of contents by default (h2–h4)
+const DEFAULT_TOC_DEPTH: u8 = 4;
+/// Reading speed used for `page.reading_time_minutes`
+const WORDS_PER_MINUTE: usize = 200;
+/// Longest generated summary for pages without a `summary` in front matter
+const AUTO_SUMMARY_CHARS: usize = 160;
+/// Marks the end of a page's excerpt in its Markdown body
+const EXCERPT_MARKER: &str = "<!-- more -->";
+/// Template for the stripped-down page variants written when `plain_pages` is on
+const PLAIN_TEMPLATE: &str = "plain.html";
+/// Used for `PLAIN_TEMPLATE` when the templates directory doesn't provide one
+const DEFAULT_PLAIN_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ lang }}">
+<head>
+  <meta charset="utf-8">
+  <title>{{ page.title }}</title>
+  <link rel="canonical" href="{{ page.permalink }}">
+</head>
+<body>
+  <h1>{{ page.title }}</h1>
+  {{ page.content | safe }}
+</body>
+</html>
+"#;
+const DEFAULT_SERVE_PORT: u16 = 1111;
+/// Interface the dev server listens on
+const SERVE_HOST: &str = "127.0.0.1";
+/// Quiet period after a file change before `serve` rebuilds, so one save triggers one build
+const REBUILD_DEBOUNCE: Duration = Duration::from_millis(250);
+/// Written to the output directory when static assets are fingerprinted
+const ASSET_MANIFEST_PATH: &str = "manifest.json";
+const OUTPUT_MANIFEST_FILE: &str = "output-manifest.json";
+/// Inputs of the previous build, for `--incremental`
+const BUILD_MANIFEST_FILE: &str = "build-manifest.json";
+
+/// Set by `--quiet`; silences warnings and the build summary
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints a build warning to stderr unless `--quiet` was given
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            eprintln!("⚠️  Warning: {}", format!($($arg)*));
+        }
+    };
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 struct Config {
     base_url: String,
     title: String,
     description: String,
+    /// Default language of the site, as a BCP 47 tag such as `en` or `fr-CA`
+    #[serde(default = "default_lang")]
+    lang: String,
+    /// Content directories walked in order; later directories override earlier ones on path conflicts
+    #[serde(default = "default_content_dirs", alias = "content_dirs")]
+    content: Vec<PathBuf>,
+    /// File extensions (without the dot) read as Markdown; everything else in the content directories is an asset
+    #[serde(default = "default_markdown_extensions")]
+    markdown_extensions: Vec<String>,
+    /// Open external Markdown links in a new tab with `rel="noopener noreferrer"`
+    #[serde(default)]
+    external_links_new_tab: bool,
+    /// Run rendered Markdown through an allowlist sanitizer, dropping scripts and event handlers
+    #[serde(default)]
+    sanitize_html: bool,
+    /// Rewrite relative Markdown image paths to absolute URLs under the file's section directory
+    #[serde(default)]
+    absolute_image_urls: bool,
+    /// Write pages and sections as `section/slug.html` instead of `section/slug/index.html`
+    #[serde(default)]
+    ugly_urls: bool,
+    /// Also render every page as `section/slug/plain.html` with `plain.html`, without the site chrome
+    #[serde(default)]
+    plain_pages: bool,
+    /// Build pages and sections marked as drafts (also enabled by `SITE_DRAFTS=1`)
+    #[serde(default)]
+    drafts: bool,
+    /// Fail the build on unknown top-level front matter keys instead of only warning about likely typos
+    #[serde(default)]
+    strict_front_matter: bool,
+    /// Output files, relative to the output directory, that survive the clean before each build
+    #[serde(default)]
+    keep_files: Vec<PathBuf>,
+    /// Evaluate Tera expressions in Markdown bodies before conversion (code is left untouched)
+    #[serde(default)]
+    markdown_templating: bool,
+    /// Settings for the opt-in `--check-external-links` pass
+    #[serde(default)]
+    link_check: LinkCheckConfig,
+    /// Extra attributes on images rendered from Markdown
+    #[serde(default)]
+    images: ImagesConfig,
+    /// The deep-link anchor added to Markdown headings
+    #[serde(default)]
+    anchors: AnchorsConfig,
+    /// Settings shared by tags and categories
+    #[serde(default)]
+    taxonomies: TaxonomiesConfig,
+    /// Strategy for filling `page.related`; unset disables related pages
+    #[serde(default)]
+    related_by: Option<RelatedBy>,
+    /// Maximum number of related pages computed per page (explicit lists are not capped)
+    #[serde(default = "default_related_limit")]
+    related_limit: usize,
+    /// Maximum number of entries in generated feeds
+    #[serde(default = "default_feed_limit")]
+    feed_limit: usize,
+    /// Collect `page.toc` for pages whose front matter doesn't set `toc`
+    #[serde(default = "default_toc")]
+    toc: bool,
+    /// Deepest heading level in `page.toc` for pages whose front matter doesn't set `toc_depth`
+    #[serde(default = "default_toc_depth")]
+    toc_depth: u8,
+    /// Site-wide fallbacks for sitemap `<priority>`/`<changefreq>` hints
+    #[serde(default)]
+    sitemap: SitemapConfig,
+    /// Markdown extensions enabled for every page
+    #[serde(default)]
+    markdown: MarkdownConfig,
+    /// Primary navigation as `[[menu]]` entries; templates get it as `menu`
+    #[serde(default)]
+    menu: Vec<MenuItem>,
+    /// Error pages keyed by status code, e.g. `[error_pages.500]`; a 404 page is always built
+    #[serde(default)]
+    error_pages: BTreeMap<String, ErrorPageConfig>,
     #[serde(default)]
     extra: HashMap<String, toml::Value>,
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
-struct FrontMatter {
-    title: Option<String>,
-    description: Option<String>,
-    template: Option<String>,
-    date: Option<NaiveDate>,
-    summary: Option<String>,
-    /// Reserved for future use - will support sorting by date, title, etc.
-    #[allow(dead_code)]
-    sort_by: Option<String>,
+/// One `[[menu]]` entry. Root-relative URLs such as `/about/` are rewritten for each page;
+/// anything else is used as is
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MenuItem {
+    name: String,
+    url: String,
+    /// Lower first; unweighted entries follow in the order they are written
+    weight: Option<i64>,
+    #[serde(default)]
+    children: Vec<MenuItem>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct PageData {
-    title: String,
-    date: Option<NaiveDate>,
-    summary: Option<String>,
-    content: String,
-    permalink: String,
-    relative_path: String,
+/// One `[error_pages.<status>]` table; both fields default to `<status>.html`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct ErrorPageConfig {
     template: Option<String>,
-    slug: String,
+    /// Output path relative to the output directory; a trailing `/` means `index.html` inside it
+    path: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-struct SectionContent {
-    meta: FrontMatter,
-    body_html: String,
-    pages: Vec<PageData>,
+/// An error page to render, resolved from `error_pages`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ErrorPage {
+    status: u16,
+    template: String,
+    path: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct SectionData {
-    title: String,
-    description: Option<String>,
-    pages: Vec<PageData>,
-    content: String,
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct SitemapConfig {
+    priority: Option<f64>,
+    changefreq: Option<String>,
 }
 
-fn main() -> Result<()> {
-    build_site()
+/// The `[images]` table
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+struct ImagesConfig {
+    /// `loading="lazy"` and `decoding="async"` on every Markdown image
+    lazy_loading: bool,
+    /// `width`/`height` read from image files beside the Markdown file, to avoid layout shift
+    dimensions: bool,
 }
 
-fn build_site() -> Result<()> {
-    let config = load_config(CONFIG_PATH)?;
-    let tera = Tera::new(TEMPLATES_GLOB).context("loading templates")?;
-
-    let content_dir = Path::new(CONTENT_DIR);
-    let output_dir = Path::new(OUTPUT_DIR);
-
-    if output_dir.exists() {
-        fs::remove_dir_all(output_dir).context("clearing public directory")?;
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self { lazy_loading: true, dimensions: false }
     }
-    fs::create_dir_all(output_dir).context("creating public directory")?;
+}
 
-    copy_static_assets(Path::new(STATIC_DIR), output_dir)?;
+/// The `[taxonomies]` table
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct TaxonomiesConfig {
+    /// Write an RSS and an Atom feed for every term, e.g. `tags/rust/rss.xml`
+    feeds: bool,
+}
 
-    let (root_section, sections) = load_content(content_dir, &config.base_url)?;
+/// The `[anchors]` table
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+struct AnchorsConfig {
+    /// Link content, inserted as HTML so it can be an `<svg>` icon: `"#"`, `"¶"`, `""` for an empty
+    /// link styled through its class, or `"none"` for no link at all, only the heading `id`
+    symbol: String,
+    /// Which side of the heading text the link goes on
+    position: AnchorPosition,
+}
 
-    // Validate and warn about potential issues
-    validate_content(&sections);
+impl Default for AnchorsConfig {
+    fn default() -> Self {
+        Self { symbol: "#".to_string(), position: AnchorPosition::After }
+    }
+}
 
-    render_home(&tera, &config, &sections, output_dir, &root_section)?;
-    render_sections(&tera, &config, &sections, output_dir)?;
-    render_pages(&tera, &config, &sections, output_dir)?;
-    render_404(&tera, &config, output_dir)?;
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum AnchorPosition {
+    Before,
+    #[default]
+    After,
+}
 
-    Ok(())
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+struct LinkCheckConfig {
+    /// When non-empty, only links to these domains (or their subdomains) are checked
+    allow_domains: Vec<String>,
+    /// Links to these domains (or their subdomains) are never checked
+    deny_domains: Vec<String>,
+    /// Maximum number of requests in flight at once
+    concurrency: usize,
+    /// Per-request timeout in seconds
+    timeout_secs: u64,
 }
 
-fn load_config(path: &str) -> Result<Config> {
-    let contents = fs::read_to_string(path).context("reading site.toml")?;
-    let mut config: Config = toml::from_str(&contents).context("parsing site.toml")?;
-    config.base_url = config.base_url.trim_end_matches('/').to_string();
-    Ok(config)
+impl Default for LinkCheckConfig {
+    fn default() -> Self {
+        Self {
+            allow_domains: Vec::new(),
+            deny_domains: Vec::new(),
+            concurrency: 8,
+            timeout_secs: 10,
+        }
+    }
 }
 
-fn copy_static_assets(static_dir: &Path, output_dir: &Path) -> Result<()> {
-    if !static_dir.exists() {
-        return Ok(());
+/// The `[markdown]` table; each flag switches on a Markdown extension
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+struct MarkdownConfig {
+    tables: bool,
+    footnotes: bool,
+    strikethrough: bool,
+    /// `- [ ]` / `- [x]` render as disabled checkboxes
+    tasklists: bool,
+    /// Curly quotes and en/em dashes in prose
+    smart_punctuation: bool,
+    /// `## Heading {#id .class}` sets the heading's id and classes
+    heading_attributes: bool,
+    /// GitHub-style `:rocket:` shortcodes in prose become emoji
+    emoji: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            footnotes: true,
+            strikethrough: true,
+            tasklists: true,
+            smart_punctuation: false,
+            heading_attributes: false,
+            emoji: false,
+        }
     }
+}
 
-    for entry in WalkDir::new(static_dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() {
-            let relative = path.strip_prefix(static_dir).unwrap();
-            let dest = output_dir.join(relative);
-            if let Some(parent) = dest.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            fs::copy(path, dest)?;
+impl MarkdownConfig {
+    fn options(&self) -> Options {
+        let mut options = Options::empty();
+        for (enabled, option) in [
+            (self.tables, Options::ENABLE_TABLES),
+            (self.footnotes, Options::ENABLE_FOOTNOTES),
+            (self.strikethrough, Options::ENABLE_STRIKETHROUGH),
+            (self.tasklists, Options::ENABLE_TASKLISTS),
+            (self.smart_punctuation, Options::ENABLE_SMART_PUNCTUATION),
+            (self.heading_attributes, Options::ENABLE_HEADING_ATTRIBUTES),
+        ] {
+            options.set(option, enabled);
         }
+        options
     }
+}
 
-    Ok(())
+fn default_content_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from(CONTENT_DIR)]
 }
 
-fn parse_front_matter(content: &str) -> Result<(FrontMatter, String)> {
-    let mut lines = content.lines();
-    let first_line = lines.next().unwrap_or("");
+fn default_markdown_extensions() -> Vec<String> {
+    ["md", "markdown", "mdown"].map(String::from).to_vec()
+}
 
-    if first_line.trim() != "+++" {
-        return Ok((FrontMatter::default(), content.to_string()));
-    }
+/// Whether `path` has one of `extensions`, ignoring case
+fn is_markdown_file(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|known| known.eq_ignore_ascii_case(ext)))
+}
 
-    let mut front_matter = String::new();
-    let mut body = String::new();
-    let mut in_front_matter = true;
+fn default_related_limit() -> usize {
+    3
+}
 
-    for line in lines {
-        if in_front_matter && line.trim() == "+++" {
-            in_front_matter = false;
-            continue;
-        }
+fn default_lang() -> String {
+    "en".to_string()
+}
 
-        if in_front_matter {
-            front_matter.push_str(line);
-            front_matter.push('\n');
-        } else {
-            body.push_str(line);
-            body.push('\n');
-        }
-    }
+fn default_toc() -> bool {
+    true
+}
 
-    let data: FrontMatter = toml::from_str(&front_matter)
-        .context("parsing frontmatter TOML")?;
-    Ok((data, body))
+fn default_toc_depth() -> u8 {
+    DEFAULT_TOC_DEPTH
 }
 
-fn markdown_to_html(markdown: &str) -> String {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    options.insert(Options::ENABLE_TABLES);
-    options.insert(Options::ENABLE_FOOTNOTES);
+fn default_feed_limit() -> usize {
+    20
+}
 
-    let parser = Parser::new_ext(markdown, options);
-    let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
-    html_output
+/// How related pages are chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum RelatedBy {
+    /// The most recent other pages in the same section
+    Section,
+    /// The pages listed in the page's `related` front matter
+    Explicit,
+    /// Pages from any section sharing the most tags, newest first among equals
+    Tags,
 }
 
-/// Renders a template with the given context and writes to output file
-fn render_template_to_file(
-    tera: &Tera,
-    template_name: &str,
-    context: &TeraContext,
-    output_path: &Path,
-    context_desc: &str,
-) -> Result<()> {
-    let rendered = tera
-        .render(template_name, context)
-        .with_context(|| format!("rendering {}", context_desc))?;
+/// Site-level settings that influence Markdown rendering
+#[derive(Debug, Clone, Default)]
+struct MarkdownSettings {
+    base_url: String,
+    external_links_new_tab: bool,
+    /// Highlight fenced code blocks with syntect (`extra.highlight_code`)
+    highlight_code: bool,
+    /// Enabled Markdown extensions (`[markdown]`)
+    features: MarkdownConfig,
+    /// Pass `$...$` and `$$...$$` through untouched for KaTeX (`extra.math`)
+    math: bool,
+    /// Clean the rendered HTML with `sanitize_html`
+    sanitize_html: bool,
+    /// Output directory of the file being rendered (its section key), set per file with
+    /// `absolute_image_urls`; relative image paths are resolved against it
+    image_dir: Option<String>,
+    /// Image attributes (`[images]`)
+    images: ImagesConfig,
+    /// Directory of the Markdown file on disk, set per file with `images.dimensions`
+    source_dir: Option<PathBuf>,
+    /// Heading anchor links (`[anchors]`)
+    anchors: AnchorsConfig,
+}
 
-    if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("creating directory for {}", context_desc))?;
+impl MarkdownSettings {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            base_url: config.base_url.clone(),
+            external_links_new_tab: config.external_links_new_tab,
+            highlight_code: config.extra_bool("highlight_code"),
+            features: MarkdownConfig {
+                // `extra.smart_punctuation` predates the `[markdown]` table and still works
+                smart_punctuation: config.markdown.smart_punctuation || config.extra_bool("smart_punctuation"),
+                ..config.markdown.clone()
+            },
+            math: config.extra_bool("math"),
+            sanitize_html: config.sanitize_html,
+            image_dir: None,
+            images: config.images.clone(),
+            source_dir: None,
+            anchors: config.anchors.clone(),
+        }
     }
+}
 
-    fs::write(output_path, rendered)
-        .with_context(|| format!("writing {} to {:?}", context_desc, output_path))?;
+impl Config {
+    /// Reads a boolean flag from `[extra]`, treating missing or non-boolean values as false
+    fn extra_bool(&self, key: &str) -> bool {
+        self.extra.get(key).and_then(toml::Value::as_bool).unwrap_or(false)
+    }
 
-    Ok(())
-}
+    /// Words `humanize_slug` keeps in a fixed spelling, from `[extra] title_acronyms`
+    fn title_acronyms(&self) -> Vec<String> {
+        self.extra
+            .get("title_acronyms")
+            .and_then(toml::Value::as_array)
+            .map(|words| words.iter().filter_map(toml::Value::as_str).map(String::from).collect())
+            .unwrap_or_default()
+    }
 
-/// Creates base template context with config and path prefix
-fn build_base_context(config: &Config, path_prefix: &str) -> TeraContext {
-    let mut context = TeraContext::new();
-    context.insert("config", config);
-    context.insert("path_prefix", path_prefix);
-    context
+    /// Reads a string from `[extra]`
+    fn extra_str(&self, key: &str) -> Option<&str> {
+        self.extra.get(key).and_then(toml::Value::as_str)
+    }
+
+    /// Every error page to build, ordered by status: `404.html` unless `[error_pages.404]`
+    /// moves it, plus any other configured statuses
+    fn error_pages(&self) -> Result<Vec<ErrorPage>> {
+        let mut pages = BTreeMap::new();
+        for (status, page) in &self.error_pages {
+            let code: u16 = status
+                .parse()
+                .ok()
+                .filter(|code| (400..=599).contains(code))
+                .with_context(|| format!("error_pages key '{}' is not a 4xx or 5xx status code", status))?;
+            let default = format!("{}.html", code);
+            let path = match &page.path {
+                Some(path) => error_page_output_path(path)?,
+                None => default.clone(),
+            };
+            let template = page.template.clone().unwrap_or(default);
+            pages.insert(code, ErrorPage { status: code, template, path });
+        }
+        pages.entry(404).or_insert_with(|| ErrorPage {
+            status: 404,
+            template: "404.html".to_string(),
+            path: "404.html".to_string(),
+        });
+        Ok(pages.into_values().collect())
+    }
 }
 
-/// Calculates directory depth for path prefix generation
-/// Returns number of "../" needed to reach site root
-fn calculate_path_depth(path: &str, is_page: bool) -> usize {
-    if path.is_empty() {
-        if is_page { 1 } else { 0 }
+/// Normalizes an `error_pages` output path: `/404/` and `404/` both become `404/index.html`
+fn error_page_output_path(path: &str) -> Result<String> {
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    if segments.is_empty() || segments.iter().any(|segment| *segment == "." || *segment == "..") {
+        anyhow::bail!("error page path '{}' must name a file inside the output directory", path);
+    }
+    let joined = segments.join("/");
+    if path.ends_with('/') || !joined.ends_with(".html") {
+        Ok(format!("{}/index.html", joined))
     } else {
-        let base_depth = path.split('/').count();
-        if is_page { base_depth + 1 } else { base_depth }
+        Ok(joined)
     }
 }
 
-/// Generates "../" prefix to reach site root from given depth
-fn path_prefix_for_depth(depth: usize) -> String {
-    "../".repeat(depth)
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+struct FrontMatter {
+    title: Option<String>,
+    description: Option<String>,
+    /// Overrides the filename-derived slug; slugified before use
+    slug: Option<String>,
+    /// Old root-relative URLs that redirect to this page, e.g. `/blog/old-name/`
+    aliases: Option<Vec<String>>,
+    template: Option<String>,
+    /// On a section `_index`, the template for its pages that don't set their own `template`
+    default_template: Option<String>,
+    /// Calendar day of `published` as written, before any conversion to UTC
+    #[serde(skip_deserializing)]
+    date: Option<NaiveDate>,
+    /// Front matter `date`, either a day or an RFC 3339 datetime; a time without an offset is UTC
+    #[serde(rename = "date", default, deserialize_with = "deserialize_optional_datetime", skip_serializing)]
+    published: Option<DateTime<FixedOffset>>,
+    /// When the page last changed meaningfully; sitemaps and feeds fall back to `date`
+    #[serde(default, alias = "last_modified", deserialize_with = "deserialize_optional_date")]
+    updated: Option<NaiveDate>,
+    summary: Option<String>,
+    /// Social preview image; relative paths resolve against `base_url`
+    image: Option<String>,
+    /// Language of this page when it differs from the site's `lang`
+    lang: Option<String>,
+    /// `false` leaves `page.toc` empty (heading anchors are unaffected); defaults to the site's `toc`
+    toc: Option<bool>,
+    /// Deepest heading level collected into `page.toc`; defaults to the site's `toc_depth` (4, i.e. h2–h4)
+    toc_depth: Option<u8>,
+    /// On a section `_index.md`, how many pages each listing page shows; unset means one listing
+    paginate_by: Option<usize>,
+    /// On a section `_index.md`, the file the section renders to inside its directory, e.g.
+    /// `feed.xml`, instead of `index.html`. Such a section is never paginated
+    output_filename: Option<String>,
+    /// Section page ordering as comma-separated `key[:asc|desc]` entries, e.g. `weight,date:desc`
+    sort_by: Option<String>,
+    /// Manual ordering hint used by `sort_by = "weight"` and, on a section `_index.md`,
+    /// by the site nav (lower first). `nav_order` is accepted as an alias
+    #[serde(alias = "nav_order")]
+    weight: Option<i64>,
+    /// `public` (default), `unlisted` (rendered but left out of listings), or `draft` (not built)
+    visibility: Option<Visibility>,
+    /// Shorthand for `visibility = "draft"`; on a section `_index.md` it hides the whole section
+    draft: Option<bool>,
+    /// Taxonomy terms; each gets a `/tags/<slug>/` listing page
+    tags: Option<Vec<String>>,
+    /// Single category, listed at `/categories/<slug>/` separately from tags
+    category: Option<String>,
+    /// Related pages as slugs (same section) or `section/slug` paths, used by `related_by = "explicit"`
+    related: Option<Vec<String>>,
+    /// Sitemap priority (0.0–1.0); on a section `_index.md` it is the default for its pages
+    sitemap_priority: Option<f64>,
+    /// Sitemap change frequency; on a section `_index.md` it is the default for its pages
+    sitemap_changefreq: Option<String>,
+    /// Every other key, including an explicit `[extra]` table; see `template_extra`
+    #[serde(flatten, deserialize_with = "deserialize_extra")]
+    extra: HashMap<String, toml::Value>,
 }
 
-fn load_content(
-    content_dir: &Path,
-    base_url: &str,
-) -> Result<(SectionData, HashMap<String, SectionContent>)> {
-    let mut sections: HashMap<String, SectionContent> = HashMap::new();
-    let mut root_meta = FrontMatter::default();
-    let mut root_body = String::new();
-
-    for entry in WalkDir::new(content_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file() && e.path().extension().map(|e| e == "md").unwrap_or(false))
-    {
-        let path = entry.path();
-        let relative = path
-            .strip_prefix(content_dir)
-            .context("stripping content prefix")?;
-        let parent = relative
-            .parent()
-            .map(|p| p.to_path_buf())
-            .unwrap_or_default();
-        let parent_key = parent.to_string_lossy().to_string();
-
-        let raw = fs::read_to_string(path)
-            .with_context(|| format!("reading markdown file {:?}", path))?;
-        let (meta, body) = parse_front_matter(&raw)
-            .with_context(|| format!("parsing frontmatter in {:?}", path))?;
-        let html_body = markdown_to_html(&body);
-
-        if path.file_name().unwrap() == "_index.md" {
-            if relative.components().count() == 1 {
-                root_meta = meta;
-                root_body = html_body;
-            } else {
-                // Use entry API to preserve existing pages if section already exists
-                sections.entry(parent_key.clone())
-                    .and_modify(|section| {
-                        section.meta = meta.clone();
-                        section.body_html = html_body.clone();
-                    })
-                    .or_insert_with(|| SectionContent {
-                        meta,
-                        body_html: html_body,
-                        pages: Vec::new(),
-                    });
-            }
-            continue;
-        }
-
-        let slug = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("page")
-            .to_string();
+#[derive(Debug, Clone, Serialize, Default)]
+struct PageData {
+    title: String,
+    date: Option<NaiveDate>,
+    /// Publish time in UTC; midnight for pages dated without a time of day
+    datetime: Option<DateTime<Utc>>,
+    /// Front matter `updated`, unset when the page only has a publish date
+    updated: Option<NaiveDate>,
+    description: Option<String>,
+    /// Absolute URL of the social preview image
+    image: Option<String>,
+    /// Front matter `lang`; templates get the resolved language as `lang`
+    lang: Option<String>,
+    /// Summary as HTML, whatever its source: front matter `summary` or a `<!-- more -->` excerpt
+    /// rendered from Markdown, or the generated summary as a paragraph
+    summary: Option<String>,
+    /// Summary with markup stripped, for meta tags and plain listings
+    summary_text: Option<String>,
+    content: String,
+    /// Words in the Markdown body, excluding fenced code blocks
+    word_count: usize,
+    reading_time_minutes: usize,
+    permalink: String,
+    relative_path: String,
+    /// Output paths of redirect stubs pointing at this page
+    aliases: Vec<String>,
+    template: Option<String>,
+    slug: String,
+    weight: Option<i64>,
+    visibility: Visibility,
+    /// True for draft pages included because drafts are enabled
+    draft: bool,
+    sitemap_priority: Option<f64>,
+    sitemap_changefreq: Option<String>,
+    related: Vec<PageLink>,
+    /// Neighbouring listed pages in the section's sort order
+    prev: Option<PageLink>,
+    next: Option<PageLink>,
+    tags: Vec<TagLink>,
+    category: Option<TagLink>,
+    toc: Vec<TocEntry>,
+    /// Home, then each enclosing section, then the page itself
+    breadcrumbs: Vec<Crumb>,
+    /// Front matter keys the generator doesn't use itself
+    extra: HashMap<String, toml::Value>,
+    /// Unresolved `related` front matter references
+    #[serde(skip)]
+    related_refs: Vec<String>,
+    /// Content file the page was built from, for error messages
+    #[serde(skip)]
+    source: String,
+}
 
-        let mut url_path = PathBuf::new();
-        if !parent_key.is_empty() {
-            url_path.push(&parent_key);
-        }
-        url_path.push(&slug);
+/// Who can find a page: listed everywhere, reachable only by URL, or not built at all
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Visibility {
+    #[default]
+    Public,
+    Unlisted,
+    Draft,
+}
 
-        let url_str = url_path.to_string_lossy();
-        let permalink = format!("{}/{}/", base_url, url_str);
-        let relative_path = if parent_key.is_empty() {
-            format!("{}/index.html", slug)
-        } else {
-            format!("{}/{}/index.html", parent_key, slug)
-        };
+/// One table-of-contents entry; deeper headings that follow it are nested in `children`
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct TocEntry {
+    level: u32,
+    title: String,
+    id: String,
+    children: Vec<TocEntry>,
+}
 
-        let page = PageData {
-            title: meta
-                .title
-                .clone()
-                .unwrap_or_else(|| slug.replace('-', " ").to_uppercase()),
-            date: meta.date,
-            summary: meta.summary.clone(),
-            content: html_body,
-            permalink,
-            relative_path,
-            template: meta.template.clone(),
-            slug,
-        };
+/// A heading collected while rendering Markdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Heading {
+    level: u32,
+    text: String,
+    id: String,
+}
 
-        sections
-            .entry(parent_key.clone())
-            .or_insert_with(|| SectionContent {
-                meta: FrontMatter::default(),
-                body_html: String::new(),
-                pages: Vec::new(),
-            })
-            .pages
-            .push(page);
-    }
+/// Rendered HTML plus the structure gathered while producing it
+#[derive(Debug, Default)]
+struct RenderedMarkdown {
+    html: String,
+    headings: Vec<Heading>,
+}
 
-    for (_, section) in sections.iter_mut() {
-        section.pages.sort_by(|a, b| b.date.cmp(&a.date));
-    }
+/// A family of term listing pages such as tags or categories
+struct Taxonomy {
+    /// Output directory, index template (`tags.html`), and the index's list of terms
+    name: &'static str,
+    /// Term template (`tag.html`) and the term's name in its context
+    singular: &'static str,
+}
 
-    let root_section = SectionData {
-        title: root_meta.title.unwrap_or_else(|| "Home".to_string()),
-        description: root_meta.description,
-        pages: Vec::new(),
-        content: root_body,
-    };
+/// A page's tag or category with the location of its listing page
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct TagLink {
+    name: String,
+    slug: String,
+    permalink: String,
+    relative_path: String,
+}
 
-    Ok((root_section, sections))
+/// Every listed page carrying one tag or category, for the taxonomy's templates
+#[derive(Debug, Clone, Serialize)]
+struct TagData {
+    name: String,
+    slug: String,
+    permalink: String,
+    relative_path: String,
+    pages: Vec<PageData>,
 }
 
-/// Validates loaded content and prints warnings for common issues
-fn validate_content(sections: &HashMap<String, SectionContent>) {
-    let mut seen_slugs: HashMap<String, Vec<String>> = HashMap::new();
+/// Title and addresses of one feed: the site-wide feed or a taxonomy term's
+struct FeedChannel {
+    title: String,
+    /// Page the feed belongs to
+    link: String,
+    /// Absolute URL of the feed itself
+    self_url: String,
+}
 
-    for (section_key, section) in sections {
-        // Check for missing titles in section metadata
-        if section.meta.title.is_none() {
-            eprintln!("⚠️  Warning: Section '{}' has no title", section_key);
+impl FeedChannel {
+    /// The site-wide feed written to `path`
+    fn site(config: &Config, path: &str) -> Self {
+        Self {
+            title: config.title.clone(),
+            link: format!("{}/", config.base_url),
+            self_url: format!("{}/{}", config.base_url, path),
         }
+    }
+}
 
-        // Check for duplicate slugs within sections
-        for page in &section.pages {
-            let key = format!("{}/{}", section_key, &page.slug);
-            seen_slugs.entry(page.slug.clone())
-                .or_insert_with(Vec::new)
-                .push(key);
-        }
+/// A feed advertised with `<link rel="alternate">` on the page it belongs to
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct FeedLink {
+    title: String,
+    /// MIME type, e.g. `application/rss+xml`
+    mime: String,
+    relative_path: String,
+}
 
-        // Check for pages without dates (affects sorting)
-        let undated: Vec<_> = section.pages.iter()
-            .filter(|p| p.date.is_none())
-            .collect();
-        if !undated.is_empty() && !section.pages.is_empty() {
-            eprintln!("⚠️  Warning: Section '{}' has {} pages without dates (may affect sorting)",
-                section_key, undated.len());
+/// A lightweight reference to another page for navigation lists
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct PageLink {
+    title: String,
+    permalink: String,
+    relative_path: String,
+}
+
+impl FrontMatter {
+    /// Resolves `visibility`, treating `draft = true` as `visibility = "draft"`
+    fn visibility_or_default(&self) -> Visibility {
+        if self.draft == Some(true) {
+            Visibility::Draft
+        } else {
+            self.visibility.unwrap_or_default()
         }
     }
 
-    // Report duplicate slugs
-    for (slug, locations) in seen_slugs {
-        if locations.len() > 1 {
-            eprintln!("⚠️  Warning: Duplicate slug '{}' found in: {}",
-                slug, locations.join(", "));
+    /// Unknown keys for templates as `page.extra`, with the entries of an explicit
+    /// `[extra]` table lifted alongside them
+    fn template_extra(&self) -> HashMap<String, toml::Value> {
+        let mut extra = self.extra.clone();
+        if let Some(toml::Value::Table(table)) = extra.remove("extra") {
+            extra.extend(table);
         }
+        extra
     }
 }
 
-fn render_home(
-    tera: &Tera,
-    config: &Config,
-    sections: &HashMap<String, SectionContent>,
-    output_dir: &Path,
-    root_section: &SectionData,
-) -> Result<()> {
-    let mut context = build_base_context(config, "");
-    context.insert("section", root_section);
+impl PageData {
+    /// Whether the page may appear in listings and other aggregations.
+    /// Drafts only exist when drafts are enabled, so they are listed for previewing
+    fn is_listed(&self) -> bool {
+        self.visibility != Visibility::Unlisted
+    }
 
-    if let Some(section) = sections.get("writing") {
-        context.insert("writing_pages", &section.pages);
-    } else {
-        context.insert("writing_pages", &Vec::<PageData>::new());
+    /// `updated`, or the publish date for pages that were never updated
+    fn last_modified(&self) -> Option<NaiveDate> {
+        self.updated.or(self.date)
     }
 
-    render_template_to_file(
-        tera,
-        "index.html",
-        &context,
-        &output_dir.join("index.html"),
-        "homepage",
-    )
+    /// Publish time for ordering and feeds, falling back to midnight UTC of `date`
+    fn published(&self) -> Option<DateTime<Utc>> {
+        self.datetime.or_else(|| self.date.map(midnight_utc))
+    }
+
+    /// `last_modified` as a timestamp: the later of midnight UTC of `updated` and the publish time,
+    /// so a page updated the day it went out never reads as modified before it was published
+    fn last_modified_at(&self) -> Option<DateTime<Utc>> {
+        self.updated.map(midnight_utc).max(self.published())
+    }
+}
+
+fn midnight_utc(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Pages that may appear in listings, in their existing order
+fn listed_pages(pages: &[PageData]) -> Vec<PageData> {
+    pages.iter().filter(|page| page.is_listed()).cloned().collect()
+}
+
+impl From<&PageData> for PageLink {
+    fn from(page: &PageData) -> Self {
+        Self {
+            title: page.title.clone(),
+            permalink: page.permalink.clone(),
+            relative_path: page.relative_path.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct SectionContent {
+    meta: FrontMatter,
+    body_html: String,
+    pages: Vec<PageData>,
+    /// Keys of direct child sections, sorted
+    subsections: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SectionData {
+    title: String,
+    /// The `_index.md` description, if it has one
+    description: Option<String>,
+    /// `description`, or the site description when there is none, for meta tags
+    meta_description: String,
+    permalink: String,
+    relative_path: String,
+    pages: Vec<PageData>,
+    content: String,
+    subsections: Vec<SectionLink>,
+    /// Home, then each enclosing section, ending with this one
+    breadcrumbs: Vec<Crumb>,
+}
+
+/// One page of a paginated section listing. Links are relative to the site root, like `relative_path`
+#[derive(Debug, Clone, Serialize)]
+struct Paginator {
+    /// 1-based number of this listing page
+    current_index: usize,
+    number_pagers: usize,
+    paginate_by: usize,
+    /// Pages listed across every listing page
+    total_pages: usize,
+    pages: Vec<PageData>,
+    first: String,
+    last: String,
+    previous: Option<String>,
+    next: Option<String>,
+    relative_path: String,
+}
+
+/// A lightweight reference to a section for sub-section listings
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct SectionLink {
+    key: String,
+    title: String,
+    permalink: String,
+    relative_path: String,
+}
+
+/// A menu entry as templates see it, with `url` relative to the page being rendered
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct MenuLink {
+    name: String,
+    url: String,
+    children: Vec<MenuLink>,
+}
+
+/// One step of a breadcrumb trail
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct Crumb {
+    title: String,
+    permalink: String,
+}
+
+/// Builds the site from Markdown content and Tera templates
+#[derive(Debug, clap::Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    options: BuildOptions,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Build, serve the output over HTTP, and rebuild when sources change
+    Serve {
+        #[arg(long, default_value_t = DEFAULT_SERVE_PORT)]
+        port: u16,
+        /// Open the site in the default browser after the first successful build
+        #[arg(long)]
+        open: bool,
+        /// Keep the rendered site in memory instead of writing the output directory
+        #[arg(long)]
+        in_memory: bool,
+    },
+}
+
+/// Command-line switches that alter a build
+#[derive(Debug, Clone, Args)]
+struct BuildOptions {
+    #[command(flatten)]
+    paths: SitePaths,
+    /// Request every external link in the generated HTML and report dead ones
+    #[arg(long, global = true)]
+    check_external_links: bool,
+    /// Report output files added, removed, or changed since the previous build
+    #[arg(long, global = true)]
+    diff: bool,
+    /// Fail the build when a link into the site points at a file that wasn't generated
+    #[arg(long, global = true)]
+    check_links: bool,
+    /// Keep the previous output and only re-render pages whose inputs changed since the last build
+    #[arg(long, global = true)]
+    incremental: bool,
+    /// Build draft pages too, as if `drafts = true` were set in the config
+    #[arg(long, global = true)]
+    drafts: bool,
+    /// Replaces `base_url` from the config, e.g. for preview deployments; `""` makes URLs root-relative
+    #[arg(long, global = true)]
+    base_url: Option<String>,
+    /// Fail on unknown front matter keys, as if `strict_front_matter = true` were set in the config
+    #[arg(long, global = true)]
+    strict_front_matter: bool,
+    /// Fail the build on any content warning (untitled sections, duplicate slugs, invalid sitemap hints,
+    /// missing taxonomy templates, ...) and on unknown front matter keys, as `--strict-front-matter` does
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Write over the previous output instead of clearing the output directory first
+    #[arg(long, global = true)]
+    no_clean: bool,
+    /// Leave static files alone when their output copy has the same size and is at least as new
+    #[arg(long, global = true)]
+    skip_unchanged_assets: bool,
+    /// Suppress warnings and the build summary
+    #[arg(long, short, global = true)]
+    quiet: bool,
+    /// Render into memory rather than the output directory; set by `serve --in-memory`
+    #[arg(skip)]
+    in_memory: bool,
+}
+
+/// Where a build reads its inputs and writes its output
+#[derive(Debug, Clone, Args, PartialEq, Eq)]
+struct SitePaths {
+    /// Site configuration file
+    #[arg(long = "config", default_value = CONFIG_PATH, global = true)]
+    config: PathBuf,
+    /// Content directory, replacing the `content` list from the config
+    #[arg(long, global = true)]
+    content_dir: Option<PathBuf>,
+    /// Directory the site is generated into
+    #[arg(long, default_value = OUTPUT_DIR, global = true)]
+    output_dir: PathBuf,
+    /// Directory holding the Tera templates
+    #[arg(long = "templates", default_value = TEMPLATES_DIR, global = true)]
+    templates_dir: PathBuf,
+    /// Directory copied verbatim into the output
+    #[arg(long, default_value = STATIC_DIR, global = true)]
+    static_dir: PathBuf,
+}
+
+impl SitePaths {
+    /// Loads the config, applying a `--content-dir` override
+    fn load_config(&self) -> Result<Config> {
+        let mut config = load_config(&self.config)?;
+        if let Some(content_dir) = &self.content_dir {
+            config.content = vec![content_dir.clone()];
+        }
+        Ok(config)
+    }
+
+    /// Where builds with these paths keep their manifests: `.cache/<hash>` next to the config,
+    /// hashed from the output directory and config file so no two output trees share one
+    fn cache_dir(&self) -> PathBuf {
+        let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let key = format!("{}\n{}", absolute(&self.output_dir).display(), absolute(&self.config).display());
+        let site_dir = self.config.parent().unwrap_or(Path::new(""));
+        site_dir.join(CACHE_DIR).join(content_hash(key.as_bytes()))
+    }
+}
+
+impl BuildOptions {
+    /// Loads the config, applying `--content-dir`, `--base-url`, `--strict-front-matter` (which `--strict`
+    /// implies), and `--drafts`. Templates see whether drafts are being built as `config.extra.drafts_enabled`
+    fn load_config(&self) -> Result<Config> {
+        let mut config = self.paths.load_config()?;
+        if let Some(base_url) = &self.base_url {
+            config.base_url = base_url.trim_end_matches('/').to_string();
+        }
+        config.strict_front_matter |= self.strict_front_matter || self.strict;
+        config.drafts |= self.drafts;
+        config
+            .extra
+            .insert("drafts_enabled".to_string(), toml::Value::Boolean(config.drafts));
+        Ok(config)
+    }
+}
+
+/// Size and content hash of one generated file, as stored in the output manifest
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+struct OutputEntry {
+    size: u64,
+    hash: String,
+}
+
+/// Output paths added, removed, or changed between two builds
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BuildDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<String>,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    QUIET.store(cli.options.quiet, Ordering::Relaxed);
+    match cli.command {
+        Some(Command::Serve { port, open, in_memory }) => {
+            let options = BuildOptions { in_memory, ..cli.options };
+            serve(&options, port, open)
+        }
+        None => build_site(&cli.options).map(drop),
+    }
+}
+
+/// Builds once, serves the output directory over HTTP, and rebuilds whenever sources change.
+/// With `open`, the site opens in a browser once a build has succeeded. An in-memory build is
+/// served from memory, with static files coming straight from the static directory
+fn serve(options: &BuildOptions, port: u16, open: bool) -> Result<()> {
+    let paths = &options.paths;
+    let served = Arc::new(if options.in_memory {
+        Served::Memory { files: RwLock::default(), static_dir: paths.static_dir.clone() }
+    } else {
+        Served::Disk(paths.output_dir.clone())
+    });
+    // A failed build leaves the last good one in place
+    let rebuild = || build_site(options).map(|output| served.update(output));
+    let built = match rebuild() {
+        Ok(()) => true,
+        Err(error) => {
+            eprintln!("Build failed: {:#}", error);
+            false
+        }
+    };
+
+    let server = tiny_http::Server::http((SERVE_HOST, port))
+        .map_err(|error| anyhow::anyhow!("starting server on port {}: {}", port, error))?;
+    let not_found = options
+        .load_config()
+        .and_then(|config| config.error_pages())
+        .ok()
+        .and_then(|pages| pages.into_iter().find(|page| page.status == 404))
+        .map_or_else(|| "404.html".to_string(), |page| page.path);
+    let server_files = Arc::clone(&served);
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            serve_request(request, &server_files, &not_found);
+        }
+    });
+    let url = serve_url(port);
+    let description = if options.in_memory {
+        "the site from memory".to_string()
+    } else {
+        paths.output_dir.display().to_string()
+    };
+    println!("Serving {} at {} (Ctrl+C to stop)", description, url);
+    let mut open_pending = open;
+    if built && open_pending {
+        open_in_browser(&url);
+        open_pending = false;
+    }
+
+    let mut watched = paths
+        .load_config()
+        .map(|config| config.content)
+        .unwrap_or_else(|_| default_content_dirs());
+    watched.extend([&paths.templates_dir, &paths.static_dir, &paths.config].map(PathBuf::clone));
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("creating file watcher")?;
+    for path in watched.iter().filter(|path| path.exists()) {
+        notify::Watcher::watch(&mut watcher, path, notify::RecursiveMode::Recursive)
+            .with_context(|| format!("watching {:?}", path))?;
+    }
+
+    let is_change = |event: notify::Result<notify::Event>| {
+        matches!(event, Ok(event) if !matches!(event.kind, notify::EventKind::Access(_)))
+    };
+    while let Ok(event) = rx.recv() {
+        let mut changed = is_change(event);
+        while let Ok(event) = rx.recv_timeout(REBUILD_DEBOUNCE) {
+            changed |= is_change(event);
+        }
+        if !changed {
+            continue;
+        }
+        println!("Change detected, rebuilding...");
+        match rebuild() {
+            Ok(()) => {
+                println!("Rebuilt {}", description);
+                if open_pending {
+                    open_in_browser(&url);
+                    open_pending = false;
+                }
+            }
+            Err(error) => eprintln!("Build failed: {:#}", error),
+        }
+    }
+    Ok(())
+}
+
+/// Address of the dev server's home page
+fn serve_url(port: u16) -> String {
+    format!("http://{}:{}/", SERVE_HOST, port)
+}
+
+/// Launches the default browser, or just points at the URL when none can be started (e.g. over SSH)
+fn open_in_browser(url: &str) {
+    if let Err(error) = open::that_detached(url) {
+        warn!("couldn't open a browser ({}); visit {} instead", error, url);
+    }
+}
+
+/// What the dev server answers requests from
+#[derive(Debug)]
+enum Served {
+    /// The output directory on disk
+    Disk(PathBuf),
+    /// The files of the last successful in-memory build, by path relative to the output directory.
+    /// In-memory builds don't copy static files, so those come from `static_dir`
+    Memory { files: RwLock<HashMap<String, Vec<u8>>>, static_dir: PathBuf },
+}
+
+impl Served {
+    /// Starts serving the files of a finished in-memory build
+    fn update(&self, output: Output) {
+        if let (Served::Memory { files, .. }, Some(built)) = (self, output.into_memory()) {
+            *files.write().unwrap() = built;
+        }
+    }
+}
+
+/// Answers one dev-server request, falling back to the 404 page at `not_found`
+fn serve_request(request: tiny_http::Request, served: &Served, not_found: &str) {
+    let found = read_served_file(served, request.url()).map(|file| (200, file));
+    let response = match found.or_else(|| read_output_file(served, not_found).map(|file| (404, file))) {
+        Some((status, (path, bytes))) => {
+            let mut response = tiny_http::Response::from_data(bytes).with_status_code(status);
+            if let Ok(header) = tiny_http::Header::from_bytes("Content-Type", content_type(&path)) {
+                response.add_header(header);
+            }
+            response
+        }
+        None => tiny_http::Response::from_string("Not found").with_status_code(404),
+    };
+    if let Err(error) = request.respond(response) {
+        warn!("failed to send response: {}", error);
+    }
+}
+
+/// Reads the file a request URL maps to, from the output directory on disk, or from memory (then
+/// `static_dir`) for an in-memory build
+fn read_served_file(served: &Served, url: &str) -> Option<(PathBuf, Vec<u8>)> {
+    let (files, static_dir) = match served {
+        Served::Disk(output_dir) => {
+            let path = resolve_request_path(output_dir, url)?;
+            return fs::read(&path).ok().map(|bytes| (path, bytes));
+        }
+        Served::Memory { files, static_dir } => (files.read().unwrap(), static_dir),
+    };
+    let path = url.split(['?', '#']).next().unwrap_or_default().trim_matches('/');
+    let index = if path.is_empty() { "index.html".to_string() } else { format!("{}/index.html", path) };
+    if let Some((path, bytes)) = [path, index.as_str()].into_iter().find_map(|key| files.get_key_value(key)) {
+        return Some((PathBuf::from(path), bytes.clone()));
+    }
+    drop(files);
+    let path = resolve_request_path(static_dir, url)?;
+    fs::read(&path).ok().map(|bytes| (path, bytes))
+}
+
+/// Reads a generated file by its path relative to the output directory, in memory or on disk
+fn read_output_file(served: &Served, relative: &str) -> Option<(PathBuf, Vec<u8>)> {
+    match served {
+        Served::Disk(output_dir) => {
+            let path = output_dir.join(relative);
+            fs::read(&path).ok().map(|bytes| (path, bytes))
+        }
+        Served::Memory { files, .. } => {
+            files.read().unwrap().get(relative).map(|bytes| (PathBuf::from(relative), bytes.clone()))
+        }
+    }
+}
+
+/// Where one build puts the files it generates: the output directory on disk, or memory for
+/// `serve --in-memory`, in which case `dir` only names the paths
+#[derive(Debug)]
+struct Output {
+    dir: PathBuf,
+    /// Files by path relative to `dir`, when the build stays in memory
+    memory: Option<Mutex<HashMap<String, Vec<u8>>>>,
+    /// Files on disk this build has written or found current. When an incremental build ends,
+    /// whatever else is left in `dir` is stale
+    written: Mutex<HashSet<PathBuf>>,
+}
+
+impl Output {
+    fn disk(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf(), memory: None, written: Mutex::default() }
+    }
+
+    fn memory(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf(), memory: Some(Mutex::default()), written: Mutex::default() }
+    }
+
+    /// Writes one generated file at `path` under `dir`, creating its directory first
+    fn write(&self, path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+        if let Some(files) = &self.memory {
+            let relative = url_key(path.strip_prefix(&self.dir).unwrap_or(path));
+            files.lock().unwrap().insert(relative, contents.as_ref().to_vec());
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+        self.record(path);
+        Ok(())
+    }
+
+    /// Copies a source file to `dest` under `dir`, like `write`
+    fn copy(&self, source: &Path, dest: &Path) -> std::io::Result<()> {
+        if self.memory.is_some() {
+            return self.write(dest, fs::read(source)?);
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source, dest)?;
+        self.record(dest);
+        Ok(())
+    }
+
+    /// Notes that `path` is part of this build's output, written or left as it was
+    fn record(&self, path: &Path) {
+        self.written.lock().unwrap().insert(path.to_path_buf());
+    }
+
+    /// The files of an in-memory build, or `None` for one written to disk
+    fn into_memory(self) -> Option<HashMap<String, Vec<u8>>> {
+        self.memory.map(|files| files.into_inner().unwrap())
+    }
+}
+
+/// Maps a request URL onto a file in the output directory; directories resolve to their `index.html`
+fn resolve_request_path(output_dir: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let mut resolved = output_dir.to_path_buf();
+    for part in path.split('/').filter(|part| !part.is_empty()) {
+        if part == "." || part == ".." || part.contains('\\') {
+            return None;
+        }
+        resolved.push(part);
+    }
+    if resolved.is_dir() {
+        resolved.push("index.html");
+    }
+    resolved.is_file().then_some(resolved)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or_default() {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Builds the site into the output directory, or into memory with `options.in_memory`, returning
+/// where the files went
+fn build_site(options: &BuildOptions) -> Result<Output> {
+    let started = Instant::now();
+    // Every page of one build shows the same build date
+    let built_at = Utc::now();
+    let paths = &options.paths;
+    let mut config = options.load_config()?;
+    // Collected here and reported together once the content is loaded, so --strict sees them all
+    let mut warnings = Vec::new();
+    config.sitemap = validated_sitemap_hints(
+        config.sitemap.priority,
+        config.sitemap.changefreq.as_deref(),
+        &paths.config.display().to_string(),
+        &mut warnings,
+    );
+    let templates_glob = paths.templates_dir.join("**").join("*");
+    let mut tera = Tera::new(&templates_glob.to_string_lossy())
+        .map_err(tera_error_chain)
+        .context("loading templates")?;
+    register_filters(&mut tera);
+    add_builtin_templates(&mut tera, &config)?;
+
+    let output_dir = paths.output_dir.as_path();
+
+    let build_manifest_path = paths.cache_dir().join(BUILD_MANIFEST_FILE);
+    // In-memory builds always start from nothing and never touch the output directory
+    let previous_build = if options.incremental && !options.in_memory {
+        load_build_manifest(&build_manifest_path)?
+    } else {
+        None
+    };
+    let output = if options.in_memory {
+        Output::memory(output_dir)
+    } else {
+        // Incremental builds keep the old output and delete stale pages themselves
+        if output_dir.exists() && previous_build.is_none() && !options.no_clean {
+            clean_output_dir(output_dir, &config.keep_files)?;
+        }
+        fs::create_dir_all(output_dir).context("creating output directory")?;
+        Output::disk(output_dir)
+    };
+
+    // The dev server serves static files from the static directory itself for in-memory builds
+    let (assets, assets_copied) = if options.in_memory {
+        (AssetManifest::new(), 0)
+    } else {
+        copy_static_assets(
+            &paths.static_dir,
+            &output,
+            config.extra_bool("fingerprint_assets"),
+            options.skip_unchanged_assets,
+        )?
+    };
+    let assets_json = serde_json::to_string(&assets).context("serializing asset manifest")?;
+    register_asset_url(&mut tera, assets);
+
+    let (root_section, sections, content_warnings) = load_content(&config.content, &config, &tera)?;
+    let assets_copied = assets_copied + copy_content_assets(&config.content, &config.markdown_extensions, &output)?;
+    let tags = collect_tags(&sections);
+    let categories = collect_categories(&sections);
+
+    // Validate and warn about potential issues, or fail on them with --strict
+    warnings.extend(content_warnings);
+    warnings.extend(validate_content(&sections));
+    warnings.extend(missing_taxonomy_templates(&tera, &TAGS, &tags));
+    warnings.extend(missing_taxonomy_templates(&tera, &CATEGORIES, &categories));
+    warnings.extend(undated_archive_pages(&tera, &sections));
+    report_content_warnings(&warnings, options.strict)?;
+    check_path_consistency(&sections, &config.base_url)?;
+    let static_dir = (!options.in_memory).then_some(paths.static_dir.as_path());
+    check_output_collisions(&tera, &sections, &config, static_dir)?;
+    check_templates_exist(&tera, &config, &sections)?;
+
+    let nav = nav_sections(&sections, &config);
+    // Going through a Value sorts map keys, so `[extra]` hashes the same from one run to the next
+    let global_inputs = serde_json::to_value((&config, &nav))
+        .context("serializing site settings")?
+        .to_string()
+        + &assets_json;
+    // Only incremental builds compare inputs with the last build, and keep them for the next
+    let build = if options.incremental && !options.in_memory {
+        Some(build_manifest(&tera, &sections, content_hash(global_inputs.as_bytes()))?)
+    } else {
+        None
+    };
+    let dirty = match (&previous_build, &build) {
+        (Some(previous), Some(build)) => pages_to_render(previous, build, &tera).map(|mut dirty| {
+            // Pages deleted from the output since the last build come back too
+            dirty.extend(build.pages.keys().filter(|path| !output_dir.join(path).exists()).cloned());
+            dirty
+        }),
+        _ => None,
+    };
+
+    render_home(&tera, &config, &sections, &nav, built_at, &output, &root_section)?;
+    let sections_rendered = render_sections(&tera, &config, &sections, &nav, built_at, &output)?;
+    let pages_rendered = render_pages(&tera, &config, &sections, &nav, built_at, &output, dirty.as_ref())?;
+    render_taxonomy(&tera, &config, &TAGS, &tags, &nav, built_at, &output)?;
+    render_taxonomy(&tera, &config, &CATEGORIES, &categories, &nav, built_at, &output)?;
+    render_archive(&tera, &config, &sections, &nav, built_at, &output)?;
+    render_error_pages(&tera, &config, &nav, built_at, &output)?;
+    render_feed(&config, &sections, &output)?;
+    render_atom_feed(&config, &sections, &output)?;
+    render_json_feed(&config, &sections, &output)?;
+    render_sitemap(&config, &sections, &output)?;
+    render_robots(&config, &paths.static_dir, &output)?;
+    if config.extra_bool("build_search_index") {
+        render_search_index(&sections, &output)?;
+    }
+    if config.extra_bool("section_json") {
+        render_section_json(&config, &sections, &output)?;
+    }
+    render_syntax_css(&config, &output)?;
+
+    // Building over earlier output leaves behind whatever this build didn't write
+    if let (Some(_), Some(build)) = (&previous_build, &build) {
+        // Pages this build didn't need to re-render are still current
+        for (path, record) in &build.pages {
+            output.record(&output_dir.join(path));
+            if config.plain_pages {
+                output.record(&output_dir.join(plain_output_path(path)));
+            }
+            for alias in &record.aliases {
+                output.record(&output_dir.join(alias));
+            }
+        }
+        remove_stale_outputs(output_dir, &output.written.lock().unwrap(), &config.keep_files)?;
+    }
+
+    let output_files = match &output.memory {
+        Some(files) => files.lock().unwrap().len(),
+        None => {
+            // Hashing every output file is only worth it when the diff is asked for
+            if options.diff {
+                let manifest_path = paths.cache_dir().join(OUTPUT_MANIFEST_FILE);
+                let manifest = snapshot_output(output_dir)?;
+                match load_output_manifest(&manifest_path)? {
+                    Some(previous) => print_build_diff(&diff_output_manifests(&previous, &manifest)),
+                    None => eprintln!("No previous build manifest found; recording one for the next --diff"),
+                }
+                save_output_manifest(&manifest_path, &manifest)?;
+            }
+            if let Some(build) = &build {
+                save_build_manifest(&build_manifest_path, build)?;
+            }
+
+            if options.check_links {
+                check_internal_links(output_dir, &config.base_url)?;
+            }
+            if options.check_external_links {
+                report_dead_external_links(output_dir, &config)?;
+            }
+            count_output_files(output_dir)
+        }
+    };
+
+    if !QUIET.load(Ordering::Relaxed) {
+        let pages = match (&dirty, &build) {
+            (Some(_), Some(build)) => format!("{} of {} pages re-rendered", pages_rendered, build.pages.len()),
+            _ => format!("{} pages", pages_rendered),
+        };
+        eprintln!(
+            "Built {} sections, {}, {} static assets ({} output files) in {:.2?}",
+            sections_rendered,
+            pages,
+            assets_copied,
+            output_files,
+            started.elapsed()
+        );
+    }
+
+    Ok(output)
+}
+
+/// Removes the output directory, putting back any of the `keep` files it held
+fn clean_output_dir(output_dir: &Path, keep: &[PathBuf]) -> Result<()> {
+    let mut kept = Vec::new();
+    for relative in keep {
+        let path = output_dir.join(relative);
+        if path.is_file() {
+            let bytes = fs::read(&path).with_context(|| format!("reading kept file {:?}", path))?;
+            kept.push((path, bytes));
+        }
+    }
+    fs::remove_dir_all(output_dir).context("clearing output directory")?;
+    for (path, bytes) in kept {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("creating directory for {:?}", path))?;
+        }
+        fs::write(&path, bytes).with_context(|| format!("restoring kept file {:?}", path))?;
+    }
+    Ok(())
+}
+
+fn load_config(path: &Path) -> Result<Config> {
+    let contents = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut config: Config =
+        toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))?;
+    config.base_url = config.base_url.trim_end_matches('/').to_string();
+    // Kept files are put back and spared from deletion, so they must stay inside the output
+    if let Some(keep) = config.keep_files.iter().find(|keep| {
+        keep.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    }) {
+        anyhow::bail!("keep_files entry {:?} in {} must be a path inside the output directory", keep, path.display());
+    }
+    if std::env::var("SITE_DRAFTS").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true")) {
+        config.drafts = true;
+    }
+    Ok(config)
+}
+
+/// Static asset path -> path it is served from, both relative to the output directory
+type AssetManifest = BTreeMap<String, String>;
+
+/// Copies static files into the output. With `fingerprint`, each file also gets a
+/// content-hashed copy (`css/main.<hash>.css`) and the mapping is written to `manifest.json`.
+/// The plain copy stays for fixed URLs such as `favicon.ico`. With `skip_unchanged`, files whose
+/// output copy already matches are left alone, and keep the hash the last `manifest.json` gave
+/// them instead of being read again. Also returns how many static assets the site has
+fn copy_static_assets(
+    static_dir: &Path,
+    output: &Output,
+    fingerprint: bool,
+    skip_unchanged: bool,
+) -> Result<(AssetManifest, usize)> {
+    let mut manifest = AssetManifest::new();
+    if !static_dir.exists() {
+        return Ok((manifest, 0));
+    }
+    let previous: AssetManifest = if fingerprint && skip_unchanged {
+        fs::read_to_string(output.dir.join(ASSET_MANIFEST_PATH))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    } else {
+        AssetManifest::new()
+    };
+
+    for entry in WalkDir::new(static_dir).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() {
+            let relative = path.strip_prefix(static_dir).unwrap();
+            let dest = output.dir.join(relative);
+            let up_to_date = skip_unchanged && is_up_to_date(path, &dest);
+            if up_to_date {
+                output.record(&dest);
+            } else {
+                output.copy(path, &dest)?;
+            }
+
+            let relative = url_key(relative);
+            let unchanged_hash = previous
+                .get(&relative)
+                .filter(|hashed| up_to_date && output.dir.join(hashed).is_file());
+            let served = if let Some(hashed) = unchanged_hash {
+                output.record(&output.dir.join(hashed));
+                hashed.clone()
+            } else if fingerprint {
+                let bytes = fs::read(path).with_context(|| format!("reading static asset {:?}", path))?;
+                let hashed = fingerprinted_path(&relative, &bytes);
+                let hashed_dest = output.dir.join(&hashed);
+                // The name changes with the content, so an existing file is already current
+                if skip_unchanged && hashed_dest.is_file() {
+                    output.record(&hashed_dest);
+                } else {
+                    output.write(&hashed_dest, bytes)?;
+                }
+                hashed
+            } else {
+                relative.clone()
+            };
+            manifest.insert(relative, served);
+        }
+    }
+
+    if fingerprint {
+        let contents = serde_json::to_string_pretty(&manifest).context("serializing asset manifest")?;
+        output.write(&output.dir.join(ASSET_MANIFEST_PATH), contents).context("writing asset manifest")?;
+    }
+    let count = manifest.len();
+    Ok((manifest, count))
+}
+
+/// True when `dest` has the same size as `source` and was written no earlier than `source` changed
+fn is_up_to_date(source: &Path, dest: &Path) -> bool {
+    let (Ok(source_meta), Ok(dest_meta)) = (fs::metadata(source), fs::metadata(dest)) else {
+        return false;
+    };
+    let (Some(source_mtime), Some(dest_mtime)) = (file_mtime(source), file_mtime(dest)) else {
+        return false;
+    };
+    source_meta.len() == dest_meta.len() && dest_mtime >= source_mtime
+}
+
+/// Copies every non-Markdown file in the content directories to the same relative path in the
+/// output, so images can sit next to the posts that use them. Returns how many files were copied
+fn copy_content_assets(content_dirs: &[PathBuf], markdown_extensions: &[String], output: &Output) -> Result<usize> {
+    let mut copied = 0;
+    for content_dir in content_dirs {
+        for entry in WalkDir::new(content_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && !is_markdown_file(e.path(), markdown_extensions))
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        {
+            let path = entry.path();
+            let relative = path.strip_prefix(content_dir).context("stripping content prefix")?;
+            let dest = output.dir.join(content_asset_path(relative));
+            output.copy(path, &dest).with_context(|| format!("copying content asset {:?}", path))?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// Where a content asset lands, relative to the output: assets follow their section's slugged
+/// directory but keep their own file name
+fn content_asset_path(relative: &Path) -> PathBuf {
+    Path::new(&relative.parent().map(section_key).unwrap_or_default()).join(relative.file_name().unwrap_or_default())
+}
+
+/// Inserts the first 8 hex digits of the content hash before the extension: `css/main.1a2b3c4d.css`
+fn fingerprinted_path(relative: &str, bytes: &[u8]) -> String {
+    let hash = &content_hash(bytes)[..8];
+    let (dir, file) = match relative.rsplit_once('/') {
+        Some((dir, file)) => (format!("{}/", dir), file),
+        None => (String::new(), relative),
+    };
+    match file.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{}{}.{}.{}", dir, stem, hash, extension),
+        _ => format!("{}{}.{}", dir, file, hash),
+    }
+}
+
+/// Registers `asset_url(path="css/main.css")`, which returns the fingerprinted path when there is one.
+/// Like `relative_path`, the result is relative to the site root, so prefix it with `path_prefix`
+fn register_asset_url(tera: &mut Tera, manifest: AssetManifest) {
+    tera.register_function("asset_url", move |args: &HashMap<String, tera::Value>| {
+        let path = args
+            .get("path")
+            .and_then(tera::Value::as_str)
+            .ok_or_else(|| tera::Error::msg("asset_url needs a `path` string argument"))?
+            .trim_start_matches('/');
+        Ok(tera::Value::String(
+            manifest.get(path).cloned().unwrap_or_else(|| path.to_string()),
+        ))
+    });
+}
+
+/// Fills in templates the site can run without providing: `plain.html` when `plain_pages` is on
+fn add_builtin_templates(tera: &mut Tera, config: &Config) -> Result<()> {
+    if config.plain_pages && !tera.get_template_names().any(|name| name == PLAIN_TEMPLATE) {
+        tera.add_raw_template(PLAIN_TEMPLATE, DEFAULT_PLAIN_TEMPLATE)
+            .map_err(tera_error_chain)
+            .context("adding the built-in plain.html")?;
+    }
+    Ok(())
+}
+
+/// Registers the site's template filters:
+/// - `date_format(fmt="%B %-d, %Y")` formats a date with chrono; missing dates become `""`
+/// - `slugify` applies the same slug rules as page URLs
+/// - `truncate_words(n=30)` keeps the first `n` words, ending in `…` when anything was cut
+fn register_filters(tera: &mut Tera) {
+    tera.register_filter("date_format", |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+        let fmt = args
+            .get("fmt")
+            .and_then(tera::Value::as_str)
+            .ok_or_else(|| tera::Error::msg("date_format needs a `fmt` string argument"))?;
+        let text = match value {
+            tera::Value::Null => return Ok(tera::Value::String(String::new())),
+            tera::Value::String(text) => text,
+            other => return Err(tera::Error::msg(format!("date_format expects a date, got {}", other))),
+        };
+        let items: Vec<_> = chrono::format::StrftimeItems::new(fmt).collect();
+        if items.contains(&chrono::format::Item::Error) {
+            return Err(tera::Error::msg(format!("date_format: invalid format '{}'", fmt)));
+        }
+        let formatted = if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+            date.format_with_items(items.into_iter()).to_string()
+        } else if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(text) {
+            datetime.format_with_items(items.into_iter()).to_string()
+        } else {
+            return Err(tera::Error::msg(format!("date_format: '{}' is not a date", text)));
+        };
+        Ok(tera::Value::String(formatted))
+    });
+
+    tera.register_filter("slugify", |value: &tera::Value, _: &HashMap<String, tera::Value>| {
+        let text = value
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("slugify expects a string"))?;
+        Ok(tera::Value::String(slugify(text)))
+    });
+
+    tera.register_filter("truncate_words", |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+        let text = value
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("truncate_words expects a string"))?;
+        let limit = args
+            .get("n")
+            .and_then(tera::Value::as_u64)
+            .ok_or_else(|| tera::Error::msg("truncate_words needs a whole-number `n` argument"))?;
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+        if words.len() <= limit {
+            return Ok(tera::Value::String(words.join(" ")));
+        }
+        Ok(tera::Value::String(format!("{}…", words[..limit].join(" "))))
+    });
+}
+
+fn parse_front_matter(content: &str) -> Result<(FrontMatter, String)> {
+    let mut lines = content.lines();
+    let first_line = lines.next().unwrap_or("");
+
+    // `+++` fences TOML front matter, `---` fences YAML (Jekyll/Hugo style)
+    let delimiter = match first_line.trim() {
+        "+++" => "+++",
+        "---" => "---",
+        _ => return Ok((FrontMatter::default(), content.to_string())),
+    };
+
+    let mut front_matter = String::new();
+    let mut body = String::new();
+    let mut in_front_matter = true;
+
+    for line in lines {
+        if in_front_matter && line.trim() == delimiter {
+            in_front_matter = false;
+            continue;
+        }
+
+        if in_front_matter {
+            front_matter.push_str(line);
+            front_matter.push('\n');
+        } else {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+
+    let mut data: FrontMatter = if delimiter == "---" {
+        // An empty YAML document deserializes as null rather than an empty map
+        if front_matter.trim().is_empty() {
+            FrontMatter::default()
+        } else {
+            serde_yaml::from_str(&front_matter).context("parsing frontmatter YAML")?
+        }
+    } else {
+        match toml::from_str(&front_matter) {
+            Ok(data) => data,
+            // Loose dates like `date = 2025-1-5` aren't valid TOML; retry with them quoted
+            // so the date deserializer can coerce them, and report the original error otherwise
+            Err(err) => match quote_bare_dates(&front_matter) {
+                Some(quoted) => toml::from_str(&quoted)
+                    .or(Err(err))
+                    .context("parsing frontmatter TOML")?,
+                None => return Err(err).context("parsing frontmatter TOML"),
+            },
+        }
+    };
+    data.date = data.published.map(|published| published.date_naive());
+    Ok((data, body))
+}
+
+/// Front matter keys holding dates
+const DATE_FIELDS: &[&str] = &["date"];
+
+/// Date formats accepted in addition to ISO `YYYY-MM-DD`
+const LOOSE_DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d", "%Y/%m/%d", "%b %d %Y", "%b %d, %Y", "%B %d %Y", "%B %d, %Y", "%d %b %Y", "%d %B %Y",
+];
+
+/// Parses a date written in any of the accepted formats
+fn parse_loose_date(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    LOOSE_DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+}
+
+/// Accepts TOML dates as well as date strings in any of `LOOSE_DATE_FORMATS`
+fn deserialize_optional_date<'de, D>(deserializer: D) -> std::result::Result<Option<NaiveDate>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let value = match Option::<toml::Value>::deserialize(deserializer)? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let text = match value {
+        toml::Value::Datetime(datetime) => match datetime.date {
+            Some(date) => return NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())
+                .map(Some)
+                .ok_or_else(|| D::Error::custom(format!("invalid date '{}'", datetime))),
+            None => datetime.to_string(),
+        },
+        toml::Value::String(text) => text,
+        other => other.to_string(),
+    };
+
+    parse_loose_date(&text).map(Some).ok_or_else(|| {
+        D::Error::custom(format!(
+            "invalid date '{}': expected YYYY-MM-DD (also accepted: \"Jan 5 2025\", \"January 5, 2025\", \"5 Jan 2025\", \"2025/01/05\")",
+            text
+        ))
+    })
+}
+
+/// Collects unrecognised front matter keys, dropping YAML nulls since TOML has no null
+fn deserialize_extra<'de, D>(deserializer: D) -> std::result::Result<HashMap<String, toml::Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(HashMap::<String, Option<toml::Value>>::deserialize(deserializer)?
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect())
+}
+
+/// Keys `FrontMatter` reads itself, aliases included
+const FRONT_MATTER_KEYS: &[&str] = &[
+    "title", "description", "slug", "aliases", "template", "default_template", "date", "updated", "last_modified",
+    "summary", "image", "lang", "toc", "toc_depth", "paginate_by", "output_filename", "sort_by", "weight",
+    "nav_order", "visibility", "draft", "tags", "category", "related", "sitemap_priority", "sitemap_changefreq",
+    "extra",
+];
+
+/// How similar an unknown key must be to a known one to be reported as a likely typo
+const KEY_SUGGESTION_THRESHOLD: f64 = 0.7;
+
+/// Top-level front matter keys the generator doesn't read, sorted, each with the known key it
+/// most resembles when it looks like a typo
+fn unknown_front_matter_keys(meta: &FrontMatter) -> Vec<(&str, Option<&'static str>)> {
+    let mut unknown: Vec<_> = meta
+        .extra
+        .keys()
+        .filter(|key| key.as_str() != "extra")
+        .map(|key| {
+            let suggestion = FRONT_MATTER_KEYS
+                .iter()
+                .map(|known| (strsim::normalized_damerau_levenshtein(key, known), *known))
+                .filter(|(score, _)| *score >= KEY_SUGGESTION_THRESHOLD)
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, known)| known);
+            (key.as_str(), suggestion)
+        })
+        .collect();
+    unknown.sort();
+    unknown
+}
+
+/// Warnings for unknown front matter keys that look like typos of known ones. With
+/// `strict_front_matter`, any unknown top-level key is an error instead, so custom
+/// template data has to live in an `[extra]` table
+fn check_front_matter_keys(meta: &FrontMatter, path: &Path, strict: bool) -> Result<Vec<Warning>> {
+    let unknown = unknown_front_matter_keys(meta);
+    let describe = |(key, suggestion): &(&str, Option<&str>)| match suggestion {
+        Some(known) => format!("`{}` (did you mean `{}`?)", key, known),
+        None => format!("`{}`", key),
+    };
+    if strict && !unknown.is_empty() {
+        anyhow::bail!(
+            "unknown front matter keys in {:?}: {}; move custom data under [extra]",
+            path,
+            unknown.iter().map(describe).collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(unknown
+        .into_iter()
+        .filter_map(|(key, suggestion)| {
+            suggestion.map(|known| Warning::UnknownFrontMatterKey {
+                source: path.display().to_string(),
+                key: key.to_string(),
+                suggestion: known.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Datetime formats without an offset accepted for `date`, read as UTC
+const NAIVE_DATETIME_FORMATS: &[&str] =
+    &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"];
+
+/// Parses an RFC 3339 datetime, an offset-free datetime (taken as UTC), or any date
+/// `parse_loose_date` accepts (taken as midnight UTC)
+fn parse_loose_datetime(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .or_else(|| {
+            NAIVE_DATETIME_FORMATS
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                .map(|datetime| datetime.and_utc().fixed_offset())
+        })
+        .or_else(|| parse_loose_date(value).map(|date| midnight_utc(date).fixed_offset()))
+}
+
+/// Like `deserialize_optional_date`, but keeps the time of day and offset of TOML datetimes
+/// and RFC 3339 strings
+fn deserialize_optional_datetime<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<DateTime<FixedOffset>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let text = match Option::<toml::Value>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(toml::Value::Datetime(datetime)) if datetime.date.is_some() => {
+            let invalid = || D::Error::custom(format!("invalid date '{}'", datetime));
+            let date = datetime.date.unwrap();
+            let day = NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())
+                .ok_or_else(invalid)?;
+            let time = match datetime.time {
+                Some(time) => {
+                    NaiveTime::from_hms_nano_opt(time.hour.into(), time.minute.into(), time.second.into(), time.nanosecond)
+                        .ok_or_else(invalid)?
+                }
+                None => NaiveTime::MIN,
+            };
+            let offset_minutes = match datetime.offset {
+                Some(toml::value::Offset::Custom { minutes }) => i32::from(minutes),
+                Some(toml::value::Offset::Z) | None => 0,
+            };
+            let offset = FixedOffset::east_opt(offset_minutes * 60).ok_or_else(invalid)?;
+            return offset.from_local_datetime(&day.and_time(time)).single().map(Some).ok_or_else(invalid);
+        }
+        Some(toml::Value::String(text)) => text,
+        Some(other) => other.to_string(),
+    };
+
+    parse_loose_datetime(&text).map(Some).ok_or_else(|| {
+        D::Error::custom(format!(
+            "invalid date '{}': expected YYYY-MM-DD or an RFC 3339 datetime such as 2025-01-05T09:30:00Z \
+             (also accepted: \"Jan 5 2025\", \"January 5, 2025\", \"5 Jan 2025\", \"2025/01/05\")",
+            text
+        ))
+    })
+}
+
+/// Wraps unquoted values of date keys in quotes, keeping any trailing `# comment` outside them.
+/// Returns None when nothing needed quoting
+fn quote_bare_dates(front_matter: &str) -> Option<String> {
+    let mut changed = false;
+    let quoted: Vec<String> = front_matter
+        .lines()
+        .map(|line| {
+            let Some((key, value)) = line.split_once('=') else {
+                return line.to_string();
+            };
+            let (value, comment) = match value.split_once('#') {
+                Some((value, comment)) => (value.trim(), format!(" #{}", comment)),
+                None => (value.trim(), String::new()),
+            };
+            let is_bare = !value.is_empty() && !value.starts_with(['"', '\'', '[', '{']);
+            if DATE_FIELDS.contains(&key.trim()) && is_bare {
+                changed = true;
+                format!("{} = \"{}\"{}", key.trim(), value, comment)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    changed.then(|| quoted.join("\n"))
+}
+
+fn markdown_to_html(markdown: &str, settings: &MarkdownSettings) -> String {
+    render_markdown(markdown, settings).html
+}
+
+fn render_markdown(markdown: &str, settings: &MarkdownSettings) -> RenderedMarkdown {
+    let options = settings.features.options();
+
+    let (markdown, math) = if settings.math {
+        protect_math(markdown)
+    } else {
+        (markdown.to_string(), Vec::new())
+    };
+
+    let mut headings = Vec::new();
+    let mut events: Vec<Event> = Parser::new_ext(&markdown, options).collect();
+    if settings.features.emoji {
+        events = expand_emoji(events);
+    }
+    events = add_heading_anchors(events, &settings.anchors, &mut headings);
+    if settings.external_links_new_tab {
+        events = mark_external_links(events, &settings.base_url);
+    }
+    if settings.images.lazy_loading || settings.images.dimensions {
+        events = add_image_attributes(events, settings);
+    }
+    // Only images left as image tags (no attributes added) still need their URLs rewritten
+    if let Some(image_dir) = &settings.image_dir {
+        events = absolutize_image_urls(events, &settings.base_url, image_dir);
+    }
+    if settings.highlight_code {
+        events = highlight_code_blocks(events);
+    }
+    events = number_footnotes(events);
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+    for (index, span) in math.iter().enumerate() {
+        let placeholder = math_placeholder(index);
+        html_output = html_output.replacen(&placeholder, &span.html(), 1);
+        for heading in &mut headings {
+            heading.text = heading.text.replace(&placeholder, &span.tex);
+        }
+    }
+    if settings.sanitize_html {
+        html_output = sanitize_html(&html_output);
+    }
+    RenderedMarkdown {
+        html: html_output,
+        headings,
+    }
+}
+
+/// Keeps only allowlisted tags and attributes (ammonia's defaults plus what the renderer itself emits:
+/// classes and ids, task-list checkboxes, link targets, table alignment, the footnotes section), so raw HTML in Markdown
+/// can't run scripts. Links also get `rel="noopener noreferrer"`
+fn sanitize_html(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_generic_attributes(["class", "id"])
+        .add_tags(["input", "section"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("a", ["target", "aria-label"])
+        .add_tag_attributes("img", ["loading", "decoding"])
+        .add_tag_attributes("th", ["style"])
+        .add_tag_attributes("td", ["style"])
+        .clean(html)
+        .to_string()
+}
+
+/// A `$...$` or `$$...$$` span lifted out of Markdown before parsing
+#[derive(Debug)]
+struct MathSpan {
+    tex: String,
+    display: bool,
+}
+
+impl MathSpan {
+    /// The element KaTeX's auto-render picks up, e.g. `<span class="math inline">a^2</span>`
+    fn html(&self) -> String {
+        let class = if self.display { "display" } else { "inline" };
+        format!("<span class=\"math {}\">{}</span>", class, escape_html(&self.tex))
+    }
+}
+
+fn math_placeholder(index: usize) -> String {
+    format!("\u{E001}{}\u{E001}", index)
+}
+
+/// Replaces math in prose with placeholders so emphasis and escapes can't mangle it; code is
+/// left alone. Inline `$` must hug its content, as in Pandoc, so prices like `$5 and $10` stay text
+fn protect_math(markdown: &str) -> (String, Vec<MathSpan>) {
+    let mut spans = Vec::new();
+    let mut source = String::with_capacity(markdown.len());
+    for segment in markdown_segments(markdown) {
+        let text = match segment {
+            MarkdownSegment::Code(code) => {
+                source.push_str(code);
+                continue;
+            }
+            MarkdownSegment::Text(text) => text,
+        };
+
+        let mut rest = text;
+        while let Some(open) = rest.find('$') {
+            let escaped = rest[..open].ends_with('\\');
+            let display = rest[open..].starts_with("$$");
+            let delimiter = if display { "$$" } else { "$" };
+            let body = &rest[open + delimiter.len()..];
+            let close = if escaped {
+                None
+            } else if display {
+                body.find("$$")
+            } else {
+                inline_math_end(body)
+            };
+            match close {
+                Some(close) if close > 0 => {
+                    source.push_str(&rest[..open]);
+                    source.push_str(&math_placeholder(spans.len()));
+                    spans.push(MathSpan {
+                        tex: body[..close].trim().to_string(),
+                        display,
+                    });
+                    rest = &body[close + delimiter.len()..];
+                }
+                _ => {
+                    source.push_str(&rest[..open + delimiter.len()]);
+                    rest = body;
+                }
+            }
+        }
+        source.push_str(rest);
+    }
+    (source, spans)
+}
+
+/// Byte offset of the `$` closing inline math that starts at `body`, if there is one
+fn inline_math_end(body: &str) -> Option<usize> {
+    if body.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut previous = None;
+    for (index, c) in body.char_indices() {
+        match c {
+            '\n' if previous == Some('\n') => return None,
+            '$' if previous != Some('\\') && !previous.is_some_and(char::is_whitespace) => {
+                let next = body[index + 1..].chars().next();
+                return (!next.is_some_and(|c| c.is_ascii_digit())).then_some(index);
+            }
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    None
+}
+
+/// Nests headings from h2 down to `max_level` into a table of contents, in document order
+fn build_toc(headings: &[Heading], max_level: u8) -> Vec<TocEntry> {
+    fn insert(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+        match entries.last_mut() {
+            Some(last) if entry.level > last.level => insert(&mut last.children, entry),
+            _ => entries.push(entry),
+        }
+    }
+
+    let mut toc = Vec::new();
+    for heading in headings.iter().filter(|h| h.level <= u32::from(max_level)) {
+        insert(&mut toc, TocEntry {
+            level: heading.level,
+            title: heading.text.clone(),
+            id: heading.id.clone(),
+            children: Vec::new(),
+        });
+    }
+    toc
+}
+
+/// A heading whose inner events are buffered until its end tag
+struct PendingHeading<'a> {
+    level: HeadingLevel,
+    id: Option<String>,
+    classes: Vec<String>,
+    inner: Vec<Event<'a>>,
+}
+
+/// Gives every `<h2>`–`<h6>` a slugified `id` (deduplicated with numeric suffixes) and, unless
+/// `anchors.symbol` is `none`, an `<a class="anchor">` link before or after the text so readers
+/// can deep-link to it. `<h1>` is left alone since pages already render their title at that level.
+/// Each heading is recorded in `headings`
+fn add_heading_anchors<'a>(
+    events: Vec<Event<'a>>,
+    anchors: &AnchorsConfig,
+    headings: &mut Vec<Heading>,
+) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut used_ids = HashSet::new();
+    let mut heading: Option<PendingHeading> = None;
+
+    for event in events {
+        match (&mut heading, event) {
+            (None, Event::Start(Tag::Heading { level, id, classes, .. })) if level != HeadingLevel::H1 => {
+                heading = Some(PendingHeading {
+                    level,
+                    id: id.map(|id| id.to_string()),
+                    classes: classes.iter().map(|c| c.to_string()).collect(),
+                    inner: Vec::new(),
+                });
+            }
+            (Some(pending), Event::End(TagEnd::Heading(_))) => {
+                let text: String = pending
+                    .inner
+                    .iter()
+                    .filter_map(|e| match e {
+                        Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                let base_id = pending.id.clone().unwrap_or_else(|| {
+                    let slug = slugify(&text);
+                    if slug.is_empty() { "section".to_string() } else { slug }
+                });
+                let id = unique_heading_id(&base_id, &mut used_ids);
+                headings.push(Heading {
+                    level: pending.level as u32,
+                    text: text.clone(),
+                    id: id.clone(),
+                });
+                let class_attr = if pending.classes.is_empty() {
+                    String::new()
+                } else {
+                    format!(" class=\"{}\"", escape_html(&pending.classes.join(" ")))
+                };
+
+                output.push(Event::Html(
+                    format!("<{} id=\"{}\"{}>", pending.level, escape_html(&id), class_attr).into(),
+                ));
+                let link = (anchors.symbol != "none").then(|| {
+                    format!(
+                        "<a class=\"anchor\" href=\"#{}\" aria-label=\"Link to this section\">{}</a>",
+                        escape_html(&id),
+                        anchors.symbol
+                    )
+                });
+                match (&link, anchors.position) {
+                    (Some(link), AnchorPosition::Before) => {
+                        output.push(Event::InlineHtml(format!("{} ", link).into()));
+                        output.append(&mut pending.inner);
+                        output.push(Event::InlineHtml(format!("</{}>\n", pending.level).into()));
+                    }
+                    (Some(link), AnchorPosition::After) => {
+                        output.append(&mut pending.inner);
+                        output.push(Event::InlineHtml(format!(" {}</{}>\n", link, pending.level).into()));
+                    }
+                    (None, _) => {
+                        output.append(&mut pending.inner);
+                        output.push(Event::InlineHtml(format!("</{}>\n", pending.level).into()));
+                    }
+                }
+                heading = None;
+            }
+            (Some(pending), event) => pending.inner.push(event),
+            (None, event) => output.push(event),
+        }
+    }
+    output
+}
+
+/// Returns `base` the first time it is seen, then `base-1`, `base-2`, ...
+fn unique_heading_id(base: &str, used_ids: &mut HashSet<String>) -> String {
+    let mut candidate = base.to_string();
+    let mut suffix = 0;
+    while used_ids.contains(&candidate) {
+        suffix += 1;
+        candidate = format!("{}-{}", base, suffix);
+    }
+    used_ids.insert(candidate.clone());
+    candidate
+}
+
+/// Replaces `:name:` emoji shortcodes in text, leaving code spans and blocks alone
+fn expand_emoji(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut in_code_block = false;
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code_block = true;
+                event
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                event
+            }
+            Event::Text(text) if !in_code_block && text.contains(':') => {
+                Event::Text(replace_emoji_shortcodes(&text).into())
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// Swaps every known GitHub emoji shortcode for its emoji; unknown names are kept as written
+fn replace_emoji_shortcodes(text: &str) -> String {
+    let mut replaced = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find(':') {
+        replaced.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let emoji = after.find(':').and_then(|close| {
+            let name = &after[..close];
+            let valid = !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-'));
+            valid.then(|| emojis::get_by_shortcode(name)).flatten().map(|emoji| (emoji, close))
+        });
+        match emoji {
+            Some((emoji, close)) => {
+                replaced.push_str(emoji.as_str());
+                rest = &after[close + 1..];
+            }
+            None => {
+                replaced.push(':');
+                rest = after;
+            }
+        }
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+/// Footnote numbers in order of first reference, and how often each label has been referenced
+#[derive(Default)]
+struct FootnoteNumbers<'a> {
+    order: Vec<CowStr<'a>>,
+    references: HashMap<CowStr<'a>, usize>,
+}
+
+impl<'a> FootnoteNumbers<'a> {
+    /// Numbers a reference to `label`, giving each reference its own id for the backrefs
+    fn reference(&mut self, label: CowStr<'a>) -> Event<'a> {
+        let nth = self.references.entry(label.clone()).or_insert(0);
+        *nth += 1;
+        let nth = *nth;
+        if nth == 1 {
+            self.order.push(label.clone());
+        }
+        let number = self.order.iter().position(|known| *known == label).unwrap_or_default() + 1;
+        Event::InlineHtml(
+            format!(
+                "<sup class=\"footnote-reference\" id=\"{}\"><a href=\"#fn-{}\">{}</a></sup>",
+                footnote_reference_id(number, nth),
+                number,
+                number
+            )
+            .into(),
+        )
+    }
+}
+
+/// `fnref-2` for the first reference to footnote 2, `fnref-2-3` for the third
+fn footnote_reference_id(number: usize, nth: usize) -> String {
+    if nth == 1 {
+        format!("fnref-{}", number)
+    } else {
+        format!("fnref-{}-{}", number, nth)
+    }
+}
+
+/// Replaces pulldown's footnote markup: references become `1`, `2`, ... in order of first use and
+/// the definitions move into a closing `<section class="footnotes">` list whose entries link back
+/// to every reference. Definitions nothing refers to are dropped
+fn number_footnotes<'a>(events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+    let mut body = Vec::new();
+    let mut definitions: HashMap<CowStr<'a>, Vec<Event<'a>>> = HashMap::new();
+    let mut current: Option<(CowStr<'a>, Vec<Event<'a>>)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::FootnoteDefinition(label)) => current = Some((label, Vec::new())),
+            Event::End(TagEnd::FootnoteDefinition) => {
+                if let Some((label, inner)) = current.take() {
+                    definitions.entry(label).or_insert(inner);
+                }
+            }
+            event => match &mut current {
+                Some((_, inner)) => inner.push(event),
+                None => body.push(event),
+            },
+        }
+    }
+
+    let mut numbers = FootnoteNumbers::default();
+    let number_references = |events: Vec<Event<'a>>, numbers: &mut FootnoteNumbers<'a>| -> Vec<Event<'a>> {
+        events
+            .into_iter()
+            .map(|event| match event {
+                Event::FootnoteReference(label) if definitions.contains_key(&label) => numbers.reference(label),
+                other => other,
+            })
+            .collect()
+    };
+    let mut output = number_references(body, &mut numbers);
+    // A definition can cite a footnote nothing else has, which appends to `order` as we go
+    let mut notes = Vec::new();
+    while notes.len() < numbers.order.len() {
+        let label = numbers.order[notes.len()].clone();
+        let inner = definitions.get(&label).cloned().unwrap_or_default();
+        notes.push(number_references(inner, &mut numbers));
+    }
+    if notes.is_empty() {
+        return output;
+    }
+
+    output.push(Event::Html("<section class=\"footnotes\">\n<ol>\n".into()));
+    for (index, mut note) in notes.into_iter().enumerate() {
+        let number = index + 1;
+        let backrefs: String = (1..=numbers.references[&numbers.order[index]])
+            .map(|nth| {
+                let mark = if nth == 1 { "↩".to_string() } else { format!("↩<sup>{}</sup>", nth) };
+                format!(
+                    " <a href=\"#{}\" class=\"footnote-backref\" aria-label=\"Back to reference {}\">{}</a>",
+                    footnote_reference_id(number, nth),
+                    number,
+                    mark
+                )
+            })
+            .collect();
+        // Backrefs sit at the end of the note's last paragraph when it ends with one
+        let closing = note.pop_if(|event| matches!(event, Event::End(TagEnd::Paragraph)));
+        note.push(Event::InlineHtml(backrefs.into()));
+        note.extend(closing);
+        output.push(Event::Html(format!("<li id=\"fn-{}\">\n", number).into()));
+        output.extend(note);
+        output.push(Event::Html("</li>\n".into()));
+    }
+    output.push(Event::Html("</ol>\n</section>\n".into()));
+    output
+}
+
+/// Rewrites links to external sites so they open in a new tab
+fn mark_external_links<'a>(events: Vec<Event<'a>>, base_url: &str) -> Vec<Event<'a>> {
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Start(Tag::Link { dest_url, title, .. }) if is_external_url(&dest_url, base_url) => {
+                let title_attr = if title.is_empty() {
+                    String::new()
+                } else {
+                    format!(" title=\"{}\"", escape_html(&title))
+                };
+                Event::InlineHtml(
+                    format!(
+                        "<a href=\"{}\"{} target=\"_blank\" rel=\"noopener noreferrer\">",
+                        escape_html(&dest_url),
+                        title_attr
+                    )
+                    .into(),
+                )
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// An image whose alt text is buffered until its end tag
+struct PendingImage<'a> {
+    dest_url: CowStr<'a>,
+    title: CowStr<'a>,
+    alt: String,
+}
+
+/// Renders images as `<img>` HTML with the attributes `[images]` asks for: lazy loading and,
+/// for relative paths to files beside the Markdown file, `width`/`height`. Sources are
+/// rewritten the same way as `absolutize_image_urls` when `image_dir` is set
+fn add_image_attributes<'a>(events: Vec<Event<'a>>, settings: &MarkdownSettings) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut image: Option<PendingImage> = None;
+
+    for event in events {
+        match (&mut image, event) {
+            (None, Event::Start(Tag::Image { dest_url, title, .. })) => {
+                image = Some(PendingImage { dest_url, title, alt: String::new() });
+            }
+            (Some(pending), Event::End(TagEnd::Image)) => {
+                let relative = is_relative_url(&pending.dest_url);
+                let src = match &settings.image_dir {
+                    Some(dir) if relative => resolve_relative_url(&settings.base_url, dir, &pending.dest_url),
+                    _ => pending.dest_url.to_string(),
+                };
+                let mut html = format!("<img src=\"{}\" alt=\"{}\"", escape_html(&src), escape_html(&pending.alt));
+                if !pending.title.is_empty() {
+                    html.push_str(&format!(" title=\"{}\"", escape_html(&pending.title)));
+                }
+                let dimensions = match &settings.source_dir {
+                    Some(dir) if settings.images.dimensions && relative => image_dimensions(dir, &pending.dest_url),
+                    _ => None,
+                };
+                if let Some((width, height)) = dimensions {
+                    html.push_str(&format!(" width=\"{}\" height=\"{}\"", width, height));
+                }
+                if settings.images.lazy_loading {
+                    html.push_str(" loading=\"lazy\" decoding=\"async\"");
+                }
+                html.push_str(" />");
+                output.push(Event::InlineHtml(html.into()));
+                image = None;
+            }
+            (Some(pending), Event::Text(text) | Event::Code(text)) => pending.alt.push_str(&text),
+            (Some(pending), Event::SoftBreak | Event::HardBreak) => pending.alt.push(' '),
+            (Some(_), _) => {}
+            (None, other) => output.push(other),
+        }
+    }
+    output
+}
+
+/// Pixel size of the image at `relative` (query and fragment ignored) under `dir`, when it
+/// exists and its format is recognized
+fn image_dimensions(dir: &Path, relative: &str) -> Option<(usize, usize)> {
+    let path = relative.split(['?', '#']).next().unwrap_or(relative);
+    let size = imagesize::size(dir.join(path)).ok()?;
+    Some((size.width, size.height))
+}
+
+/// Points relative image sources at `base_url/dir/...`. Root-relative paths, URLs with a
+/// scheme (including `data:`), and fragments are left alone
+fn absolutize_image_urls<'a>(events: Vec<Event<'a>>, base_url: &str, dir: &str) -> Vec<Event<'a>> {
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Start(Tag::Image { link_type, dest_url, title, id }) if is_relative_url(&dest_url) => {
+                let dest_url = resolve_relative_url(base_url, dir, &dest_url).into();
+                Event::Start(Tag::Image { link_type, dest_url, title, id })
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// True for paths such as `image.png` or `../img/a.png`, false for `/img/a.png`, `#top`, and
+/// anything with a scheme like `https:` or `data:`
+fn is_relative_url(url: &str) -> bool {
+    let has_scheme = url.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    !url.is_empty() && !url.starts_with(['/', '#', '?']) && !has_scheme
+}
+
+/// Joins `relative` onto the directory `dir` under `base_url`, resolving `.` and `..` segments.
+/// `..` never climbs above the site root
+fn resolve_relative_url(base_url: &str, dir: &str, relative: &str) -> String {
+    let mut segments: Vec<&str> = dir.split('/').filter(|segment| !segment.is_empty()).collect();
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("{}/{}", base_url, segments.join("/"))
+}
+
+fn syntax_set() -> &'static syntect::parsing::SyntaxSet {
+    static SYNTAX_SET: OnceLock<syntect::parsing::SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(syntect::parsing::SyntaxSet::load_defaults_newlines)
+}
+
+/// Highlights `code` as `language` into `<span>`s with syntect scope classes.
+/// Returns None when the language is unknown
+fn highlight_code(code: &str, language: &str) -> Option<String> {
+    use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+    use syntect::util::LinesWithEndings;
+
+    let syntaxes = syntax_set();
+    let syntax = syntaxes.find_syntax_by_token(language)?;
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, ClassStyle::Spaced);
+    for line in LinesWithEndings::from(code) {
+        generator.parse_html_for_line_which_includes_newline(line).ok()?;
+    }
+    Some(generator.finalize())
+}
+
+/// Replaces fenced code blocks in a known language with highlighted HTML.
+/// Blocks without a language or in an unknown one are left for the default renderer
+fn highlight_code_blocks(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut pending: Option<(String, Vec<Event>)> = None;
+
+    for event in events {
+        match (&mut pending, event) {
+            (None, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))) => {
+                let language = info.split_whitespace().next().unwrap_or("").to_string();
+                let start = Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)));
+                if language.is_empty() {
+                    output.push(start);
+                } else {
+                    pending = Some((language, vec![start]));
+                }
+            }
+            (Some((language, buffered)), Event::End(TagEnd::CodeBlock)) => {
+                let code: String = buffered
+                    .iter()
+                    .filter_map(|e| match e {
+                        Event::Text(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                match highlight_code(&code, language) {
+                    Some(highlighted) => output.push(Event::Html(
+                        format!(
+                            "<pre class=\"code\" data-lang=\"{}\"><code class=\"language-{}\">{}</code></pre>\n",
+                            escape_html(language),
+                            escape_html(language),
+                            highlighted
+                        )
+                        .into(),
+                    )),
+                    None => {
+                        output.append(buffered);
+                        output.push(Event::End(TagEnd::CodeBlock));
+                    }
+                }
+                pending = None;
+            }
+            (Some((_, buffered)), event) => buffered.push(event),
+            (None, event) => output.push(event),
+        }
+    }
+    output
+}
+
+/// Stylesheet for the classes emitted by `highlight_code`, using a bundled syntect theme
+fn syntax_theme_css(theme_name: &str) -> Result<String> {
+    use syntect::highlighting::ThemeSet;
+    use syntect::html::{ClassStyle, css_for_theme_with_class_style};
+
+    let themes = ThemeSet::load_defaults();
+    let theme = themes.themes.get(theme_name).with_context(|| {
+        let mut available: Vec<&String> = themes.themes.keys().collect();
+        available.sort();
+        format!("unknown highlight_theme '{}' (available: {:?})", theme_name, available)
+    })?;
+    css_for_theme_with_class_style(theme, ClassStyle::Spaced).context("generating syntax highlighting CSS")
+}
+
+/// Writes the syntax highlighting stylesheet when highlighting is enabled
+fn render_syntax_css(config: &Config, output: &Output) -> Result<()> {
+    if !config.extra_bool("highlight_code") {
+        return Ok(());
+    }
+    let theme = config.extra_str("highlight_theme").unwrap_or(DEFAULT_HIGHLIGHT_THEME);
+    let output_path = output.dir.join(SYNTAX_CSS_PATH);
+    output.write(&output_path, syntax_theme_css(theme)?)
+        .with_context(|| format!("writing syntax CSS to {:?}", output_path))
+}
+
+/// Returns true for absolute http(s) URLs that don't point back into the site
+fn is_external_url(url: &str, base_url: &str) -> bool {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return false;
+    }
+    if base_url.is_empty() {
+        return true;
+    }
+    match url.strip_prefix(base_url) {
+        Some(rest) => !(rest.is_empty() || rest.starts_with(['/', '?', '#'])),
+        None => true,
+    }
+}
+
+/// Removes HTML tags, decodes common entities, and collapses whitespace
+fn strip_html(html: &str) -> String {
+    const BLOCK_TAGS: &[&str] = &[
+        "p", "div", "br", "li", "ul", "ol", "pre", "blockquote", "table", "tr", "td", "th",
+        "h1", "h2", "h3", "h4", "h5", "h6", "hr", "section", "article",
+    ];
+
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map(|end| &comment[end + 3..]).unwrap_or_default();
+            continue;
+        }
+        let Some(end) = html_tag_end(rest) else {
+            break;
+        };
+        let tag = &rest[1..end];
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if BLOCK_TAGS.contains(&name.as_str()) {
+            text.push(' ');
+        }
+        rest = &rest[end + 1..];
+
+        // Script and style bodies are code, not text
+        if !tag.starts_with('/') && (name == "script" || name == "style") {
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(index) => {
+                    let after = &rest[index..];
+                    after.find('>').map(|end| &after[end + 1..]).unwrap_or_default()
+                }
+                None => "",
+            };
+        }
+    }
+    text.push_str(rest);
+
+    decode_html_entities(&text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Index of the `>` closing the tag that starts `html`, ignoring any inside quoted attribute values
+fn html_tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in html.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Decodes named entities common in generated HTML plus numeric ones; unknown entities stay as written
+fn decode_html_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Renders a Markdown summary into its HTML and plain-text variants
+fn summary_variants(summary: &str, settings: &MarkdownSettings) -> (String, String) {
+    let html = markdown_to_html(summary, settings);
+    let text = strip_html(&html);
+    (html, text)
+}
+
+/// Markdown before the first excerpt marker outside code, if the body has one
+fn split_excerpt(markdown: &str) -> Option<&str> {
+    markdown_segments(markdown).into_iter().find_map(|segment| match segment {
+        MarkdownSegment::Text(text) => text.find(EXCERPT_MARKER).map(|index| {
+            let offset = text.as_ptr() as usize - markdown.as_ptr() as usize;
+            &markdown[..offset + index]
+        }),
+        MarkdownSegment::Code(_) => None,
+    })
+}
+
+/// Plain-text fallback summary: the first paragraph of the rendered body, or the start of its text
+fn auto_summary(html: &str) -> Option<String> {
+    let first_paragraph = html.find("<p>").and_then(|start| {
+        let body = &html[start..];
+        body.find("</p>").map(|end| &body[..end + "</p>".len()])
+    });
+    let text = match first_paragraph.map(strip_html).filter(|text| !text.is_empty()) {
+        Some(text) => text,
+        None => strip_html(html),
+    };
+    if text.is_empty() {
+        None
+    } else {
+        Some(truncate_text(&text, AUTO_SUMMARY_CHARS))
+    }
+}
+
+/// Escapes text for safe inclusion in HTML/XML bodies and attribute values
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A slice of Markdown source that is either prose or code (fenced block or inline span)
+#[derive(Debug, PartialEq, Eq)]
+enum MarkdownSegment<'a> {
+    Text(&'a str),
+    Code(&'a str),
+}
+
+/// Splits Markdown into prose and code segments so text passes can leave code untouched
+fn markdown_segments(markdown: &str) -> Vec<MarkdownSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut offset = 0;
+    let mut fence: Option<(char, usize, usize)> = None;
+
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let run = marker.map(|m| trimmed.chars().take_while(|c| *c == m).count()).unwrap_or(0);
+
+        match fence {
+            None if run >= 3 => {
+                push_inline_segments(&markdown[text_start..offset], &mut segments);
+                fence = Some((marker.unwrap(), run, offset));
+            }
+            Some((fence_char, fence_len, start))
+                if marker == Some(fence_char)
+                    && run >= fence_len
+                    && trimmed.trim_end().chars().all(|c| c == fence_char) =>
+            {
+                segments.push(MarkdownSegment::Code(&markdown[start..offset + line.len()]));
+                fence = None;
+                text_start = offset + line.len();
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+
+    match fence {
+        // An unterminated fence runs to the end of the document
+        Some((_, _, start)) => segments.push(MarkdownSegment::Code(&markdown[start..])),
+        None => push_inline_segments(&markdown[text_start..], &mut segments),
+    }
+    segments
+}
+
+/// Counts words in Markdown prose, skipping fenced code so code-heavy posts aren't inflated
+fn count_words(markdown: &str) -> usize {
+    markdown_segments(markdown)
+        .into_iter()
+        .filter(|segment| match segment {
+            MarkdownSegment::Text(_) => true,
+            MarkdownSegment::Code(code) => !is_fenced_code(code),
+        })
+        .map(|segment| match segment {
+            MarkdownSegment::Text(text) | MarkdownSegment::Code(text) => text,
+        })
+        .flat_map(str::split_whitespace)
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+fn is_fenced_code(segment: &str) -> bool {
+    let trimmed = segment.trim_start();
+    (trimmed.starts_with("```") || trimmed.starts_with("~~~")) && segment.contains('\n')
+}
+
+/// Whole minutes to read `word_count` words, rounding up
+fn reading_time_minutes(word_count: usize) -> usize {
+    word_count.div_ceil(WORDS_PER_MINUTE)
+}
+
+/// Splits prose on inline code spans delimited by matching backtick runs
+fn push_inline_segments<'a>(text: &'a str, segments: &mut Vec<MarkdownSegment<'a>>) {
+    let mut rest = text;
+    while let Some(open) = rest.find('`') {
+        let ticks = rest[open..].chars().take_while(|c| *c == '`').count();
+        let delimiter = "`".repeat(ticks);
+        let body_start = open + ticks;
+        let close = rest[body_start..].match_indices(&delimiter).find(|(i, _)| {
+            let after = body_start + i + ticks;
+            !rest[after..].starts_with('`')
+        });
+        let Some((close, _)) = close else {
+            break;
+        };
+        let end = body_start + close + ticks;
+        if open > 0 {
+            segments.push(MarkdownSegment::Text(&rest[..open]));
+        }
+        segments.push(MarkdownSegment::Code(&rest[open..end]));
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        segments.push(MarkdownSegment::Text(rest));
+    }
+}
+
+/// Swaps code blocks and spans for placeholders so text passes leave them alone
+fn protect_code(body: &str) -> (String, Vec<&str>) {
+    let mut protected = Vec::new();
+    let mut source = String::with_capacity(body.len());
+    for segment in markdown_segments(body) {
+        match segment {
+            MarkdownSegment::Text(text) => source.push_str(text),
+            MarkdownSegment::Code(code) => {
+                source.push_str(&format!("\u{E000}{}\u{E000}", protected.len()));
+                protected.push(code);
+            }
+        }
+    }
+    (source, protected)
+}
+
+/// Puts code hidden by `protect_code` back in place
+fn restore_code(mut text: String, protected: &[&str]) -> String {
+    for (index, code) in protected.iter().enumerate() {
+        text = text.replacen(&format!("\u{E000}{}\u{E000}", index), code, 1);
+    }
+    text
+}
+
+/// Evaluates Tera expressions in a Markdown body with `config` and `page` (front matter) in scope.
+/// Code blocks and spans are swapped for placeholders first so their braces survive literally
+fn render_markdown_template(body: &str, meta: &FrontMatter, config: &Config) -> Result<String> {
+    let (source, protected) = protect_code(body);
+
+    let mut context = TeraContext::new();
+    context.insert("config", config);
+    context.insert("page", meta);
+    let rendered = Tera::one_off(&source, &context, false).map_err(tera_error_chain)?;
+    Ok(restore_code(rendered, &protected))
+}
+
+/// Expands shortcodes that have a `shortcodes/<name>.html` template: inline `{{ name(key="value") }}`
+/// and block `{% name(key=1) %}...{% end %}`, whose inner text reaches the template as `body`.
+/// Arguments are strings, numbers, or booleans. Calls without a template and anything in code stay literal.
+/// Each call becomes a placeholder and its output is returned alongside, for `restore_shortcodes` to put
+/// back once later passes over the body, such as markdown templating, can no longer evaluate it
+fn expand_shortcodes(body: &str, tera: &Tera, config: &Config) -> Result<(String, Vec<String>)> {
+    let (source, protected) = protect_code(body);
+    let mut outputs = Vec::new();
+    let mut expanded = String::with_capacity(source.len());
+    let mut rest = source.as_str();
+
+    while let Some(open) = rest.find('{') {
+        expanded.push_str(&rest[..open]);
+        let call = &rest[open..];
+        let Some(invocation) = parse_shortcode(call) else {
+            expanded.push('{');
+            rest = &call[1..];
+            continue;
+        };
+        let template = format!("shortcodes/{}.html", invocation.name);
+        if !tera.get_template_names().any(|name| name == template) {
+            expanded.push('{');
+            rest = &call[1..];
+            continue;
+        }
+
+        let mut context = TeraContext::new();
+        context.insert("config", config);
+        for (key, value) in &invocation.args {
+            context.insert(key.as_str(), value);
+        }
+        let mut consumed = invocation.len;
+        if invocation.block {
+            let inner = &call[consumed..];
+            let (end, end_len) = find_shortcode_end(inner)
+                .with_context(|| format!("shortcode '{}' is missing its {{% end %}}", invocation.name))?;
+            context.insert("body", &restore_code(inner[..end].to_string(), &protected));
+            consumed += end + end_len;
+        }
+        let html = tera
+            .render(&template, &context)
+            .map_err(tera_error_chain)
+            .with_context(|| format!("rendering shortcode '{}'", invocation.name))?;
+        // A different marker from protect_code's, whose placeholders this text still holds
+        expanded.push_str(&format!("\u{E001}{}\u{E001}", outputs.len()));
+        outputs.push(html.trim_end().to_string());
+        rest = &call[consumed..];
+    }
+    expanded.push_str(rest);
+    Ok((restore_code(expanded, &protected), outputs))
+}
+
+/// Puts shortcode output held back by `expand_shortcodes` in place
+fn restore_shortcodes(mut text: String, outputs: &[String]) -> String {
+    for (index, output) in outputs.iter().enumerate() {
+        text = text.replacen(&format!("\u{E001}{}\u{E001}", index), output, 1);
+    }
+    text
+}
+
+/// A parsed shortcode opening tag
+#[derive(Debug, PartialEq)]
+struct ShortcodeCall {
+    name: String,
+    args: Vec<(String, serde_json::Value)>,
+    /// `{% %}` form, closed by a later `{% end %}`
+    block: bool,
+    /// Length of the opening tag in bytes
+    len: usize,
+}
+
+/// Parses `{{ name(args) }}` or `{% name(args) %}` at the start of `text`
+fn parse_shortcode(text: &str) -> Option<ShortcodeCall> {
+    let (block, close) = if text.starts_with("{{") {
+        (false, "}}")
+    } else if text.starts_with("{%") {
+        (true, "%}")
+    } else {
+        return None;
+    };
+    let mut rest = text[2..].trim_start();
+    let name_len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+    let name = &rest[..name_len];
+    if name.is_empty() || name == "end" {
+        return None;
+    }
+    rest = rest[name_len..].strip_prefix('(')?;
+
+    let mut args = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(')') {
+            rest = after;
+            break;
+        }
+        if !args.is_empty() {
+            rest = rest.strip_prefix(',')?.trim_start();
+        }
+        let key_len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+        let key = &rest[..key_len];
+        if key.is_empty() {
+            return None;
+        }
+        rest = rest[key_len..].trim_start().strip_prefix('=')?.trim_start();
+        let (value, value_len) = parse_shortcode_value(rest)?;
+        args.push((key.to_string(), value));
+        rest = &rest[value_len..];
+    }
+    rest = rest.trim_start().strip_prefix(close)?;
+    Some(ShortcodeCall {
+        name: name.to_string(),
+        args,
+        block,
+        len: text.len() - rest.len(),
+    })
+}
+
+/// Parses a quoted string, number, or boolean, returning it with its length in bytes
+fn parse_shortcode_value(text: &str) -> Option<(serde_json::Value, usize)> {
+    let quote = text.chars().next()?;
+    if quote == '"' || quote == '\'' {
+        let mut value = String::new();
+        let mut escaped = false;
+        for (index, c) in text.char_indices().skip(1) {
+            match c {
+                _ if escaped => {
+                    value.push(c);
+                    escaped = false;
+                }
+                '\\' => escaped = true,
+                _ if c == quote => return Some((serde_json::Value::String(value), index + 1)),
+                _ => value.push(c),
+            }
+        }
+        return None;
+    }
+
+    let len = text.find([',', ')', ' ']).unwrap_or(text.len());
+    let literal = &text[..len];
+    let value = match literal {
+        "true" => serde_json::Value::Bool(true),
+        "false" => serde_json::Value::Bool(false),
+        _ => match literal.parse::<i64>() {
+            Ok(number) => number.into(),
+            Err(_) => literal.parse::<f64>().ok().map(serde_json::Value::from)?,
+        },
+    };
+    Some((value, len))
+}
+
+/// Offset and length of the `{% end %}` closing a block shortcode
+fn find_shortcode_end(text: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    while let Some(open) = text[offset..].find("{%") {
+        let start = offset + open;
+        let tag = &text[start + 2..];
+        let inner = tag.trim_start();
+        if let Some(after) = inner.strip_prefix("end") {
+            if let Some(after) = after.trim_start().strip_prefix("%}") {
+                return Some((start, text.len() - start - after.len()));
+            }
+        }
+        offset = start + 2;
+    }
+    None
+}
+
+/// Flattens a Tera error and its sources into one message
+fn tera_error_chain(error: tera::Error) -> anyhow::Error {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(&error);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    anyhow::anyhow!(message)
+}
+
+/// Renders a template with the given context and writes to output file
+fn render_template_to_file(
+    tera: &Tera,
+    config: &Config,
+    template_name: &str,
+    context: &TeraContext,
+    output: &Output,
+    output_path: &Path,
+    context_desc: &str,
+) -> Result<()> {
+    let rendered = tera.render(template_name, context).map_err(|error| {
+        // Name the file on disk when the template came from one, so the error is easy to chase
+        let template = tera
+            .get_template(template_name)
+            .ok()
+            .and_then(|template| template.path.clone())
+            .unwrap_or_else(|| template_name.to_string());
+        tera_error_chain(error).context(format!("rendering {} with template {}", context_desc, template))
+    })?;
+    let is_html = output_path.extension().is_some_and(|extension| extension == "html");
+    let rendered = if config.extra_bool("minify") && is_html {
+        minify_html(&rendered)
+    } else {
+        rendered
+    };
+
+    output.write(output_path, rendered)
+        .with_context(|| format!("writing {} to {:?}", context_desc, output_path))?;
+
+    Ok(())
+}
+
+/// Collapses insignificant whitespace and drops comments. `<pre>` and `<textarea>` contents are
+/// kept exactly, and so are `<script>` and `<style>` bodies, which the minifier would trim
+fn minify_html(html: &str) -> String {
+    let cfg = minify_html::Cfg {
+        keep_closing_tags: true,
+        keep_html_and_head_opening_tags: true,
+        ..minify_html::Cfg::spec_compliant()
+    };
+    let (source, protected) = protect_raw_text(html);
+    let minified = String::from_utf8_lossy(&minify_html::minify(source.as_bytes(), &cfg)).into_owned();
+    restore_code(minified, &protected)
+}
+
+/// Swaps the bodies of `<script>` and `<style>` elements for placeholders, like `protect_code`
+fn protect_raw_text(html: &str) -> (String, Vec<&str>) {
+    // ASCII lowercasing keeps byte offsets, so positions found here index `html` too
+    let lower = html.to_ascii_lowercase();
+    let mut protected = Vec::new();
+    let mut source = String::with_capacity(html.len());
+    let mut rest = 0;
+    while let Some((start, tag)) = ["<script", "<style"]
+        .into_iter()
+        .filter_map(|tag| lower[rest..].find(tag).map(|at| (rest + at, tag)))
+        .min()
+    {
+        let Some(body_start) = lower[start..].find('>').map(|end| start + end + 1) else {
+            break;
+        };
+        let closing = format!("</{}", &tag[1..]);
+        let body_end = lower[body_start..].find(&closing).map_or(html.len(), |end| body_start + end);
+        source.push_str(&html[rest..body_start]);
+        if body_start < body_end {
+            source.push_str(&format!("\u{E000}{}\u{E000}", protected.len()));
+            protected.push(&html[body_start..body_end]);
+        }
+        rest = body_end;
+    }
+    source.push_str(&html[rest..]);
+    (source, protected)
+}
+
+/// Creates base template context with config, the site nav, the time of the build, and path prefix
+fn build_base_context(
+    config: &Config,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    path_prefix: &str,
+) -> TeraContext {
+    let mut context = TeraContext::new();
+    context.insert("config", config);
+    context.insert("lang", &config.lang);
+    context.insert("nav_sections", nav);
+    context.insert("menu", &menu_links(&config.menu, path_prefix));
+    context.insert("path_prefix", path_prefix);
+    context.insert("base_path", base_path(&config.base_url));
+    context.insert("build_date", &built_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+    context.insert("current_year", &built_at.year());
+    context
+}
+
+/// Orders menu entries (and their children) by weight and resolves their URLs against `path_prefix`
+fn menu_links(items: &[MenuItem], path_prefix: &str) -> Vec<MenuLink> {
+    let mut items: Vec<&MenuItem> = items.iter().collect();
+    items.sort_by_key(|item| (item.weight.is_none(), item.weight));
+    items
+        .into_iter()
+        .map(|item| MenuLink {
+            name: item.name.clone(),
+            url: menu_url(&item.url, path_prefix),
+            children: menu_links(&item.children, path_prefix),
+        })
+        .collect()
+}
+
+/// Makes a root-relative URL relative to the current page: `/about/` becomes `../about/`
+/// two levels down, and `/` on the home page becomes `./`. Other URLs pass through
+fn menu_url(url: &str, path_prefix: &str) -> String {
+    match url.strip_prefix('/') {
+        Some(rest) if !rest.starts_with('/') => {
+            let url = format!("{}{}", path_prefix, rest);
+            if url.is_empty() { "./".to_string() } else { url }
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Path part of `base_url` without a trailing slash: `/blog` for `https://example.com/blog`,
+/// empty when the site sits at the root of its host
+fn base_path(base_url: &str) -> &str {
+    let after_scheme = base_url.split_once("://").map_or(base_url, |(_, rest)| rest);
+    after_scheme.find('/').map_or("", |slash| &after_scheme[slash..])
+}
+
+/// Calculates directory depth for path prefix generation
+/// Returns number of "../" needed to reach site root
+fn calculate_path_depth(path: &str, is_page: bool) -> usize {
+    if path.is_empty() {
+        if is_page { 1 } else { 0 }
+    } else {
+        let base_depth = path.split('/').count();
+        if is_page { base_depth + 1 } else { base_depth }
+    }
+}
+
+/// Generates "../" prefix to reach site root from given depth
+fn path_prefix_for_depth(depth: usize) -> String {
+    "../".repeat(depth)
+}
+
+/// Transliterates text to ASCII (`Café Olé` → `Cafe Ole`), then lowercases it and collapses runs
+/// of other characters into single hyphens
+fn slugify(text: &str) -> String {
+    hyphenate(&deunicode::deunicode(text))
+}
+
+/// Lowercases text and collapses runs of non-alphanumeric characters into single hyphens,
+/// keeping non-ASCII letters. Explicit `slug` front matter goes through this, not `slugify`
+fn hyphenate(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Builds the `body_class` string for CSS targeting, e.g. `section-writing page-my-post`
+fn body_class(section_key: &str, page_slug: Option<&str>) -> String {
+    let mut classes = Vec::new();
+    if !section_key.is_empty() {
+        classes.push(format!("section-{}", slugify(section_key)));
+    }
+    if let Some(slug) = page_slug {
+        classes.push(format!("page-{}", slugify(slug)));
+    }
+    classes.join(" ")
+}
+
+/// Walks every content directory in order and merges them into one set of sections, along with
+/// the warnings raised on the way. When two directories provide the same relative file, the later one wins
+fn load_content(
+    content_dirs: &[PathBuf],
+    config: &Config,
+    tera: &Tera,
+) -> Result<(SectionData, HashMap<String, SectionContent>, Vec<Warning>)> {
+    let markdown_settings = MarkdownSettings::from_config(config);
+    let mut sections: HashMap<String, SectionContent> = HashMap::new();
+    let mut root_meta = FrontMatter::default();
+    let mut root_body = String::new();
+
+    let files = content_files(content_dirs, &config.markdown_extensions)?;
+    let mut warnings = content_overrides(&files);
+    warnings.extend(duplicate_section_indexes(&files));
+
+    // Files are read and rendered in parallel, then merged in walk order so later
+    // content directories still override earlier ones
+    let loaded = files
+        .par_iter()
+        .map(|(path, relative, _)| load_markdown_file(path, relative, config, &markdown_settings, tera))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Relative content path -> output path of the page built from it, so an override
+    // replaces its predecessor even when front matter moves it elsewhere
+    let mut placed: HashMap<&Path, String> = HashMap::new();
+    // Output path -> content directory whose page writes it, so a later directory can take over
+    // a page that a differently named file in an earlier one slugs to the same URL
+    let mut written_by: HashMap<String, usize> = HashMap::new();
+    for ((_, relative, root), (file, file_warnings)) in files.iter().zip(loaded) {
+        warnings.extend(file_warnings);
+        match file {
+            LoadedFile::Section { key: None, meta, body_html } => {
+                root_meta = *meta;
+                root_body = body_html;
+            }
+            LoadedFile::Section { key: Some(key), meta, body_html } => {
+                // Use entry API to preserve existing pages if section already exists
+                let section = sections.entry(key).or_default();
+                section.meta = *meta;
+                section.body_html = body_html;
+            }
+            LoadedFile::Page { section_key, page } => {
+                let section_pages = &mut sections.entry(section_key).or_default().pages;
+                if let Some(previous) = placed.insert(relative, page.relative_path.clone()) {
+                    section_pages.retain(|existing| existing.relative_path != previous);
+                }
+                if written_by.insert(page.relative_path.clone(), *root).is_some_and(|earlier| earlier < *root) {
+                    if let Some(existing) = section_pages.iter().find(|p| p.relative_path == page.relative_path) {
+                        warnings.push(Warning::ContentOverride {
+                            source: page.source.clone(),
+                            previous: existing.source.clone(),
+                            output: Some(page.relative_path.clone()),
+                        });
+                    }
+                    section_pages.retain(|existing| existing.relative_path != page.relative_path);
+                }
+                section_pages.push(*page);
+            }
+            LoadedFile::Skipped => {}
+        }
+    }
+
+    apply_section_drafts(&mut sections, config.drafts);
+
+    for (key, section) in sections.iter_mut() {
+        let sort_fields = parse_sort_spec(section.meta.sort_by.as_deref().unwrap_or("date"))
+            .with_context(|| format!("invalid sort_by in section '{}'", key))?;
+        sort_pages(&mut section.pages, &sort_fields);
+        link_adjacent_pages(&mut section.pages);
+
+        // Validate the section's defaults once rather than for every page that inherits them
+        let section_hints = validated_sitemap_hints(
+            section.meta.sitemap_priority,
+            section.meta.sitemap_changefreq.as_deref(),
+            &format!("section '{}' _index.md", key),
+            &mut warnings,
+        );
+        section.meta.sitemap_priority = section_hints.priority;
+        section.meta.sitemap_changefreq = section_hints.changefreq.clone();
+        let defaults = SitemapConfig {
+            priority: section_hints.priority.or(config.sitemap.priority),
+            changefreq: section_hints.changefreq.or_else(|| config.sitemap.changefreq.clone()),
+        };
+        for page in section.pages.iter_mut() {
+            resolve_sitemap_hints(page, &defaults);
+        }
+    }
+
+    if let Some(related_by) = config.related_by {
+        warnings.extend(compute_related(&mut sections, related_by, config.related_limit));
+    }
+
+    let trails: HashMap<String, Vec<Crumb>> =
+        sections.keys().map(|key| (key.clone(), breadcrumbs(key, &sections, config))).collect();
+    for (key, section) in sections.iter_mut() {
+        for page in section.pages.iter_mut() {
+            page.breadcrumbs = trails[key].clone();
+            page.breadcrumbs.push(Crumb { title: page.title.clone(), permalink: page.permalink.clone() });
+        }
+    }
+
+    let root_subsections = link_section_hierarchy(&mut sections);
+
+    let (permalink, relative_path) = section_paths("", config);
+    let root_section = SectionData {
+        title: root_meta.title.unwrap_or_else(|| config.title.clone()),
+        meta_description: root_meta.description.clone().unwrap_or_else(|| config.description.clone()),
+        description: root_meta.description,
+        permalink,
+        relative_path,
+        pages: Vec::new(),
+        content: root_body,
+        subsections: section_links(&root_subsections, &sections, config),
+        breadcrumbs: breadcrumbs("", &sections, config),
+    };
+
+    Ok((root_section, sections, warnings))
+}
+
+/// Markdown files across the content directories as (path, path relative to its directory, index
+/// of the directory), in walk order: directory by directory, and by file name within each
+fn content_files(content_dirs: &[PathBuf], markdown_extensions: &[String]) -> Result<Vec<(PathBuf, PathBuf, usize)>> {
+    let mut files = Vec::new();
+    for (root, content_dir) in content_dirs.iter().enumerate() {
+        if !content_dir.is_dir() {
+            anyhow::bail!("content directory {:?} does not exist", content_dir);
+        }
+        for entry in WalkDir::new(content_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && is_markdown_file(e.path(), markdown_extensions))
+        {
+            let path = entry.path();
+            let relative = path
+                .strip_prefix(content_dir)
+                .context("stripping content prefix")?;
+            files.push((path.to_path_buf(), relative.to_path_buf(), root));
+        }
+    }
+    Ok(files)
+}
+
+/// A warning for each file that replaces one at the same relative path in an earlier content directory
+fn content_overrides(files: &[(PathBuf, PathBuf, usize)]) -> Vec<Warning> {
+    // Relative content path -> file that currently provides it
+    let mut origins: HashMap<&Path, &Path> = HashMap::new();
+    files
+        .iter()
+        .filter_map(|(path, relative, _)| {
+            origins.insert(relative.as_path(), path.as_path()).map(|previous| Warning::ContentOverride {
+                source: path.display().to_string(),
+                previous: previous.display().to_string(),
+                output: None,
+            })
+        })
+        .collect()
+}
+
+/// Permalink and output file for a URL path such as `writing/my-post`: `.../writing/my-post/`
+/// and `writing/my-post/index.html`, or with `ugly_urls`, `writing/my-post.html` for both
+fn url_paths(url_path: &str, config: &Config) -> (String, String) {
+    if config.ugly_urls {
+        let file = format!("{}.html", url_path);
+        (format!("{}/{}", config.base_url, file), file)
+    } else {
+        (
+            format!("{}/{}/", config.base_url, url_path),
+            format!("{}/index.html", url_path),
+        )
+    }
+}
+
+/// Permalink and output file of the listing for the section at `key`; the root is the home page
+fn section_paths(key: &str, config: &Config) -> (String, String) {
+    if key.is_empty() {
+        (format!("{}/", config.base_url), "index.html".to_string())
+    } else {
+        url_paths(key, config)
+    }
+}
+
+/// Like `section_paths`, but honouring the section's `output_filename`
+fn section_output_paths(key: &str, meta: &FrontMatter, config: &Config) -> (String, String) {
+    match &meta.output_filename {
+        Some(file) if key.is_empty() => (format!("{}/{}", config.base_url, file), file.clone()),
+        Some(file) => (format!("{}/{}/{}", config.base_url, key, file), format!("{}/{}", key, file)),
+        None => section_paths(key, config),
+    }
+}
+
+/// Section key for a content directory relative to its root: each directory name slugified
+/// and joined with `/`, so `Rust Notes/Café` becomes `rust-notes/cafe`
+fn section_key(path: &Path) -> String {
+    path.components()
+        .map(|component| path_slug(&component.as_os_str().to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Slugifies a file or directory name, keeping names with nothing to slug as they are
+fn path_slug(name: &str) -> String {
+    let slug = slugify(name);
+    if slug.is_empty() { name.to_string() } else { slug }
+}
+
+/// Joins a relative content path with `/` so section keys and URLs match on every platform
+fn url_key(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Expands a permalink pattern such as `/{section}/{year}/{month}/{slug}/` into a URL path
+/// without surrounding slashes. Returns `None` when the pattern needs a date the page lacks
+fn expand_permalink(pattern: &str, section: &str, slug: &str, date: Option<NaiveDate>) -> Result<Option<String>> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("unclosed placeholder in '{}'", pattern))?;
+        let value = match &rest[start + 1..start + end] {
+            "section" => section.to_string(),
+            "slug" => slug.to_string(),
+            field @ ("year" | "month" | "day") => {
+                let Some(date) = date else {
+                    return Ok(None);
+                };
+                match field {
+                    "year" => date.format("%Y"),
+                    "month" => date.format("%m"),
+                    _ => date.format("%d"),
+                }
+                .to_string()
+            }
+            other => anyhow::bail!("unknown placeholder {{{}}} in '{}'", other, pattern),
+        };
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+
+    // An empty {section} for top-level pages leaves doubled slashes behind
+    let segments: Vec<&str> = expanded.split('/').filter(|segment| !segment.is_empty()).collect();
+    Ok(Some(segments.join("/")))
+}
+
+/// One Markdown file read and rendered, before it is merged into the section tree
+enum LoadedFile {
+    /// An `_index.md`; `key` is `None` for the site root
+    Section {
+        key: Option<String>,
+        meta: Box<FrontMatter>,
+        body_html: String,
+    },
+    Page {
+        section_key: String,
+        page: Box<PageData>,
+    },
+    /// A draft page left out because drafts are disabled
+    Skipped,
+}
+
+/// Reads, parses, and renders a single content file, with the warnings it raised
+fn load_markdown_file(
+    path: &Path,
+    relative: &Path,
+    config: &Config,
+    markdown_settings: &MarkdownSettings,
+    tera: &Tera,
+) -> Result<(LoadedFile, Vec<Warning>)> {
+    let base_url = config.base_url.as_str();
+    let parent_key = relative.parent().map(section_key).unwrap_or_default();
+
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("reading markdown file {:?}", path))?;
+    let (meta, body) = parse_front_matter(&raw)
+        .with_context(|| format!("parsing frontmatter in {:?}", path))?;
+    let mut warnings = check_front_matter_keys(&meta, path, config.strict_front_matter)?;
+    let word_count = count_words(&body);
+    let (body, shortcodes) = expand_shortcodes(&body, tera, config)
+        .with_context(|| format!("expanding shortcodes in {:?}", path))?;
+    let body = if config.markdown_templating {
+        render_markdown_template(&body, &meta, config)
+            .with_context(|| format!("evaluating template expressions in {:?}", path))?
+    } else {
+        body
+    };
+    let body = restore_shortcodes(body, &shortcodes);
+    let page_settings;
+    let markdown_settings = if config.absolute_image_urls || config.images.dimensions {
+        page_settings = MarkdownSettings {
+            image_dir: config.absolute_image_urls.then(|| parent_key.clone()),
+            source_dir: config.images.dimensions.then(|| path.parent().unwrap_or(Path::new("")).to_path_buf()),
+            ..markdown_settings.clone()
+        };
+        &page_settings
+    } else {
+        markdown_settings
+    };
+    let rendered = render_markdown(&body, markdown_settings);
+    let html_body = rendered.html;
+
+    if path.file_stem().unwrap() == "_index" {
+        let key = (relative.components().count() > 1).then_some(parent_key);
+        let section = LoadedFile::Section {
+            key,
+            meta: Box::new(meta),
+            body_html: html_body,
+        };
+        return Ok((section, warnings));
+    }
+
+    let visibility = meta.visibility_or_default();
+    if visibility == Visibility::Draft && !config.drafts {
+        return Ok((LoadedFile::Skipped, warnings));
+    }
+
+    let slug = meta
+        .slug
+        .as_deref()
+        .map(hyphenate)
+        .filter(|slug| !slug.is_empty())
+        .unwrap_or_else(|| path_slug(&path.file_stem().unwrap_or_default().to_string_lossy()));
+
+    let source = path.display().to_string();
+    let default_url = if parent_key.is_empty() {
+        slug.clone()
+    } else {
+        format!("{}/{}", parent_key, slug)
+    };
+    let url_str = match config.extra_str("permalink") {
+        Some(pattern) => match expand_permalink(pattern, &parent_key, &slug, meta.date)
+            .with_context(|| format!("expanding permalink pattern for {}", source))?
+        {
+            Some(url) => url,
+            None => {
+                warnings.push(Warning::MissingPermalinkDate {
+                    source: source.clone(),
+                    pattern: pattern.to_string(),
+                    fallback: default_url.clone(),
+                });
+                default_url
+            }
+        },
+        None => default_url,
+    };
+    let (permalink, relative_path) = url_paths(&url_str, config);
+
+    let (summary, summary_text) = match meta.summary.as_deref().or_else(|| split_excerpt(&body)) {
+        Some(markdown) => {
+            let (html, text) = summary_variants(markdown, markdown_settings);
+            (Some(html), Some(text))
+        }
+        None => {
+            let text = auto_summary(&html_body);
+            (text.as_deref().map(|text| format!("<p>{}</p>\n", escape_html(text))), text)
+        }
+    };
+    let page = PageData {
+        title: meta
+            .title
+            .clone()
+            .unwrap_or_else(|| humanize_slug(&slug, &config.title_acronyms())),
+        date: meta.date,
+        datetime: meta.published.map(|published| published.to_utc()),
+        updated: meta.updated,
+        extra: meta.template_extra(),
+        description: meta.description.clone(),
+        image: meta.image.as_deref().map(|image| absolute_url(image, base_url)),
+        lang: meta.lang.clone(),
+        summary,
+        summary_text,
+        content: html_body,
+        word_count,
+        reading_time_minutes: reading_time_minutes(word_count),
+        permalink,
+        relative_path,
+        aliases: meta
+            .aliases
+            .iter()
+            .flatten()
+            .map(|alias| alias_output_path(alias))
+            .collect::<Result<_>>()
+            .with_context(|| format!("invalid alias in {}", source))?,
+        template: meta.template.clone(),
+        slug,
+        weight: meta.weight,
+        visibility,
+        draft: visibility == Visibility::Draft,
+        sitemap_priority: meta
+            .sitemap_priority
+            .and_then(|p| valid_sitemap_priority(p, &source, &mut warnings)),
+        sitemap_changefreq: meta
+            .sitemap_changefreq
+            .as_deref()
+            .and_then(|f| valid_sitemap_changefreq(f, &source, &mut warnings)),
+        related: Vec::new(),
+        prev: None,
+        next: None,
+        tags: term_links(meta.tags.as_deref().unwrap_or_default(), &TAGS, config),
+        category: term_links(meta.category.as_slice(), &CATEGORIES, config).pop(),
+        toc: if meta.toc.unwrap_or(config.toc) {
+            build_toc(&rendered.headings, meta.toc_depth.unwrap_or(config.toc_depth))
+        } else {
+            Vec::new()
+        },
+        // Filled in once every section has been loaded
+        breadcrumbs: Vec::new(),
+        related_refs: meta.related.clone().unwrap_or_default(),
+        source,
+    };
+
+    let page = LoadedFile::Page {
+        section_key: parent_key,
+        page: Box::new(page),
+    };
+    Ok((page, warnings))
+}
+
+/// Handles sections whose `_index.md` is a draft: without drafts enabled the section and
+/// everything below it is dropped; with drafts enabled its pages are marked as drafts
+fn apply_section_drafts(sections: &mut HashMap<String, SectionContent>, drafts_enabled: bool) {
+    let mut draft_keys: Vec<String> = sections
+        .iter()
+        .filter(|(_, section)| section.meta.visibility_or_default() == Visibility::Draft)
+        .map(|(key, _)| key.clone())
+        .collect();
+    draft_keys.sort();
+
+    for draft_key in draft_keys {
+        let in_subtree = |key: &str| key == draft_key || key.starts_with(&format!("{}/", draft_key));
+        if drafts_enabled {
+            for (_, section) in sections.iter_mut().filter(|(key, _)| in_subtree(key)) {
+                for page in section.pages.iter_mut() {
+                    page.draft = true;
+                }
+            }
+        } else {
+            sections.retain(|key, _| !in_subtree(key));
+        }
+    }
+}
+
+/// Creates any missing intermediate sections (e.g. `writing/rust` for `writing/rust/async`)
+/// and records each section's direct children. Returns the top-level section keys
+fn link_section_hierarchy(sections: &mut HashMap<String, SectionContent>) -> Vec<String> {
+    let keys: Vec<String> = sections.keys().filter(|k| !k.is_empty()).cloned().collect();
+    for key in keys {
+        let mut current = key.as_str();
+        while let Some((parent, _)) = current.rsplit_once('/') {
+            sections.entry(parent.to_string()).or_default();
+            current = parent;
+        }
+    }
+
+    let mut keys: Vec<String> = sections.keys().filter(|k| !k.is_empty()).cloned().collect();
+    keys.sort();
+
+    let mut top_level = Vec::new();
+    for section in sections.values_mut() {
+        section.subsections.clear();
+    }
+    for key in keys {
+        match key.rsplit_once('/') {
+            Some((parent, _)) => sections.get_mut(parent).unwrap().subsections.push(key),
+            None => top_level.push(key),
+        }
+    }
+    top_level
+}
+
+/// A section's `title`, or its last path segment made readable
+fn section_title(key: &str, meta: &FrontMatter, config: &Config) -> String {
+    meta.title.clone().unwrap_or_else(|| {
+        let name = key.rsplit('/').next().unwrap_or(key);
+        humanize_slug(name, &config.title_acronyms())
+    })
+}
+
+/// Title-cases a slug for pages and sections without a `title`: `my-first-post` becomes
+/// `My First Post`. Words matching an entry in `acronyms`, ignoring case, take that spelling
+fn humanize_slug(slug: &str, acronyms: &[String]) -> String {
+    slug.split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            if let Some(acronym) = acronyms.iter().find(|a| a.eq_ignore_ascii_case(word)) {
+                return acronym.clone();
+            }
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Builds template-facing links for the given section keys, leaving out sections that render to an
+/// `output_filename` such as `feed.xml` rather than a page
+fn section_links(
+    keys: &[String],
+    sections: &HashMap<String, SectionContent>,
+    config: &Config,
+) -> Vec<SectionLink> {
+    keys.iter()
+        .filter_map(|key| {
+            let section = sections.get(key).filter(|section| section.meta.output_filename.is_none())?;
+            let (permalink, relative_path) = section_paths(key, config);
+            Some(SectionLink {
+                key: key.clone(),
+                title: section_title(key, &section.meta, config),
+                permalink,
+                relative_path,
+            })
+        })
+        .collect()
+}
+
+/// Top-level sections for the site-wide nav, ordered by `weight` (unweighted last), then title.
+/// Only links are included; templates reach sub-sections through each section page
+fn nav_sections(sections: &HashMap<String, SectionContent>, config: &Config) -> Vec<SectionLink> {
+    let mut keys: Vec<&String> = sections.keys().filter(|key| !key.is_empty() && !key.contains('/')).collect();
+    keys.sort_by_cached_key(|key| {
+        let meta = &sections[*key].meta;
+        (meta.weight.is_none(), meta.weight, section_title(key, meta, config).to_lowercase())
+    });
+    let keys: Vec<String> = keys.into_iter().cloned().collect();
+    section_links(&keys, sections, config)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Date,
+    /// `updated`, falling back to `date`
+    Updated,
+    Title,
+    Slug,
+    Weight,
+}
+
+/// One entry of a `sort_by` spec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SortField {
+    key: SortKey,
+    descending: bool,
+}
+
+/// Parses a `sort_by` spec such as `weight,date:desc`. Dates (`date`, `updated`) default to descending,
+/// every other key to ascending; `key_asc`/`key_desc` are accepted as shorthands
+fn parse_sort_spec(spec: &str) -> Result<Vec<SortField>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (name, direction) = match part.split_once(':') {
+                Some((name, direction)) => (name.trim(), Some(direction.trim())),
+                // Single-key shorthands like `date_asc`
+                None => match part.rsplit_once('_') {
+                    Some((name, direction @ ("asc" | "desc"))) => (name, Some(direction)),
+                    _ => (part, None),
+                },
+            };
+            let key = match name {
+                "date" => SortKey::Date,
+                "updated" => SortKey::Updated,
+                "title" => SortKey::Title,
+                "slug" => SortKey::Slug,
+                "weight" => SortKey::Weight,
+                other => anyhow::bail!(
+                    "unknown sort key '{}' (expected date, updated, title, slug, or weight)",
+                    other
+                ),
+            };
+            let descending = match direction {
+                None => matches!(key, SortKey::Date | SortKey::Updated),
+                Some("asc") => false,
+                Some("desc") => true,
+                Some(other) => anyhow::bail!(
+                    "unknown sort direction '{}' for '{}' (expected asc or desc)",
+                    other,
+                    name
+                ),
+            };
+            Ok(SortField { key, descending })
+        })
+        .collect()
+}
+
+/// Orders two optional values by direction, always placing missing values last
+fn cmp_optional<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Stable multi-key sort; later fields only break ties left by earlier ones, and title
+/// breaks whatever ties remain so equal weights or dates list alphabetically
+fn sort_pages(pages: &mut [PageData], fields: &[SortField]) {
+    pages.sort_by(|a, b| {
+        fields
+            .iter()
+            .map(|field| match field.key {
+                SortKey::Date => cmp_optional(a.published(), b.published(), field.descending),
+                SortKey::Updated => cmp_optional(a.last_modified(), b.last_modified(), field.descending),
+                SortKey::Weight => cmp_optional(a.weight, b.weight, field.descending),
+                SortKey::Title => cmp_optional(
+                    Some(a.title.to_lowercase()),
+                    Some(b.title.to_lowercase()),
+                    field.descending,
+                ),
+                SortKey::Slug => cmp_optional(Some(&a.slug), Some(&b.slug), field.descending),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
+    });
+}
+
+/// Sets `prev`/`next` on listed pages from their order; unlisted pages get neither and are skipped over
+fn link_adjacent_pages(pages: &mut [PageData]) {
+    let listed: Vec<usize> = (0..pages.len()).filter(|&i| pages[i].is_listed()).collect();
+    let links: Vec<PageLink> = listed.iter().map(|&i| PageLink::from(&pages[i])).collect();
+
+    for page in pages.iter_mut() {
+        page.prev = None;
+        page.next = None;
+    }
+    for (position, &index) in listed.iter().enumerate() {
+        pages[index].prev = position.checked_sub(1).map(|p| links[p].clone());
+        pages[index].next = links.get(position + 1).cloned();
+    }
+}
+
+/// Fills `related` on every page using the configured strategy, returning a warning for each
+/// explicit `related` entry that matches no page
+fn compute_related(
+    sections: &mut HashMap<String, SectionContent>,
+    related_by: RelatedBy,
+    limit: usize,
+) -> Vec<Warning> {
+    let mut related: HashMap<(String, String), Vec<PageLink>> = HashMap::new();
+    let mut warnings = Vec::new();
+
+    for (key, section) in sections.iter() {
+        for page in &section.pages {
+            let links = match related_by {
+                RelatedBy::Section => {
+                    let mut siblings: Vec<&PageData> = section
+                        .pages
+                        .iter()
+                        .filter(|other| other.relative_path != page.relative_path && other.is_listed())
+                        .collect();
+                    siblings.sort_by(|a, b| cmp_optional(a.published(), b.published(), true));
+                    siblings.into_iter().take(limit).map(PageLink::from).collect()
+                }
+                RelatedBy::Explicit => page
+                    .related_refs
+                    .iter()
+                    .filter_map(|reference| {
+                        let found = resolve_page_reference(sections, key, reference);
+                        if found.is_none() {
+                            warnings.push(Warning::UnknownRelatedPage {
+                                page: page.relative_path.clone(),
+                                reference: reference.clone(),
+                            });
+                        }
+                        found.filter(|p| p.is_listed()).map(PageLink::from)
+                    })
+                    .collect(),
+                RelatedBy::Tags => {
+                    let mut candidates: Vec<(usize, &PageData)> = sections
+                        .values()
+                        .flat_map(|section| &section.pages)
+                        .filter(|other| other.relative_path != page.relative_path && other.is_listed())
+                        .map(|other| (shared_tags(page, other), other))
+                        .filter(|(shared, _)| *shared > 0)
+                        .collect();
+                    candidates.sort_by(|(a_shared, a), (b_shared, b)| {
+                        b_shared
+                            .cmp(a_shared)
+                            .then_with(|| cmp_optional(a.published(), b.published(), true))
+                            .then_with(|| a.title.cmp(&b.title))
+                    });
+                    candidates.into_iter().take(limit).map(|(_, other)| PageLink::from(other)).collect()
+                }
+            };
+            related.insert((key.clone(), page.slug.clone()), links);
+        }
+    }
+
+    for (key, section) in sections.iter_mut() {
+        for page in section.pages.iter_mut() {
+            if let Some(links) = related.remove(&(key.clone(), page.slug.clone())) {
+                page.related = links;
+            }
+        }
+    }
+    warnings
+}
+
+/// Number of tags two pages have in common
+fn shared_tags(page: &PageData, other: &PageData) -> usize {
+    page.tags.iter().filter(|tag| other.tags.iter().any(|t| t.slug == tag.slug)).count()
+}
+
+/// Looks up a page by `slug` (within `current_section`) or by `section/slug`, ignoring a `.md` suffix
+fn resolve_page_reference<'a>(
+    sections: &'a HashMap<String, SectionContent>,
+    current_section: &str,
+    reference: &str,
+) -> Option<&'a PageData> {
+    let reference = reference.trim_matches('/').trim_end_matches(".md");
+    let (section_key, slug) = match reference.rsplit_once('/') {
+        Some((section_key, slug)) => (section_key, slug),
+        None => (current_section, reference),
+    };
+    sections
+        .get(section_key)?
+        .pages
+        .iter()
+        .find(|page| page.slug == slug)
+}
+
+const SITEMAP_CHANGEFREQS: &[&str] = &[
+    "always", "hourly", "daily", "weekly", "monthly", "yearly", "never",
+];
+
+/// Returns the priority if it lies within 0.0–1.0, adding a warning and discarding it otherwise
+fn valid_sitemap_priority(priority: f64, source: &str, warnings: &mut Vec<Warning>) -> Option<f64> {
+    if (0.0..=1.0).contains(&priority) {
+        Some(priority)
+    } else {
+        warnings.push(Warning::InvalidSitemapPriority { source: source.to_string(), priority });
+        None
+    }
+}
+
+/// Returns the change frequency if the sitemap protocol allows it, adding a warning and discarding it otherwise
+fn valid_sitemap_changefreq(changefreq: &str, source: &str, warnings: &mut Vec<Warning>) -> Option<String> {
+    let normalized = changefreq.trim().to_lowercase();
+    if SITEMAP_CHANGEFREQS.contains(&normalized.as_str()) {
+        Some(normalized)
+    } else {
+        warnings.push(Warning::InvalidSitemapChangefreq {
+            source: source.to_string(),
+            changefreq: changefreq.to_string(),
+        });
+        None
+    }
+}
+
+/// Sitemap hints with invalid values dropped, adding a warning for each
+fn validated_sitemap_hints(
+    priority: Option<f64>,
+    changefreq: Option<&str>,
+    source: &str,
+    warnings: &mut Vec<Warning>,
+) -> SitemapConfig {
+    SitemapConfig {
+        priority: priority.and_then(|p| valid_sitemap_priority(p, source, warnings)),
+        changefreq: changefreq.and_then(|f| valid_sitemap_changefreq(f, source, warnings)),
+    }
+}
+
+/// Fills in sitemap hints a page didn't set from already validated defaults, which combine the
+/// section `_index.md` values with the config's
+fn resolve_sitemap_hints(page: &mut PageData, defaults: &SitemapConfig) {
+    if page.sitemap_priority.is_none() {
+        page.sitemap_priority = defaults.priority;
+    }
+    if page.sitemap_changefreq.is_none() {
+        page.sitemap_changefreq = defaults.changefreq.clone();
+    }
+}
+
+/// A content issue found while loading or validating content. Only a warning unless the build
+/// runs with `--strict`
+#[derive(Debug, Clone, PartialEq)]
+enum Warning {
+    MissingSectionTitle { section: String },
+    UndatedPages { section: String, count: usize },
+    DuplicateSlug { slug: String, sources: Vec<String> },
+    /// A section with more than one `_index` file, such as `Notes/_index.md` and `notes/_index.markdown`
+    DuplicateSectionIndex { section: String, sources: Vec<String> },
+    /// A front matter key that looks like a typo of `suggestion`
+    UnknownFrontMatterKey { source: String, key: String, suggestion: String },
+    /// A file in a later content directory replacing `previous`, either at the same relative path
+    /// or, when `output` is set, by writing the same page
+    ContentOverride { source: String, previous: String, output: Option<String> },
+    /// A `sitemap_priority` outside 0.0–1.0, which is dropped
+    InvalidSitemapPriority { source: String, priority: f64 },
+    /// A `sitemap_changefreq` the sitemap protocol doesn't allow, which is dropped
+    InvalidSitemapChangefreq { source: String, changefreq: String },
+    /// An undated page under a permalink pattern that needs a date, placed at `fallback` instead
+    MissingPermalinkDate { source: String, pattern: String, fallback: String },
+    /// An explicit `related` entry that matches no page
+    UnknownRelatedPage { page: String, reference: String },
+    /// A taxonomy in use without `template`, so `skipped` isn't rendered
+    MissingTaxonomyTemplate { taxonomy: String, template: String, skipped: String },
+    /// A listed page the archive leaves out for having no date
+    UndatedArchivePage { source: String },
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::MissingSectionTitle { section } => write!(f, "Section '{}' has no title", section),
+            Warning::UndatedPages { section, count } => {
+                write!(f, "Section '{}' has {} pages without dates (may affect sorting)", section, count)
+            }
+            Warning::DuplicateSlug { slug, sources } => {
+                write!(f, "Duplicate slug '{}' in {} files: {}", slug, sources.len(), sources.join(", "))
+            }
+            Warning::DuplicateSectionIndex { section, sources } => write!(
+                f,
+                "Section '{}' is defined by more than one index file ({}); the last one's front matter wins",
+                section,
+                sources.join(", ")
+            ),
+            Warning::UnknownFrontMatterKey { source, key, suggestion } => {
+                write!(f, "Unknown front matter key `{}` in {} (did you mean `{}`?)", key, source, suggestion)
+            }
+            Warning::ContentOverride { source, previous, output } => {
+                write!(f, "{} overrides {} from an earlier content directory", source, previous)?;
+                match output {
+                    Some(output) => write!(f, " (both write {})", output),
+                    None => Ok(()),
+                }
+            }
+            Warning::InvalidSitemapPriority { source, priority } => {
+                write!(f, "Ignoring sitemap_priority {} in {} (must be between 0.0 and 1.0)", priority, source)
+            }
+            Warning::InvalidSitemapChangefreq { source, changefreq } => write!(
+                f,
+                "Ignoring sitemap_changefreq '{}' in {} (expected one of: {})",
+                changefreq,
+                source,
+                SITEMAP_CHANGEFREQS.join(", ")
+            ),
+            Warning::MissingPermalinkDate { source, pattern, fallback } => {
+                write!(f, "{} has no date for permalink pattern '{}'; using {}/", source, pattern, fallback)
+            }
+            Warning::UnknownRelatedPage { page, reference } => {
+                write!(f, "Page '{}' lists unknown related page '{}'", page, reference)
+            }
+            Warning::MissingTaxonomyTemplate { taxonomy, template, skipped } => {
+                write!(f, "Pages use {} but templates/{} is missing; skipping {}", taxonomy, template, skipped)
+            }
+            Warning::UndatedArchivePage { source } => {
+                write!(f, "{} has no date and is left out of the archive", source)
+            }
+        }
+    }
+}
+
+/// Checks loaded content for common issues, in section order then duplicate slugs
+fn validate_content(sections: &HashMap<String, SectionContent>) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut keys: Vec<&String> = sections.keys().collect();
+    keys.sort();
+
+    for section_key in keys {
+        let section = &sections[section_key];
+        // Check for missing titles in section metadata
+        if section.meta.title.is_none() {
+            warnings.push(Warning::MissingSectionTitle { section: section_key.clone() });
+        }
+
+        // Check for pages without dates (affects sorting)
+        let undated = section.pages.iter().filter(|p| p.date.is_none()).count();
+        if undated > 0 {
+            warnings.push(Warning::UndatedPages { section: section_key.clone(), count: undated });
+        }
+    }
+
+    for (slug, sources) in duplicate_slugs(sections) {
+        warnings.push(Warning::DuplicateSlug { slug, sources });
+    }
+    warnings
+}
+
+/// Prints content warnings, or with `strict` fails the build listing all of them
+fn report_content_warnings(warnings: &[Warning], strict: bool) -> Result<()> {
+    if strict && !warnings.is_empty() {
+        let list: Vec<String> = warnings.iter().map(|warning| format!("  - {}", warning)).collect();
+        anyhow::bail!("{} content warning(s) with --strict:\n{}", warnings.len(), list.join("\n"));
+    }
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+    Ok(())
+}
+
+/// A warning for each section key claimed by more than one `_index` file, e.g. `Notes/_index.md`
+/// and `notes/_index.markdown`, listing those files in walk order. The same relative path in a later
+/// content directory is an override rather than a second definition, so only its last copy counts
+fn duplicate_section_indexes(files: &[(PathBuf, PathBuf, usize)]) -> Vec<Warning> {
+    // Section key -> relative index path -> file providing it
+    let mut indexes: BTreeMap<String, Vec<(&Path, &Path)>> = BTreeMap::new();
+    for (path, relative, _) in files {
+        if path.file_stem().is_none_or(|stem| stem != "_index") {
+            continue;
+        }
+        let key = relative.parent().map(section_key).unwrap_or_default();
+        let definitions = indexes.entry(key).or_default();
+        match definitions.iter_mut().find(|(existing, _)| *existing == relative.as_path()) {
+            Some(definition) => definition.1 = path,
+            None => definitions.push((relative, path)),
+        }
+    }
+    indexes
+        .into_iter()
+        .filter(|(_, definitions)| definitions.len() > 1)
+        .map(|(section, definitions)| Warning::DuplicateSectionIndex {
+            section,
+            sources: definitions.into_iter().map(|(_, path)| path.display().to_string()).collect(),
+        })
+        .collect()
+}
+
+/// Slugs used by more than one content file, with the files that use them, both sorted
+fn duplicate_slugs(sections: &HashMap<String, SectionContent>) -> Vec<(String, Vec<String>)> {
+    let mut seen_slugs: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for page in sections.values().flat_map(|section| &section.pages) {
+        seen_slugs.entry(&page.slug).or_default().insert(&page.source);
+    }
+    seen_slugs
+        .into_iter()
+        .filter(|(_, sources)| sources.len() > 1)
+        .map(|(slug, sources)| (slug.to_string(), sources.into_iter().map(str::to_string).collect()))
+        .collect()
+}
+
+/// Returns true when `permalink` and `relative_path` describe the same location
+/// under `base_url` and the output directory respectively
+fn permalink_matches_relative_path(base_url: &str, permalink: &str, relative_path: &str) -> bool {
+    let Some(url_path) = permalink.strip_prefix(base_url) else {
+        return false;
+    };
+    let url_path = url_path.trim_start_matches('/');
+    let disk_path = relative_path
+        .strip_suffix("index.html")
+        .unwrap_or(relative_path);
+    url_path == disk_path
+}
+
+/// Errors if any page's permalink and relative_path have drifted apart
+fn check_path_consistency(sections: &HashMap<String, SectionContent>, base_url: &str) -> Result<()> {
+    for (section_key, section) in sections {
+        for page in &section.pages {
+            if !permalink_matches_relative_path(base_url, &page.permalink, &page.relative_path) {
+                anyhow::bail!(
+                    "page '{}' in section '{}' has inconsistent paths: permalink {} does not match relative_path {}",
+                    page.slug,
+                    section_key,
+                    page.permalink,
+                    page.relative_path
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Errors when two of the files a build writes would land on the same output path: pages, their
+/// plain variants and aliases, section listings and their pagination, taxonomy terms and feeds,
+/// the archive, error pages, site feeds and data files, and copied assets. `static_dir` is where
+/// static files are copied from, or `None` when the build doesn't copy them
+fn check_output_collisions(
+    tera: &Tera,
+    sections: &HashMap<String, SectionContent>,
+    config: &Config,
+    static_dir: Option<&Path>,
+) -> Result<()> {
+    let mut keys: Vec<&String> = sections.keys().collect();
+    keys.sort();
+    for key in keys {
+        if let Some(file) = sections[key].meta.output_filename.as_ref().filter(|file| !is_plain_file_name(file)) {
+            anyhow::bail!("section '{}' has output_filename {:?}, which must be a plain file name", key, file);
+        }
+    }
+
+    // Output path -> what writes it
+    let mut destinations: HashMap<String, String> = HashMap::new();
+    for (path, owner) in generated_outputs(tera, sections, config, static_dir)? {
+        if let Some(existing) = destinations.get(&path) {
+            anyhow::bail!("{} and {} both write {}", existing, owner, path);
+        }
+        destinations.insert(path, owner);
+    }
+    Ok(())
+}
+
+/// Every file a build writes, as (output path, what writes it), in a stable order. Mirrors the
+/// render_* and copy_* functions, including their template and `[extra]` checks, so optional
+/// outputs only count when they will be written. Fingerprinted asset copies aren't included
+fn generated_outputs(
+    tera: &Tera,
+    sections: &HashMap<String, SectionContent>,
+    config: &Config,
+    static_dir: Option<&Path>,
+) -> Result<Vec<(String, String)>> {
+    let has_template = |name: &str| tera.get_template_names().any(|n| n == name);
+    let mut keys: Vec<&String> = sections.keys().collect();
+    keys.sort();
+
+    let mut outputs = vec![("index.html".to_string(), "the home page".to_string())];
+    for key in &keys {
+        let section = &sections[*key];
+        let paginate_by = section.meta.paginate_by.filter(|n| *n > 0);
+        match paginate_by {
+            Some(paginate_by) if section_template(section) != "page.html" && section.meta.output_filename.is_none() => {
+                let listed = section.pages.iter().filter(|page| page.is_listed()).count();
+                for index in 1..=listed.div_ceil(paginate_by).max(1) {
+                    let owner = match index {
+                        1 => format!("section '{}'", key),
+                        _ => format!("page {} of section '{}'", index, key),
+                    };
+                    outputs.push((pager_path(key, index, config), owner));
+                }
+            }
+            _ => outputs.push((section_output_paths(key, &section.meta, config).1, format!("section '{}'", key))),
+        }
+    }
+    for key in &keys {
+        for page in &sections[*key].pages {
+            outputs.push((page.relative_path.clone(), format!("page {}", page.source)));
+            if config.plain_pages {
+                let owner = format!("the plain variant of page {}", page.source);
+                outputs.push((plain_output_path(&page.relative_path), owner));
+            }
+            for alias in &page.aliases {
+                outputs.push((alias.clone(), format!("an alias of page {}", page.source)));
+            }
+        }
+    }
+
+    for (taxonomy, terms) in [(&TAGS, collect_tags(sections)), (&CATEGORIES, collect_categories(sections))] {
+        if terms.is_empty() {
+            continue;
+        }
+        if has_template(&format!("{}.html", taxonomy.name)) {
+            outputs.push((url_paths(taxonomy.name, config).1, format!("the {} index", taxonomy.singular)));
+        }
+        let term_pages = has_template(&format!("{}.html", taxonomy.singular));
+        for term in terms.values() {
+            if term_pages {
+                outputs.push((term.relative_path.clone(), format!("{} '{}'", taxonomy.singular, term.name)));
+            }
+            for feed in term_feed_links(config, taxonomy, term) {
+                outputs.push((feed.relative_path, format!("a feed of {} '{}'", taxonomy.singular, term.name)));
+            }
+        }
+    }
+
+    if has_template("archive.html") {
+        outputs.push((url_paths(ARCHIVE_DIR, config).1, "the archive".to_string()));
+        if config.extra_bool("archive_by_year") {
+            let years: BTreeSet<i32> = sections
+                .get(FEED_SECTION)
+                .map(|section| section.pages.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter(|page| page.is_listed())
+                .filter_map(|page| page.date.map(|date| date.year()))
+                .collect();
+            for year in years.into_iter().rev() {
+                let path = url_paths(&format!("{}/{}", ARCHIVE_DIR, year), config).1;
+                outputs.push((path, format!("the archive for {}", year)));
+            }
+        }
+    }
+
+    for page in config.error_pages()? {
+        outputs.push((page.path, format!("the {} page", page.status)));
+    }
+
+    for (path, owner) in [
+        (RSS_FEED_PATH, "the RSS feed"),
+        (ATOM_FEED_PATH, "the Atom feed"),
+        (JSON_FEED_PATH, "the JSON feed"),
+        (SITEMAP_PATH, "the sitemap"),
+    ] {
+        outputs.push((path.to_string(), owner.to_string()));
+    }
+    if !static_dir.is_some_and(|dir| dir.join(ROBOTS_PATH).exists()) {
+        outputs.push((ROBOTS_PATH.to_string(), "robots.txt".to_string()));
+    }
+    if config.extra_bool("build_search_index") {
+        outputs.push((SEARCH_INDEX_PATH.to_string(), "the search index".to_string()));
+    }
+    if config.extra_bool("section_json") {
+        for key in &keys {
+            let path = url_key(&Path::new(key.as_str()).join(SECTION_JSON_FILE));
+            outputs.push((path, format!("the data file of section '{}'", key)));
+        }
+    }
+    if config.extra_bool("highlight_code") {
+        outputs.push((SYNTAX_CSS_PATH.to_string(), "the syntax highlighting stylesheet".to_string()));
+    }
+
+    if let Some(static_dir) = static_dir.filter(|dir| dir.exists()) {
+        for entry in WalkDir::new(static_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            if entry.path().is_file() {
+                let path = url_key(entry.path().strip_prefix(static_dir).unwrap());
+                outputs.push((path.clone(), format!("the static file {}", path)));
+            }
+        }
+        if config.extra_bool("fingerprint_assets") {
+            outputs.push((ASSET_MANIFEST_PATH.to_string(), "the asset manifest".to_string()));
+        }
+    }
+    for content_dir in &config.content {
+        for entry in WalkDir::new(content_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && !is_markdown_file(e.path(), &config.markdown_extensions))
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        {
+            let relative = entry.path().strip_prefix(content_dir).context("stripping content prefix")?;
+            let owner = format!("the content file {}", entry.path().display());
+            outputs.push((url_key(&content_asset_path(relative)), owner));
+        }
+    }
+    Ok(outputs)
+}
+
+/// True for a name that stays in the directory it's joined to: no separators, `.`, or `..`
+fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// Template a section renders with: its `template`, or `section.html`
+fn section_template(section: &SectionContent) -> &str {
+    section.meta.template.as_deref().unwrap_or("section.html")
+}
+
+/// Template a page renders with: its own `template`, then its section's `default_template`, then
+/// `page.html`. The section's own `template` only renders the section itself
+fn page_template<'a>(page: &'a PageData, section: &'a SectionContent) -> &'a str {
+    page.template
+        .as_deref()
+        .or(section.meta.default_template.as_deref())
+        .unwrap_or("page.html")
+}
+
+/// Errors before rendering when any template the site needs is missing, listing who asked for each
+fn check_templates_exist(tera: &Tera, config: &Config, sections: &HashMap<String, SectionContent>) -> Result<()> {
+    let available: HashSet<&str> = tera.get_template_names().collect();
+    let error_pages = config.error_pages()?;
+    let mut requested: Vec<(&str, String)> = vec![("index.html", "the home page".to_string())];
+    for page in &error_pages {
+        requested.push((&page.template, format!("the {} page", page.status)));
+    }
+    let mut keys: Vec<&String> = sections.keys().collect();
+    keys.sort();
+    for key in keys {
+        let section = &sections[key];
+        requested.push((section_template(section), format!("section '{}'", key)));
+        for page in &section.pages {
+            requested.push((page_template(page, section), format!("page {}", page.source)));
+        }
+    }
+
+    // Missing template -> what requested it
+    let mut missing: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (template, owner) in requested {
+        if !available.contains(template) {
+            missing.entry(template).or_default().push(owner);
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let report: Vec<String> = missing
+        .iter()
+        .map(|(template, owners)| format!("  {} (requested by {})", template, owners.join(", ")))
+        .collect();
+    anyhow::bail!("missing templates:\n{}", report.join("\n"))
+}
+
+fn render_home(
+    tera: &Tera,
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    output: &Output,
+    root_section: &SectionData,
+) -> Result<()> {
+    let mut context = build_base_context(config, nav, built_at, "");
+    context.insert("section", root_section);
+    context.insert("body_class", "home");
+
+    let home_section = config.extra_str("home_section").unwrap_or(DEFAULT_HOME_SECTION);
+    if let Some(section) = sections.get(home_section) {
+        context.insert("writing_pages", &listed_pages(&section.pages));
+    } else {
+        context.insert("writing_pages", &Vec::<PageData>::new());
+    }
+
+    let all_sections: BTreeMap<&String, SectionData> = sections
+        .iter()
+        .map(|(key, section)| (key, section_data(key, section, sections, config)))
+        .collect();
+    context.insert("sections", &all_sections);
+
+    render_template_to_file(
+        tera,
+        config,
+        "index.html",
+        &context,
+        output, &output.dir.join("index.html"),
+        "homepage",
+    )
+}
+
+/// Template-facing view of a section with its listed pages
+fn section_data(
+    key: &str,
+    section: &SectionContent,
+    sections: &HashMap<String, SectionContent>,
+    config: &Config,
+) -> SectionData {
+    let (permalink, relative_path) = section_output_paths(key, &section.meta, config);
+    SectionData {
+        permalink,
+        relative_path,
+        title: section_title(key, &section.meta, config),
+        description: section.meta.description.clone(),
+        meta_description: section.meta.description.clone().unwrap_or_else(|| config.description.clone()),
+        pages: listed_pages(&section.pages),
+        content: section.body_html.clone(),
+        subsections: section_links(&section.subsections, sections, config),
+        breadcrumbs: breadcrumbs(key, sections, config),
+    }
+}
+
+/// Crumbs from the home page down to the section at `key`. Sections without an `_index.md`
+/// title are named after their directory, and link to their `output_filename` when they set one
+fn breadcrumbs(key: &str, sections: &HashMap<String, SectionContent>, config: &Config) -> Vec<Crumb> {
+    let mut crumbs = vec![Crumb { title: config.title.clone(), permalink: section_paths("", config).0 }];
+    let mut ancestor = String::new();
+    for part in key.split('/').filter(|part| !part.is_empty()) {
+        if !ancestor.is_empty() {
+            ancestor.push('/');
+        }
+        ancestor.push_str(part);
+        let (title, permalink) = match sections.get(&ancestor) {
+            Some(section) => (
+                section_title(&ancestor, &section.meta, config),
+                section_output_paths(&ancestor, &section.meta, config).0,
+            ),
+            None => (humanize_slug(part, &config.title_acronyms()), section_paths(&ancestor, config).0),
+        };
+        crumbs.push(Crumb { title, permalink });
+    }
+    crumbs
 }
 
 fn render_sections(
     tera: &Tera,
     config: &Config,
     sections: &HashMap<String, SectionContent>,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    output: &Output,
+) -> Result<usize> {
+    for (key, section_content) in sections.iter() {
+        let template = section_template(section_content);
+        let (permalink, relative_path) = section_output_paths(key, &section_content.meta, config);
+
+        // `writing/index.html` and `writing/feed.xml` sit one directory deeper than `writing.html`
+        let flattened = config.ugly_urls && !key.is_empty() && section_content.meta.output_filename.is_none();
+        let depth = calculate_path_depth(key, false) - usize::from(flattened);
+        let path_prefix = path_prefix_for_depth(depth);
+
+        if template == "page.html" {
+            let (summary, summary_text) = match &section_content.meta.summary {
+                Some(summary) => {
+                    let (html, text) = summary_variants(summary, &MarkdownSettings::from_config(config));
+                    (Some(html), Some(text))
+                }
+                None => (None, None),
+            };
+            let page = PageData {
+                title: section_title(key, &section_content.meta, config),
+                date: section_content.meta.date,
+                datetime: section_content.meta.published.map(|published| published.to_utc()),
+                updated: section_content.meta.updated,
+                extra: section_content.meta.template_extra(),
+                description: section_content.meta.description.clone(),
+                image: section_content
+                    .meta
+                    .image
+                    .as_deref()
+                    .map(|image| absolute_url(image, &config.base_url)),
+                lang: section_content.meta.lang.clone(),
+                summary,
+                summary_text,
+                content: section_content.body_html.clone(),
+                word_count: 0,
+                reading_time_minutes: 0,
+                permalink,
+                relative_path: relative_path.clone(),
+                aliases: Vec::new(),
+                template: section_content.meta.template.clone(),
+                slug: key.clone(),
+                weight: section_content.meta.weight,
+                visibility: Visibility::Public,
+                draft: false,
+                sitemap_priority: section_content.meta.sitemap_priority,
+                sitemap_changefreq: section_content.meta.sitemap_changefreq.clone(),
+                related: Vec::new(),
+                prev: None,
+                next: None,
+                tags: Vec::new(),
+                category: None,
+                toc: Vec::new(),
+                breadcrumbs: breadcrumbs(key, sections, config),
+                related_refs: Vec::new(),
+                source: String::new(),
+            };
+
+            let mut context = page_context(config, nav, built_at, key, &page, &path_prefix);
+            context.insert("body_class", &body_class(key, None));
+
+            render_template_to_file(
+                tera,
+                config,
+                template,
+                &context,
+                output, &output.dir.join(&relative_path),
+                &format!("section page {}", key),
+            )?;
+            continue;
+        }
+
+        let section = section_data(key, section_content, sections, config);
+
+        let paginate_by = section_content.meta.paginate_by.filter(|n| *n > 0);
+        if let Some(paginate_by) = paginate_by.filter(|_| section_content.meta.output_filename.is_none()) {
+            for paginator in paginate(&section.pages, paginate_by, key, config) {
+                // Listing pages after the first live deeper, under page/<n>/
+                let depth = paginator.relative_path.matches('/').count();
+                let mut context = build_base_context(config, nav, built_at, &path_prefix_for_depth(depth));
+                context.insert("section", &section);
+                context.insert("paginator", &paginator);
+                context.insert("body_class", &body_class(key, None));
+
+                render_template_to_file(
+                    tera,
+                    config,
+                    template,
+                    &context,
+                    output, &output.dir.join(&paginator.relative_path),
+                    &format!("section {} page {}", key, paginator.current_index),
+                )?;
+            }
+            continue;
+        }
+
+        let mut context = build_base_context(config, nav, built_at, &path_prefix);
+        context.insert("section", &section);
+        context.insert("body_class", &body_class(key, None));
+
+        render_template_to_file(
+            tera,
+            config,
+            template,
+            &context,
+            output, &output.dir.join(&relative_path),
+            &format!("section {}", key),
+        )?;
+    }
+
+    Ok(sections.len())
+}
+
+/// Output path of listing page `index` (1-based) for the section at `key`
+fn pager_path(key: &str, index: usize, config: &Config) -> String {
+    if index == 1 {
+        section_paths(key, config).1
+    } else {
+        url_paths(format!("{}/page/{}", key, index).trim_start_matches('/'), config).1
+    }
+}
+
+/// Splits a section's pages into listing pages of `paginate_by`; an empty section still gets one
+fn paginate(pages: &[PageData], paginate_by: usize, key: &str, config: &Config) -> Vec<Paginator> {
+    let chunks: Vec<&[PageData]> = if pages.is_empty() {
+        vec![&[]]
+    } else {
+        pages.chunks(paginate_by).collect()
+    };
+    let number_pagers = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let current_index = i + 1;
+            Paginator {
+                current_index,
+                number_pagers,
+                paginate_by,
+                total_pages: pages.len(),
+                pages: chunk.to_vec(),
+                first: pager_path(key, 1, config),
+                last: pager_path(key, number_pagers, config),
+                previous: (current_index > 1).then(|| pager_path(key, current_index - 1, config)),
+                next: (current_index < number_pagers).then(|| pager_path(key, current_index + 1, config)),
+                relative_path: pager_path(key, current_index, config),
+            }
+        })
+        .collect()
+}
+
+fn render_pages(
+    tera: &Tera,
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    output: &Output,
+    only: Option<&HashSet<String>>,
+) -> Result<usize> {
+    let pages: Vec<(&String, &SectionContent, &PageData)> = sections
+        .iter()
+        .flat_map(|(key, section)| section.pages.iter().map(move |page| (key, section, page)))
+        .filter(|(_, _, page)| only.is_none_or(|only| only.contains(&page.relative_path)))
+        .collect();
+
+    // Every page writes its own index.html, and create_dir_all tolerates concurrent
+    // creation of shared parent directories, so pages can render in parallel
+    pages.par_iter().try_for_each(|(key, section, page)| {
+        let page_template = page_template(page, section);
+
+        // Pages live wherever their permalink puts them, one directory per path segment
+        let depth = page.relative_path.matches('/').count();
+        let path_prefix = path_prefix_for_depth(depth);
+
+        let context = page_context(config, nav, built_at, key, page, &path_prefix);
+
+        render_template_to_file(
+            tera,
+            config,
+            page_template,
+            &context,
+            output, &output.dir.join(&page.relative_path),
+            &format!("page {}", page.title),
+        )?;
+
+        if config.plain_pages {
+            let plain_path = plain_output_path(&page.relative_path);
+            let path_prefix = path_prefix_for_depth(plain_path.matches('/').count());
+            render_template_to_file(
+                tera,
+                config,
+                PLAIN_TEMPLATE,
+                &page_context(config, nav, built_at, key, page, &path_prefix),
+                output, &output.dir.join(&plain_path),
+                &format!("plain page {}", page.title),
+            )?;
+        }
+
+        for alias in &page.aliases {
+            let alias_path = output.dir.join(alias);
+            let lang = page.lang.as_deref().unwrap_or(&config.lang);
+            output.write(&alias_path, redirect_stub(&page.permalink, lang))
+                .with_context(|| format!("writing alias {} to {:?}", alias, alias_path))?;
+        }
+        Ok::<_, anyhow::Error>(())
+    })?;
+    Ok(pages.len())
+}
+
+/// Turns a root-relative alias URL into the output file that redirects from it
+fn alias_output_path(alias: &str) -> Result<String> {
+    if !alias.starts_with('/') || alias.starts_with("//") {
+        anyhow::bail!("alias '{}' must be a root-relative path such as /old/url/", alias);
+    }
+    let segments: Vec<&str> = alias.split('/').filter(|segment| !segment.is_empty()).collect();
+    if segments.iter().any(|segment| *segment == "." || *segment == "..") {
+        anyhow::bail!("alias '{}' must stay inside the site", alias);
+    }
+    if segments.is_empty() {
+        anyhow::bail!("alias '{}' would replace the home page", alias);
+    }
+    let path = segments.join("/");
+    if path.ends_with(".html") {
+        Ok(path)
+    } else {
+        Ok(format!("{}/index.html", path))
+    }
+}
+
+/// A minimal page in `lang` that sends visitors and crawlers on to `permalink`
+fn redirect_stub(permalink: &str, lang: &str) -> String {
+    let url = escape_html(permalink);
+    let lang = escape_html(lang);
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"{lang}\">\n<head>\n  <meta charset=\"utf-8\">\n  \
+         <title>Redirecting…</title>\n  <link rel=\"canonical\" href=\"{url}\">\n  \
+         <meta http-equiv=\"refresh\" content=\"0; url={url}\">\n</head>\n\
+         <body>\n  <p>This page has moved to <a href=\"{url}\">{url}</a>.</p>\n</body>\n</html>\n"
+    )
+}
+
+/// Template context for a page, including its social `meta` tags
+fn page_context(
+    config: &Config,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    section_key: &str,
+    page: &PageData,
+    path_prefix: &str,
+) -> TeraContext {
+    let mut context = build_base_context(config, nav, built_at, path_prefix);
+    if let Some(lang) = &page.lang {
+        context.insert("lang", lang);
+    }
+    context.insert("page", page);
+    context.insert("meta", &social_meta(page));
+    context.insert("body_class", &body_class(section_key, Some(&page.slug)));
+    context
+}
+
+/// Where a page's plain variant goes: `writing/post/plain.html` for `writing/post/index.html`,
+/// and `writing/post.plain.html` next to `writing/post.html` under `ugly_urls`
+fn plain_output_path(relative_path: &str) -> String {
+    match relative_path.strip_suffix("index.html") {
+        Some(dir) => format!("{}plain.html", dir),
+        None => format!("{}.plain.html", relative_path.trim_end_matches(".html")),
+    }
+}
+
+/// Open Graph and Twitter Card tags keyed by property name, e.g. `og:title`
+fn social_meta(page: &PageData) -> BTreeMap<&'static str, String> {
+    let mut meta = BTreeMap::new();
+    let description = page.description.clone().or_else(|| page.summary_text.clone());
+
+    meta.insert("og:title", page.title.clone());
+    meta.insert("og:url", page.permalink.clone());
+    meta.insert("og:type", "article".to_string());
+    meta.insert("twitter:title", page.title.clone());
+    if let Some(description) = description {
+        meta.insert("og:description", description.clone());
+        meta.insert("twitter:description", description);
+    }
+    match &page.image {
+        Some(image) => {
+            meta.insert("og:image", image.clone());
+            meta.insert("twitter:image", image.clone());
+            meta.insert("twitter:card", "summary_large_image".to_string());
+        }
+        None => {
+            meta.insert("twitter:card", "summary".to_string());
+        }
+    }
+    meta
+}
+
+/// Leaves absolute URLs alone and resolves anything else against the site root
+fn absolute_url(url: &str, base_url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//") {
+        url.to_string()
+    } else {
+        format!("{}/{}", base_url, url.trim_start_matches('/'))
+    }
+}
+
+/// Shortens text to at most `max_chars` characters at a word boundary, adding an ellipsis
+fn truncate_text(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    for word in text.split_whitespace() {
+        if truncated.chars().count() + word.chars().count() + 1 > max_chars {
+            break;
+        }
+        if !truncated.is_empty() {
+            truncated.push(' ');
+        }
+        truncated.push_str(word);
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// Plain-text description for feeds: the summary, or the start of the content
+fn feed_description(page: &PageData) -> String {
+    page.summary_text
+        .clone()
+        .unwrap_or_else(|| truncate_text(&strip_html(&page.content), 200))
+}
+
+/// Pages the site-wide feeds carry: the writing section's listed pages, newest first, up to `feed_limit`
+fn feed_pages<'a>(config: &Config, sections: &'a HashMap<String, SectionContent>) -> Vec<&'a PageData> {
+    sections
+        .get(FEED_SECTION)
+        .map(|section| section.pages.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|page| page.is_listed())
+        .take(config.feed_limit)
+        .collect()
+}
+
+/// Builds an RSS 2.0 document with an item for each of `pages`, in order
+fn build_rss_feed(config: &Config, channel: &FeedChannel, pages: &[&PageData]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("  <channel>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", escape_html(&channel.title)));
+    xml.push_str(&format!("    <link>{}</link>\n", escape_html(&channel.link)));
+    xml.push_str(&format!("    <description>{}</description>\n", escape_html(&config.description)));
+    xml.push_str(&format!(
+        "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape_html(&channel.self_url)
+    ));
+
+    for page in pages {
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!("      <title>{}</title>\n", escape_html(&page.title)));
+        xml.push_str(&format!("      <link>{}</link>\n", escape_html(&page.permalink)));
+        xml.push_str(&format!("      <guid isPermaLink=\"true\">{}</guid>\n", escape_html(&page.permalink)));
+        if let Some(published) = page.published() {
+            xml.push_str(&format!("      <pubDate>{}</pubDate>\n", published.to_rfc2822()));
+        }
+        xml.push_str(&format!("      <description>{}</description>\n", escape_html(&feed_description(page))));
+        xml.push_str("    </item>\n");
+    }
+
+    xml.push_str("  </channel>\n</rss>\n");
+    xml
+}
+
+/// Writes the RSS feed for the writing section
+fn render_feed(
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    output: &Output,
+) -> Result<()> {
+    let channel = FeedChannel::site(config, RSS_FEED_PATH);
+    let output_path = output.dir.join(RSS_FEED_PATH);
+    output.write(&output_path, build_rss_feed(config, &channel, &feed_pages(config, sections)))
+        .with_context(|| format!("writing RSS feed to {:?}", output_path))
+}
+
+/// Builds an Atom 1.0 document with an entry for each of `pages`, in order. A feed with no dated
+/// pages reports the Unix epoch as its `updated` time, so rebuilding it doesn't change it
+fn build_atom_feed(config: &Config, channel: &FeedChannel, pages: &[&PageData]) -> String {
+    let feed_updated = pages
+        .iter()
+        .filter_map(|page| page.last_modified_at())
+        .max()
+        .unwrap_or(DateTime::UNIX_EPOCH)
+        .to_rfc3339();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape_html(&channel.title)));
+    xml.push_str(&format!("  <subtitle>{}</subtitle>\n", escape_html(&config.description)));
+    xml.push_str(&format!("  <id>{}</id>\n", escape_html(&channel.link)));
+    xml.push_str(&format!("  <link href=\"{}\"/>\n", escape_html(&channel.link)));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"self\" type=\"application/atom+xml\"/>\n",
+        escape_html(&channel.self_url)
+    ));
+    xml.push_str(&format!("  <updated>{}</updated>\n", feed_updated));
+    xml.push_str(&format!("  <author><name>{}</name></author>\n", escape_html(&config.title)));
+
+    for page in pages {
+        let updated = page.last_modified_at().map(|at| at.to_rfc3339()).unwrap_or_else(|| feed_updated.clone());
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape_html(&page.title)));
+        xml.push_str(&format!("    <link href=\"{}\"/>\n", escape_html(&page.permalink)));
+        xml.push_str(&format!("    <id>{}</id>\n", escape_html(&page.permalink)));
+        xml.push_str(&format!("    <updated>{}</updated>\n", updated));
+        if let Some(published) = page.published() {
+            xml.push_str(&format!("    <published>{}</published>\n", published.to_rfc3339()));
+        }
+        xml.push_str(&format!("    <summary>{}</summary>\n", escape_html(&feed_description(page))));
+        xml.push_str(&format!("    <content type=\"html\">{}</content>\n", escape_html(&page.content)));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Writes the Atom feed for the writing section
+fn render_atom_feed(
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    output: &Output,
+) -> Result<()> {
+    let channel = FeedChannel::site(config, ATOM_FEED_PATH);
+    let output_path = output.dir.join(ATOM_FEED_PATH);
+    output.write(&output_path, build_atom_feed(config, &channel, &feed_pages(config, sections)))
+        .with_context(|| format!("writing Atom feed to {:?}", output_path))
+}
+
+/// A JSON Feed 1.1 document (<https://www.jsonfeed.org/version/1.1/>)
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct JsonFeed {
+    version: String,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    items: Vec<JsonFeedItem>,
+}
+
+/// One JSON Feed item; dates are RFC 3339
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    content_html: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_modified: Option<String>,
+}
+
+fn build_json_feed(config: &Config, pages: &[&PageData]) -> JsonFeed {
+    JsonFeed {
+        version: "https://jsonfeed.org/version/1.1".to_string(),
+        title: config.title.clone(),
+        home_page_url: format!("{}/", config.base_url),
+        feed_url: format!("{}/{}", config.base_url, JSON_FEED_PATH),
+        description: Some(config.description.clone()).filter(|d| !d.is_empty()),
+        items: pages
+            .iter()
+            .map(|page| JsonFeedItem {
+                id: page.permalink.clone(),
+                url: page.permalink.clone(),
+                title: page.title.clone(),
+                content_html: page.content.clone(),
+                summary: page.summary_text.clone(),
+                date_published: page.published().map(|at| at.to_rfc3339()),
+                date_modified: page.updated.map(|date| midnight_utc(date).to_rfc3339()),
+            })
+            .collect(),
+    }
+}
+
+/// Writes the JSON Feed for the writing section
+fn render_json_feed(
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    output: &Output,
+) -> Result<()> {
+    let json = serde_json::to_string_pretty(&build_json_feed(config, &feed_pages(config, sections)))
+        .context("serializing JSON feed")?;
+    let output_path = output.dir.join(JSON_FEED_PATH);
+    output.write(&output_path, json).with_context(|| format!("writing JSON feed to {:?}", output_path))
+}
+
+/// One `<url>` element of the sitemap
+#[derive(Debug, Clone, PartialEq)]
+struct SitemapEntry {
+    loc: String,
+    lastmod: Option<NaiveDate>,
+    changefreq: Option<String>,
+    priority: Option<f64>,
+}
+
+/// Collects the home page, every section index, and every listed page
+fn collect_sitemap_entries(
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+) -> Vec<SitemapEntry> {
+    let mut entries = vec![SitemapEntry {
+        loc: format!("{}/", config.base_url),
+        lastmod: None,
+        changefreq: config.sitemap.changefreq.clone(),
+        priority: config.sitemap.priority,
+    }];
+
+    let mut keys: Vec<&String> = sections.keys().filter(|k| !k.is_empty()).collect();
+    keys.sort();
+    for key in keys {
+        let section = &sections[key];
+        // Feeds and other documents a section renders via `output_filename` aren't pages
+        if section.meta.output_filename.is_some() {
+            continue;
+        }
+        entries.push(SitemapEntry {
+            loc: section_paths(key, config).0,
+            lastmod: section.pages.iter().filter(|p| p.is_listed()).filter_map(PageData::last_modified).max(),
+            changefreq: section.meta.sitemap_changefreq.clone().or_else(|| config.sitemap.changefreq.clone()),
+            priority: section.meta.sitemap_priority.or(config.sitemap.priority),
+        });
+    }
+
+    let mut keys: Vec<&String> = sections.keys().collect();
+    keys.sort();
+    for key in keys {
+        for page in sections[key].pages.iter().filter(|p| p.is_listed()) {
+            entries.push(SitemapEntry {
+                loc: page.permalink.clone(),
+                lastmod: page.last_modified(),
+                changefreq: page.sitemap_changefreq.clone(),
+                priority: page.sitemap_priority,
+            });
+        }
+    }
+
+    entries
+}
+
+/// Writes a priority with as many decimals as it has (up to three), but at least one: `0.85`, `1.0`
+fn format_sitemap_priority(priority: f64) -> String {
+    let formatted = format!("{:.3}", priority);
+    let trimmed = formatted.trim_end_matches('0');
+    if trimmed.ends_with('.') {
+        format!("{}0", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn build_sitemap(entries: &[SitemapEntry]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for entry in entries {
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", escape_html(&entry.loc)));
+        if let Some(lastmod) = entry.lastmod {
+            xml.push_str(&format!("    <lastmod>{}</lastmod>\n", lastmod.format("%Y-%m-%d")));
+        }
+        if let Some(changefreq) = &entry.changefreq {
+            xml.push_str(&format!("    <changefreq>{}</changefreq>\n", escape_html(changefreq)));
+        }
+        if let Some(priority) = entry.priority {
+            xml.push_str(&format!("    <priority>{}</priority>\n", format_sitemap_priority(priority)));
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+fn render_sitemap(
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    output: &Output,
+) -> Result<()> {
+    let entries = collect_sitemap_entries(config, sections);
+    let output_path = output.dir.join(SITEMAP_PATH);
+    output.write(&output_path, build_sitemap(&entries))
+        .with_context(|| format!("writing sitemap to {:?}", output_path))
+}
+
+/// One page in `search-index.json`
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct SearchEntry {
+    title: String,
+    permalink: String,
+    summary: Option<String>,
+    /// Page body as plain text
+    content: String,
+}
+
+/// Collects every listed page, ordered by section key then section order
+fn collect_search_entries(sections: &HashMap<String, SectionContent>) -> Vec<SearchEntry> {
+    let mut keys: Vec<&String> = sections.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .flat_map(|key| listed_pages(&sections[key].pages))
+        .map(|page| SearchEntry {
+            content: strip_html(&page.content),
+            title: page.title,
+            permalink: page.permalink,
+            summary: page.summary_text,
+        })
+        .collect()
+}
+
+/// Writes the client-side search index
+fn render_search_index(sections: &HashMap<String, SectionContent>, output: &Output) -> Result<()> {
+    let json = serde_json::to_string(&collect_search_entries(sections)).context("serializing search index")?;
+    let output_path = output.dir.join(SEARCH_INDEX_PATH);
+    output.write(&output_path, json).with_context(|| format!("writing search index to {:?}", output_path))
+}
+
+/// A section's `index.json`, for integrations that want structured post data
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct SectionJson {
+    title: String,
+    description: Option<String>,
+    permalink: String,
+    pages: Vec<SectionJsonPage>,
+}
+
+/// One listed page in a section's `index.json`; dates serialize as ISO 8601 (`2025-01-31`)
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct SectionJsonPage {
+    title: String,
+    permalink: String,
+    date: Option<NaiveDate>,
+    summary: Option<String>,
+}
+
+fn build_section_json(key: &str, section: &SectionContent, config: &Config) -> SectionJson {
+    SectionJson {
+        title: section_title(key, &section.meta, config),
+        description: section.meta.description.clone(),
+        permalink: section_output_paths(key, &section.meta, config).0,
+        pages: listed_pages(&section.pages)
+            .into_iter()
+            .map(|page| SectionJsonPage {
+                title: page.title,
+                permalink: page.permalink,
+                date: page.date,
+                summary: page.summary_text,
+            })
+            .collect(),
+    }
+}
+
+/// Writes `<section>/index.json` for every section, in the section's page order
+fn render_section_json(
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    output: &Output,
+) -> Result<()> {
+    for (key, section) in sections {
+        let json = serde_json::to_string_pretty(&build_section_json(key, section, config))
+            .with_context(|| format!("serializing section '{}'", key))?;
+        let output_path = output.dir.join(key).join(SECTION_JSON_FILE);
+        output.write(&output_path, json).with_context(|| format!("writing section data to {:?}", output_path))?;
+    }
+    Ok(())
+}
+
+/// robots.txt body: `[extra] robots` verbatim, or allow everything and point at the sitemap
+fn build_robots_txt(config: &Config) -> String {
+    match config.extra_str("robots") {
+        Some(custom) => custom.to_string(),
+        None => format!(
+            "User-agent: *\nAllow: /\n\nSitemap: {}/{}\n",
+            config.base_url, SITEMAP_PATH
+        ),
+    }
+}
+
+/// Writes robots.txt unless the static directory provides one. A robots.txt already in the
+/// output is left over from an earlier build, so it gets regenerated
+fn render_robots(config: &Config, static_dir: &Path, output: &Output) -> Result<()> {
+    if static_dir.join(ROBOTS_PATH).exists() {
+        return Ok(());
+    }
+    let output_path = output.dir.join(ROBOTS_PATH);
+    output.write(&output_path, build_robots_txt(config))
+        .with_context(|| format!("writing robots.txt to {:?}", output_path))
+}
+
+/// Resolves front matter terms to links; names differing only by case collapse into one term
+fn term_links(names: &[String], taxonomy: &Taxonomy, config: &Config) -> Vec<TagLink> {
+    let mut links: Vec<TagLink> = Vec::new();
+    for name in names {
+        let slug = slugify(name);
+        if slug.is_empty() || links.iter().any(|link| link.slug == slug) {
+            continue;
+        }
+        let (permalink, relative_path) = url_paths(&format!("{}/{}", taxonomy.name, slug), config);
+        links.push(TagLink {
+            name: name.trim().to_string(),
+            permalink,
+            relative_path,
+            slug,
+        });
+    }
+    links
+}
+
+/// Groups listed pages by the slug of each term `terms` returns for them, newest first within each term
+fn collect_terms(
+    sections: &HashMap<String, SectionContent>,
+    terms: impl Fn(&PageData) -> &[TagLink],
+) -> BTreeMap<String, TagData> {
+    let mut keys: Vec<&String> = sections.keys().collect();
+    keys.sort();
+
+    let mut collected: BTreeMap<String, TagData> = BTreeMap::new();
+    for key in keys {
+        for page in sections[key].pages.iter().filter(|page| page.is_listed()) {
+            for link in terms(page) {
+                collected.entry(link.slug.clone())
+                    .or_insert_with(|| TagData {
+                        name: link.name.clone(),
+                        slug: link.slug.clone(),
+                        permalink: link.permalink.clone(),
+                        relative_path: link.relative_path.clone(),
+                        pages: Vec::new(),
+                    })
+                    .pages
+                    .push(page.clone());
+            }
+        }
+    }
+
+    for term in collected.values_mut() {
+        term.pages.sort_by(|a, b| {
+            cmp_optional(a.published(), b.published(), true).then_with(|| a.title.cmp(&b.title))
+        });
+    }
+    collected
+}
+
+fn collect_tags(sections: &HashMap<String, SectionContent>) -> BTreeMap<String, TagData> {
+    collect_terms(sections, |page| &page.tags)
+}
+
+fn collect_categories(sections: &HashMap<String, SectionContent>) -> BTreeMap<String, TagData> {
+    collect_terms(sections, |page| page.category.as_slice())
+}
+
+/// A term's feeds, e.g. `tags/rust/rss.xml` and `tags/rust/atom.xml`; none unless `[taxonomies] feeds`
+fn term_feed_links(config: &Config, taxonomy: &Taxonomy, term: &TagData) -> Vec<FeedLink> {
+    if !config.taxonomies.feeds {
+        return Vec::new();
+    }
+    let title = format!("{} – {}", config.title, term.name);
+    [(TERM_RSS_FILE, "application/rss+xml"), (TERM_ATOM_FILE, "application/atom+xml")]
+        .into_iter()
+        .map(|(file, mime)| FeedLink {
+            title: title.clone(),
+            mime: mime.to_string(),
+            relative_path: format!("{}/{}/{}", taxonomy.name, term.slug, file),
+        })
+        .collect()
+}
+
+/// Writes a term's RSS and Atom feeds over the same pages its listing shows
+fn render_term_feeds(config: &Config, taxonomy: &Taxonomy, term: &TagData, output: &Output) -> Result<()> {
+    // Term pages are already only the listed ones
+    let pages: Vec<&PageData> = term.pages.iter().take(config.feed_limit).collect();
+    for feed in term_feed_links(config, taxonomy, term) {
+        let channel = FeedChannel {
+            title: feed.title.clone(),
+            link: term.permalink.clone(),
+            self_url: format!("{}/{}", config.base_url, feed.relative_path),
+        };
+        let xml = if feed.relative_path.ends_with(TERM_RSS_FILE) {
+            build_rss_feed(config, &channel, &pages)
+        } else {
+            build_atom_feed(config, &channel, &pages)
+        };
+        let output_path = output.dir.join(&feed.relative_path);
+        output.write(&output_path, xml).with_context(|| format!("writing feed to {:?}", output_path))?;
+    }
+    Ok(())
+}
+
+/// Renders the taxonomy's index (`tags/index.html`) and one listing per term, skipping either when
+/// the site has no template for it (see `missing_taxonomy_templates`). Term feeds don't need a template
+fn render_taxonomy(
+    tera: &Tera,
+    config: &Config,
+    taxonomy: &Taxonomy,
+    terms: &BTreeMap<String, TagData>,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    output: &Output,
+) -> Result<()> {
+    if terms.is_empty() {
+        return Ok(());
+    }
+    let has_template = |name: &str| tera.get_template_names().any(|n| n == name);
+    let (_, index_path) = url_paths(taxonomy.name, config);
+    let index_template = format!("{}.html", taxonomy.name);
+    let term_template = format!("{}.html", taxonomy.singular);
+    for term in terms.values() {
+        render_term_feeds(config, taxonomy, term, output)?;
+    }
+
+    if has_template(&index_template) {
+        let depth = index_path.matches('/').count();
+        let mut context = build_base_context(config, nav, built_at, &path_prefix_for_depth(depth));
+        context.insert(taxonomy.name, &terms.values().collect::<Vec<_>>());
+        context.insert("body_class", taxonomy.name);
+
+        render_template_to_file(
+            tera,
+            config,
+            &index_template,
+            &context,
+            output, &output.dir.join(&index_path),
+            &format!("{} index", taxonomy.singular),
+        )?;
+    }
+
+    if !has_template(&term_template) {
+        return Ok(());
+    }
+    for term in terms.values() {
+        let depth = term.relative_path.matches('/').count();
+        let mut context = build_base_context(config, nav, built_at, &path_prefix_for_depth(depth));
+        context.insert(taxonomy.singular, term);
+        context.insert(format!("{}_index_path", taxonomy.name), &index_path);
+        context.insert("feeds", &term_feed_links(config, taxonomy, term));
+        context.insert("body_class", &format!("{} {}-{}", taxonomy.name, taxonomy.singular, term.slug));
+
+        render_template_to_file(
+            tera,
+            config,
+            &term_template,
+            &context,
+            output, &output.dir.join(&term.relative_path),
+            &format!("{} {}", taxonomy.singular, term.name),
+        )?;
+    }
+    Ok(())
+}
+
+/// A warning for each template a taxonomy in use lacks, whose pages `render_taxonomy` then skips
+fn missing_taxonomy_templates(tera: &Tera, taxonomy: &Taxonomy, terms: &BTreeMap<String, TagData>) -> Vec<Warning> {
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    [
+        (format!("{}.html", taxonomy.name), format!("the {} index", taxonomy.singular)),
+        (format!("{}.html", taxonomy.singular), format!("{} pages", taxonomy.singular)),
+    ]
+    .into_iter()
+    .filter(|(template, _)| !tera.get_template_names().any(|name| name == template))
+    .map(|(template, skipped)| Warning::MissingTaxonomyTemplate {
+        taxonomy: taxonomy.name.to_string(),
+        template,
+        skipped,
+    })
+    .collect()
+}
+
+/// Dated pages by year, then month. Pages within a month are newest first
+type Archive = BTreeMap<i32, BTreeMap<u32, Vec<PageData>>>;
+
+/// Groups listed pages by the year and month of their date, leaving out undated ones
+fn collect_archive(pages: &[PageData]) -> Archive {
+    let mut archive = Archive::new();
+    for page in pages.iter().filter(|page| page.is_listed()) {
+        if let Some(date) = page.date {
+            archive.entry(date.year()).or_default().entry(date.month()).or_default().push(page.clone());
+        }
+    }
+    for pages in archive.values_mut().flat_map(|months| months.values_mut()) {
+        pages.sort_by(|a, b| cmp_optional(a.published(), b.published(), true).then_with(|| a.title.cmp(&b.title)));
+    }
+    archive
+}
+
+/// A warning for each listed page the archive leaves out for having no date, when the site has
+/// an `archive.html` template
+fn undated_archive_pages(tera: &Tera, sections: &HashMap<String, SectionContent>) -> Vec<Warning> {
+    if !tera.get_template_names().any(|name| name == "archive.html") {
+        return Vec::new();
+    }
+    sections
+        .get(FEED_SECTION)
+        .map(|section| section.pages.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|page| page.is_listed() && page.date.is_none())
+        .map(|page| Warning::UndatedArchivePage { source: page.source.clone() })
+        .collect()
+}
+
+/// One year of the archive, as `archive.html` sees it
+#[derive(Debug, Clone, Serialize)]
+struct ArchiveYear {
+    year: i32,
+    permalink: String,
+    relative_path: String,
+    /// Pages across every month of the year
+    page_count: usize,
+    months: Vec<ArchiveMonth>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ArchiveMonth {
+    month: u32,
+    /// Full month name, e.g. `March`
+    name: String,
+    pages: Vec<PageData>,
+}
+
+/// Lays the archive out newest first: years descending, and months descending within each year
+fn archive_years(archive: &Archive, config: &Config) -> Vec<ArchiveYear> {
+    archive
+        .iter()
+        .rev()
+        .map(|(year, months)| {
+            let (permalink, relative_path) = url_paths(&format!("{}/{}", ARCHIVE_DIR, year), config);
+            ArchiveYear {
+                year: *year,
+                permalink,
+                relative_path,
+                page_count: months.values().map(Vec::len).sum(),
+                months: months
+                    .iter()
+                    .rev()
+                    .map(|(month, pages)| ArchiveMonth {
+                        month: *month,
+                        name: NaiveDate::from_ymd_opt(*year, *month, 1)
+                            .map(|date| date.format("%B").to_string())
+                            .unwrap_or_default(),
+                        pages: pages.clone(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Renders `archive/index.html` for the writing section when the site has an `archive.html`
+/// template, plus `archive/<year>/index.html` pages with `[extra] archive_by_year`
+fn render_archive(
+    tera: &Tera,
+    config: &Config,
+    sections: &HashMap<String, SectionContent>,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    output: &Output,
+) -> Result<()> {
+    if !tera.get_template_names().any(|name| name == "archive.html") {
+        return Ok(());
+    }
+    let pages = sections
+        .get(FEED_SECTION)
+        .map(|section| section.pages.as_slice())
+        .unwrap_or_default();
+    let years = archive_years(&collect_archive(pages), config);
+    let (_, index_path) = url_paths(ARCHIVE_DIR, config);
+
+    let depth = index_path.matches('/').count();
+    let mut context = build_base_context(config, nav, built_at, &path_prefix_for_depth(depth));
+    context.insert("years", &years);
+    context.insert("archive_index_path", &index_path);
+    context.insert("body_class", "archive");
+    render_template_to_file(tera, config, "archive.html", &context, output, &output.dir.join(&index_path), "archive")?;
+
+    if !config.extra_bool("archive_by_year") {
+        return Ok(());
+    }
+    for year in &years {
+        let depth = year.relative_path.matches('/').count();
+        let mut context = build_base_context(config, nav, built_at, &path_prefix_for_depth(depth));
+        context.insert("years", std::slice::from_ref(year));
+        context.insert("year", &year.year);
+        context.insert("archive_index_path", &index_path);
+        context.insert("body_class", &format!("archive archive-{}", year.year));
+        render_template_to_file(
+            tera,
+            config,
+            "archive.html",
+            &context,
+            output, &output.dir.join(&year.relative_path),
+            &format!("archive for {}", year.year),
+        )?;
+    }
+    Ok(())
+}
+
+/// Renders each of `Config::error_pages` with the base context plus its `status_code`
+fn render_error_pages(
+    tera: &Tera,
+    config: &Config,
+    nav: &[SectionLink],
+    built_at: DateTime<Utc>,
+    output: &Output,
+) -> Result<()> {
+    for page in config.error_pages()? {
+        let mut context = build_base_context(config, nav, built_at, "");
+        context.insert("body_class", &format!("error-{}", page.status));
+        context.insert("status_code", &page.status);
+
+        render_template_to_file(
+            tera,
+            config,
+            &page.template,
+            &context,
+            output, &output.dir.join(&page.path),
+            &format!("{} page", page.status),
+        )?;
+    }
+    Ok(())
+}
+
+/// Stable 64-bit FNV-1a hash of file contents, rendered as hex
+fn content_hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Records the size and hash of every file under the output directory, keyed by relative path
+fn snapshot_output(output_dir: &Path) -> Result<BTreeMap<String, OutputEntry>> {
+    let mut manifest = BTreeMap::new();
+    for entry in WalkDir::new(output_dir).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let bytes = fs::read(path).with_context(|| format!("reading output file {:?}", path))?;
+        let relative = url_key(path.strip_prefix(output_dir).unwrap_or(path));
+        manifest.insert(relative, OutputEntry {
+            size: bytes.len() as u64,
+            hash: content_hash(&bytes),
+        });
+    }
+    Ok(manifest)
+}
+
+/// Number of files in the output directory, without reading them
+fn count_output_files(output_dir: &Path) -> usize {
+    WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .count()
+}
+
+fn load_output_manifest(path: &Path) -> Result<Option<BTreeMap<String, OutputEntry>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).context("reading build manifest")?;
+    let manifest = serde_json::from_str(&contents).context("parsing build manifest")?;
+    Ok(Some(manifest))
+}
+
+fn save_output_manifest(path: &Path, manifest: &BTreeMap<String, OutputEntry>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("creating build cache directory")?;
+    }
+    let contents = serde_json::to_string_pretty(manifest).context("serializing build manifest")?;
+    fs::write(path, contents).context("writing build manifest")
+}
+
+fn diff_output_manifests(
+    previous: &BTreeMap<String, OutputEntry>,
+    current: &BTreeMap<String, OutputEntry>,
+) -> BuildDiff {
+    let mut diff = BuildDiff::default();
+    for (path, entry) in current {
+        match previous.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(old) if old != entry => diff.changed.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = previous
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// What one rendered page was built from, as recorded in the build manifest
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+struct PageRecord {
+    source: String,
+    /// Source modification time in nanoseconds since the Unix epoch
+    source_mtime: Option<u64>,
+    template: String,
+    /// Hash of the page's template data, which also covers neighbours, related pages, and tags
+    data_hash: String,
+    aliases: Vec<String>,
+}
+
+/// Inputs of a build, compared against the next `--incremental` build
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+struct BuildManifest {
+    /// Hash of the config, site nav, and asset manifest; a change re-renders every page
+    global_hash: String,
+    /// Template name -> modification time of its file
+    templates: BTreeMap<String, Option<u64>>,
+    /// Page output path -> how it was built
+    pages: BTreeMap<String, PageRecord>,
+}
+
+fn file_mtime(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+fn build_manifest(
+    tera: &Tera,
+    sections: &HashMap<String, SectionContent>,
+    global_hash: String,
+) -> Result<BuildManifest> {
+    let templates = tera
+        .get_template_names()
+        .map(|name| {
+            let path = tera.get_template(name).ok().and_then(|template| template.path.clone());
+            (name.to_string(), path.and_then(|path| file_mtime(Path::new(&path))))
+        })
+        .collect();
+
+    let mut pages = BTreeMap::new();
+    for (key, section) in sections {
+        for page in &section.pages {
+            // Through a Value, like the global inputs, so front matter `[extra]` keys hash in a fixed order
+            let data = serde_json::to_value((key, page))
+                .context("serializing page for the build manifest")?
+                .to_string();
+            pages.insert(page.relative_path.clone(), PageRecord {
+                source: page.source.clone(),
+                source_mtime: file_mtime(Path::new(&page.source)),
+                template: page_template(page, section).to_string(),
+                data_hash: content_hash(data.as_bytes()),
+                aliases: page.aliases.clone(),
+            });
+        }
+    }
+    Ok(BuildManifest { global_hash, templates, pages })
+}
+
+/// A template and every template it extends
+fn template_chain(tera: &Tera, name: &str) -> Vec<String> {
+    let mut chain = vec![name.to_string()];
+    if let Ok(template) = tera.get_template(name) {
+        chain.extend(template.parents.iter().cloned());
+    }
+    chain
+}
+
+/// Output paths of pages that need rendering, or `None` when every page does: the config, nav, or
+/// assets changed, or a template changed that no page renders with directly (such as an include)
+fn pages_to_render(previous: &BuildManifest, current: &BuildManifest, tera: &Tera) -> Option<HashSet<String>> {
+    if previous.global_hash != current.global_hash {
+        return None;
+    }
+    let changed_templates: HashSet<&String> = current
+        .templates
+        .iter()
+        .filter(|(name, mtime)| previous.templates.get(*name) != Some(*mtime))
+        .map(|(name, _)| name)
+        .chain(previous.templates.keys().filter(|name| !current.templates.contains_key(*name)))
+        .collect();
+
+    let page_templates: HashSet<&String> = current.pages.values().map(|record| &record.template).collect();
+    let chains: HashMap<&String, Vec<String>> = page_templates
+        .into_iter()
+        .map(|template| (template, template_chain(tera, template)))
+        .collect();
+    let covered = changed_templates
+        .iter()
+        .all(|changed| chains.values().any(|chain| chain.contains(changed)));
+    if !covered {
+        return None;
+    }
+
+    Some(
+        current
+            .pages
+            .iter()
+            .filter(|(path, record)| {
+                previous.pages.get(*path) != Some(*record)
+                    || chains[&record.template].iter().any(|name| changed_templates.contains(name))
+            })
+            .map(|(path, _)| path.clone())
+            .collect(),
+    )
+}
+
+/// Deletes every file in the output that the build neither wrote nor kept, and the `keep` files
+/// aside, then the directories that leaves empty. That covers pages, listings, term pages, and
+/// static assets whose sources are gone, like a clean build would
+fn remove_stale_outputs(output_dir: &Path, written: &HashSet<PathBuf>, keep: &[PathBuf]) -> Result<()> {
+    let keep: HashSet<PathBuf> = keep.iter().map(|relative| output_dir.join(relative)).collect();
+    // Contents come before their directory, so emptied directories can go on the same pass
+    for entry in WalkDir::new(output_dir).min_depth(1).contents_first(true).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            if fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none()) {
+                fs::remove_dir(path).with_context(|| format!("removing empty output directory {:?}", path))?;
+            }
+        } else if !written.contains(path) && !keep.contains(path) {
+            fs::remove_file(path).with_context(|| format!("removing stale output {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Loads the previous build's inputs; a missing or unreadable manifest means a full build
+fn load_build_manifest(path: &Path) -> Result<Option<BuildManifest>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).context("reading build manifest")?;
+    match serde_json::from_str(&contents) {
+        Ok(manifest) => Ok(Some(manifest)),
+        Err(error) => {
+            warn!("ignoring unreadable build manifest {:?}: {}", path, error);
+            Ok(None)
+        }
+    }
+}
+
+fn save_build_manifest(path: &Path, manifest: &BuildManifest) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("creating build cache directory")?;
+    }
+    let contents = serde_json::to_string_pretty(manifest).context("serializing build manifest")?;
+    fs::write(path, contents).context("writing build manifest")
+}
+
+fn print_build_diff(diff: &BuildDiff) {
+    eprintln!("Build diff vs previous build: {} added, {} removed, {} changed",
+        diff.added.len(), diff.removed.len(), diff.changed.len());
+    for (marker, paths) in [("+", &diff.added), ("-", &diff.removed), ("~", &diff.changed)] {
+        for path in paths {
+            eprintln!("  {} {}", marker, path);
+        }
+    }
+}
+
+/// Extracts the raw values of all `href` attributes in an HTML document
+fn extract_hrefs(html: &str) -> Vec<String> {
+    let mut hrefs = Vec::new();
+    let mut rest = html;
+    while let Some(pos) = rest.find("href=") {
+        rest = &rest[pos + 5..];
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        rest = &rest[1..];
+        if let Some(end) = rest.find(quote) {
+            hrefs.push(decode_html_entities(&rest[..end]));
+            rest = &rest[end + 1..];
+        }
+    }
+    hrefs
+}
+
+/// Whether an href into the site resolves to a generated file, or `None` for links that
+/// aren't internal (other sites, `mailto:` and similar schemes, and same-page anchors)
+fn internal_link_exists(output_dir: &Path, page: &str, href: &str, base_url: &str) -> Option<bool> {
+    // Like is_external_url, `https://example.com/blogroll` isn't under `https://example.com/blog`
+    let href = match href.strip_prefix(base_url).filter(|rest| rest.is_empty() || rest.starts_with(['/', '?', '#'])) {
+        Some(rest) => rest,
+        // Root-relative links must include the site's subpath to stay inside the site
+        None if href.starts_with('/') && !href.starts_with("//") => {
+            match href.strip_prefix(base_path(base_url)) {
+                Some(rest) if rest.is_empty() || rest.starts_with(['/', '?', '#']) => rest,
+                _ => return None,
+            }
+        }
+        None => href,
+    };
+    let scheme = href.split_once(':').map(|(scheme, _)| scheme);
+    if href.starts_with("//")
+        || scheme.is_some_and(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)))
+    {
+        return None;
+    }
+    let path = href.split(['?', '#']).next().unwrap_or_default();
+    if path.is_empty() {
+        return None;
+    }
+
+    // Root-relative paths start from the output directory, others from the page's directory
+    let mut segments: Vec<&str> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        let mut dir: Vec<&str> = page.split('/').collect();
+        dir.pop();
+        dir
+    };
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Some(false);
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let target = output_dir.join(segments.join("/"));
+    Some(target.is_file() || target.join("index.html").is_file())
+}
+
+/// Internal links in the generated HTML that don't resolve, as (page, href) pairs
+fn find_broken_internal_links(output_dir: &Path, base_url: &str) -> Result<Vec<(String, String)>> {
+    let mut broken = Vec::new();
+    for entry in WalkDir::new(output_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file() && e.path().extension().map(|e| e == "html").unwrap_or(false))
+    {
+        let path = entry.path();
+        let html = fs::read_to_string(path)
+            .with_context(|| format!("reading generated HTML {:?}", path))?;
+        let relative = url_key(path.strip_prefix(output_dir).unwrap_or(path));
+
+        for href in extract_hrefs(&html) {
+            if internal_link_exists(output_dir, &relative, &href, base_url) == Some(false) {
+                broken.push((relative.clone(), href));
+            }
+        }
+    }
+    Ok(broken)
+}
+
+/// Reports every broken internal link and fails if there are any
+fn check_internal_links(output_dir: &Path, base_url: &str) -> Result<()> {
+    let broken = find_broken_internal_links(output_dir, base_url)?;
+    for (page, href) in &broken {
+        warn!("Broken internal link {} in {}", href, page);
+    }
+    if !broken.is_empty() {
+        anyhow::bail!("found {} broken internal links", broken.len());
+    }
+    Ok(())
+}
+
+/// Returns the host portion of an absolute http(s) URL
+fn url_domain(url: &str) -> Option<&str> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    Some(host.split(':').next().unwrap_or(host))
+}
+
+/// Applies the allowlist/denylist; the denylist wins when a domain appears in both
+fn domain_is_checked(domain: &str, link_check: &LinkCheckConfig) -> bool {
+    let matches = |pattern: &String| {
+        domain.eq_ignore_ascii_case(pattern)
+            || domain.to_ascii_lowercase().ends_with(&format!(".{}", pattern.to_ascii_lowercase()))
+    };
+    if link_check.deny_domains.iter().any(matches) {
+        return false;
+    }
+    link_check.allow_domains.is_empty() || link_check.allow_domains.iter().any(matches)
+}
+
+/// Collects every external link in the generated HTML, mapped to the files that reference it
+fn collect_external_links(
     output_dir: &Path,
-) -> Result<()> {
-    for (key, section_content) in sections.iter() {
-        let template = section_content
-            .meta
-            .template
-            .clone()
-            .unwrap_or_else(|| "section.html".to_string());
+    base_url: &str,
+    link_check: &LinkCheckConfig,
+) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut links: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for entry in WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file() && e.path().extension().map(|e| e == "html").unwrap_or(false))
+    {
+        let path = entry.path();
+        let html = fs::read_to_string(path)
+            .with_context(|| format!("reading generated HTML {:?}", path))?;
+        let relative = url_key(path.strip_prefix(output_dir).unwrap_or(path));
+
+        for href in extract_hrefs(&html) {
+            if !is_external_url(&href, base_url) {
+                continue;
+            }
+            if !url_domain(&href).is_some_and(|domain| domain_is_checked(domain, link_check)) {
+                continue;
+            }
+            let sources = links.entry(href).or_default();
+            if !sources.contains(&relative) {
+                sources.push(relative.clone());
+            }
+        }
+    }
+
+    for sources in links.values_mut() {
+        sources.sort();
+    }
+
+    Ok(links)
+}
+
+/// Requests a URL with HEAD, falling back to GET for servers that reject HEAD
+fn check_external_url(agent: &ureq::Agent, url: &str) -> std::result::Result<(), String> {
+    // A transport error (DNS, refused, timeout) would fail the same way for GET, so only a server
+    // that rejects HEAD itself gets a second request
+    let status = agent.head(url).call().map_err(|e| e.to_string())?.status();
+    let status = if matches!(status.as_u16(), 405 | 501) {
+        agent.get(url).call().map_err(|e| e.to_string())?.status()
+    } else {
+        status
+    };
+
+    if status.is_client_error() || status.is_server_error() {
+        Err(format!("HTTP {}", status.as_u16()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks each unique URL once, with at most `concurrency` requests in flight.
+/// Returns the dead links along with the failure reason
+fn check_external_links(urls: &[String], link_check: &LinkCheckConfig) -> BTreeMap<String, String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(link_check.timeout_secs)))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let queue = Mutex::new(urls.iter().collect::<VecDeque<_>>());
+    let dead = Mutex::new(BTreeMap::new());
+    let workers = link_check.concurrency.clamp(1, urls.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let Some(url) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+                if let Err(reason) = check_external_url(&agent, url) {
+                    dead.lock().unwrap().insert(url.clone(), reason);
+                }
+            });
+        }
+    });
+
+    dead.into_inner().unwrap()
+}
+
+/// Runs the external link checker over the generated site and prints dead links
+fn report_dead_external_links(output_dir: &Path, config: &Config) -> Result<()> {
+    let links = collect_external_links(output_dir, &config.base_url, &config.link_check)?;
+    let urls: Vec<String> = links.keys().cloned().collect();
+    eprintln!("Checking {} external links...", urls.len());
+
+    let dead = check_external_links(&urls, &config.link_check);
+    for (url, reason) in &dead {
+        warn!("Dead external link {} ({}) referenced from: {}",
+            url, reason, links[url].join(", "));
+    }
+    if dead.is_empty() {
+        eprintln!("All external links resolved");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> Config {
+        toml::from_str(
+            r#"
+base_url = "https://example.com"
+title = "Test Site"
+description = "A test site"
+"#,
+        )
+        .unwrap()
+    }
+
+    /// A fixed build time, so rendered dates don't depend on when the tests run
+    fn built_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn write_file(root: &Path, relative: &str, contents: &str) {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_parse_front_matter_with_all_fields() {
+        let input = r#"+++
+title = "Test Post"
+description = "A test"
+date = "2025-01-15"
+summary = "Summary here"
+template = "custom.html"
+sort_by = "date"
++++
+Content here"#;
+
+        let result = parse_front_matter(input);
+        assert!(result.is_ok());
+        let (fm, body) = result.unwrap();
+
+        assert_eq!(fm.title, Some("Test Post".to_string()));
+        assert_eq!(fm.description, Some("A test".to_string()));
+        assert_eq!(fm.date, Some(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()));
+        assert_eq!(fm.summary, Some("Summary here".to_string()));
+        assert_eq!(fm.template, Some("custom.html".to_string()));
+        assert_eq!(body.trim(), "Content here");
+    }
+
+    #[test]
+    fn test_parse_front_matter_minimal() {
+        let input = r#"+++
+title = "Minimal"
++++
+Body"#;
+
+        let result = parse_front_matter(input);
+        assert!(result.is_ok());
+        let (fm, body) = result.unwrap();
+
+        assert_eq!(fm.title, Some("Minimal".to_string()));
+        assert_eq!(fm.description, None);
+        assert_eq!(body.trim(), "Body");
+    }
+
+    #[test]
+    fn test_parse_front_matter_no_frontmatter() {
+        let input = "Just content, no frontmatter";
+
+        let result = parse_front_matter(input);
+        assert!(result.is_ok());
+        let (fm, body) = result.unwrap();
+
+        assert_eq!(fm.title, None);
+        assert_eq!(body, input);
+    }
+
+    #[test]
+    fn test_markdown_to_html_basic() {
+        let md = "# Heading\n\nParagraph with **bold**";
+        let html = markdown_to_html(md, &MarkdownSettings::default());
+
+        assert!(html.contains("<h1>"));
+        assert!(html.contains("<strong>"));
+        assert!(html.contains("Heading"));
+    }
+
+    #[test]
+    fn test_path_depth_calculation() {
+        assert_eq!(calculate_path_depth("", false), 0);
+        assert_eq!(calculate_path_depth("", true), 1);
+        assert_eq!(calculate_path_depth("section", false), 1);
+        assert_eq!(calculate_path_depth("section", true), 2);
+        assert_eq!(calculate_path_depth("section/nested", false), 2);
+        assert_eq!(calculate_path_depth("section/nested", true), 3);
+    }
+
+    #[test]
+    fn test_path_prefix_generation() {
+        assert_eq!(path_prefix_for_depth(0), "");
+        assert_eq!(path_prefix_for_depth(1), "../");
+        assert_eq!(path_prefix_for_depth(2), "../../");
+        assert_eq!(path_prefix_for_depth(3), "../../../");
+    }
+
+    #[test]
+    fn test_relative_path_generation_root() {
+        let parent_key = "";
+        let slug = "about";
+
+        let relative_path = if parent_key.is_empty() {
+            format!("{}/index.html", slug)
+        } else {
+            format!("{}/{}/index.html", parent_key, slug)
+        };
+
+        assert_eq!(relative_path, "about/index.html");
+        assert!(!relative_path.starts_with('/'));
+    }
+
+    #[test]
+    fn test_relative_path_generation_nested() {
+        let parent_key = "writing";
+        let slug = "my-post";
+
+        let relative_path = if parent_key.is_empty() {
+            format!("{}/index.html", slug)
+        } else {
+            format!("{}/{}/index.html", parent_key, slug)
+        };
+
+        assert_eq!(relative_path, "writing/my-post/index.html");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("My Post"), "my-post");
+        assert_eq!(slugify("writing/rust"), "writing-rust");
+        assert_eq!(slugify("  C++ & Rust!  "), "c-rust");
+    }
+
+    #[test]
+    fn test_slugify_transliterates_unicode() {
+        assert_eq!(slugify("café"), "cafe");
+        assert_eq!(slugify("Crème Brûlée à la Française"), "creme-brulee-a-la-francaise");
+        assert_eq!(slugify("Ærøskøbing Straße"), "aeroskobing-strasse");
+        assert_eq!(slugify("  spaced   out\ttitle "), "spaced-out-title");
+        assert_eq!(slugify("Rust's \"async\" -- (2024 edition)!"), "rust-s-async-2024-edition");
+        assert_eq!(slugify("my_post.v2"), "my-post-v2");
+        assert_eq!(slugify("!!!"), "");
+        assert_eq!(hyphenate("Café Olé"), "café-olé");
+    }
+
+    #[test]
+    fn test_derived_slugs_and_section_keys_are_slugified() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "Café Notes/_index.md", "+++\ntitle = \"Café Notes\"\n+++\n");
+        write_file(content.path(), "Café Notes/Crème Brûlée.md", "+++\ntitle = \"Dessert\"\n+++\n");
+        write_file(content.path(), "Café Notes/menu.png", "png");
+        write_file(content.path(), "Café Notes/kept.md", "+++\ntitle = \"Kept\"\nslug = \"Déjà Vu\"\n+++\n");
+
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let section = &sections["cafe-notes"];
+        assert_eq!(section.meta.title.as_deref(), Some("Café Notes"));
+        let dessert = section.pages.iter().find(|p| p.title == "Dessert").unwrap();
+        assert_eq!(dessert.slug, "creme-brulee");
+        assert_eq!(dessert.permalink, "https://example.com/cafe-notes/creme-brulee/");
+        // Explicit slugs are only lowercased and hyphenated
+        let kept = section.pages.iter().find(|p| p.title == "Kept").unwrap();
+        assert_eq!(kept.slug, "déjà-vu");
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        copy_content_assets(&[content.path().to_path_buf()], &default_markdown_extensions(), &disk).unwrap();
+        assert!(output.path().join("cafe-notes/menu.png").is_file());
+    }
+
+    #[test]
+    fn test_section_description_falls_back_to_site_description() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "_index.md", "+++\ntitle = \"Home\"\n+++\n");
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\ndescription = \"Essays\"\n+++\n");
+        write_file(content.path(), "notes/_index.md", "+++\ntitle = \"Notes\"\n+++\n");
+
+        let mut config = test_config();
+        config.description = "A test site".to_string();
+        let (root, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+
+        assert_eq!(root.description, None);
+        assert_eq!(root.meta_description, "A test site");
+        // A section without a description doesn't show the site's as its own, but still has it for meta tags
+        let notes = section_data("notes", &sections["notes"], &sections, &config);
+        assert_eq!(notes.description, None);
+        assert_eq!(notes.meta_description, "A test site");
+        let writing = section_data("writing", &sections["writing"], &sections, &config);
+        assert_eq!(writing.description.as_deref(), Some("Essays"));
+        assert_eq!(writing.meta_description, "Essays");
+    }
+
+    #[test]
+    fn test_base_context_has_build_date_and_year() {
+        let context = build_base_context(&test_config(), &[], built_at(), "").into_json();
+        assert_eq!(context["current_year"], 2025);
+        assert_eq!(context["build_date"], "2025-06-01T12:00:00Z");
+
+        // Pages, listings, and the home page of one build all share its timestamp
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(content.path(), "writing/one.md", "+++\ntitle = \"One\"\n+++\n");
+        write_file(content.path(), "writing/two.md", "+++\ntitle = \"Two\"\n+++\n");
+        let config = test_config();
+        let (root, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let mut tera = Tera::default();
+        for template in ["index.html", "section.html", "page.html"] {
+            tera.add_raw_template(template, "{{ build_date }}").unwrap();
+        }
+        render_home(&tera, &config, &sections, &[], built_at(), &disk, &root).unwrap();
+        render_sections(&tera, &config, &sections, &[], built_at(), &disk).unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+        for path in ["index.html", "writing/index.html", "writing/one/index.html", "writing/two/index.html"] {
+            assert_eq!(fs::read_to_string(output.path().join(path)).unwrap(), "2025-06-01T12:00:00Z");
+        }
+    }
+
+    #[test]
+    fn test_body_class_nested_page() {
+        let class = body_class("writing/rust", Some("my-post"));
+        let tokens: Vec<&str> = class.split(' ').collect();
+
+        assert!(tokens.contains(&"section-writing-rust"));
+        assert!(tokens.contains(&"page-my-post"));
+        assert_eq!(body_class("", Some("about")), "page-about");
+        assert_eq!(body_class("writing", None), "section-writing");
+    }
+
+    #[test]
+    fn test_permalink_matches_relative_path() {
+        let base_url = "https://example.com";
+
+        assert!(permalink_matches_relative_path(
+            base_url,
+            "https://example.com/writing/my-post/",
+            "writing/my-post/index.html",
+        ));
+        assert!(permalink_matches_relative_path(
+            base_url,
+            "https://example.com/about/",
+            "about/index.html",
+        ));
+        assert!(!permalink_matches_relative_path(
+            base_url,
+            "https://example.com/writing/my-post/",
+            "writing/other-post/index.html",
+        ));
+        assert!(!permalink_matches_relative_path(
+            base_url,
+            "https://elsewhere.com/writing/my-post/",
+            "writing/my-post/index.html",
+        ));
+    }
+
+    #[test]
+    fn test_external_links_open_in_new_tab() {
+        let settings = MarkdownSettings {
+            base_url: "https://example.com".to_string(),
+            external_links_new_tab: true,
+            ..MarkdownSettings::default()
+        };
+        let md = "[out](https://rust-lang.org) and [in](https://example.com/about/) and [rel](../about/)";
+        let html = markdown_to_html(md, &settings);
+
+        assert!(html.contains(
+            r#"<a href="https://rust-lang.org" target="_blank" rel="noopener noreferrer">out</a>"#
+        ));
+        assert!(html.contains(r#"<a href="https://example.com/about/">in</a>"#));
+        assert!(html.contains(r#"<a href="../about/">rel</a>"#));
+    }
+
+    #[test]
+    fn test_external_links_untouched_when_disabled() {
+        let settings = MarkdownSettings {
+            base_url: "https://example.com".to_string(),
+            external_links_new_tab: false,
+            ..MarkdownSettings::default()
+        };
+        let html = markdown_to_html("[out](https://rust-lang.org)", &settings);
+
+        assert!(html.contains(r#"<a href="https://rust-lang.org">out</a>"#));
+    }
+
+    /// Serves `/live` with 200 and everything else with 404, counting requests
+    fn spawn_mock_http_server() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    if reader.read_line(&mut header).unwrap() == 0 || header == "\r\n" {
+                        break;
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                let status = if request_line.contains(" /live ") {
+                    "200 OK"
+                } else {
+                    "404 Not Found"
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+            }
+        });
+
+        (format!("http://{}", addr), hits)
+    }
+
+    #[test]
+    fn test_check_external_links_reports_dead_links() {
+        let (server, hits) = spawn_mock_http_server();
+        let live = format!("{}/live", server);
+        let dead = format!("{}/dead", server);
+
+        let results = check_external_links(&[live.clone(), dead.clone()], &LinkCheckConfig::default());
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results.get(&dead).map(String::as_str), Some("HTTP 404"));
+        assert!(!results.contains_key(&live));
+        assert!(hits.load(std::sync::atomic::Ordering::SeqCst) >= 2);
+    }
+
+    #[test]
+    fn test_extract_hrefs_and_domain_filtering() {
+        let html = r#"<a href="https://rust-lang.org/learn">a</a> <a href='https://ads.tracker.com/x?a=1&amp;b=2'>b</a>"#;
+        let hrefs = extract_hrefs(html);
+        assert_eq!(hrefs, vec!["https://rust-lang.org/learn", "https://ads.tracker.com/x?a=1&b=2"]);
+
+        let link_check = LinkCheckConfig {
+            deny_domains: vec!["tracker.com".to_string()],
+            ..LinkCheckConfig::default()
+        };
+        assert_eq!(url_domain(&hrefs[0]), Some("rust-lang.org"));
+        assert!(domain_is_checked("rust-lang.org", &link_check));
+        assert!(!domain_is_checked("ads.tracker.com", &link_check));
+
+        let allow_only = LinkCheckConfig {
+            allow_domains: vec!["rust-lang.org".to_string()],
+            ..LinkCheckConfig::default()
+        };
+        assert!(domain_is_checked("doc.rust-lang.org", &allow_only));
+        assert!(!domain_is_checked("example.com", &allow_only));
+    }
+
+    #[test]
+    fn test_sitemap_hints_resolution_order() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/_index.md",
+            "+++\ntitle = \"Writing\"\nsitemap_priority = 0.8\nsitemap_changefreq = \"sometimes\"\n+++\n",
+        );
+        write_file(
+            content.path(),
+            "writing/custom.md",
+            "+++\ntitle = \"Custom\"\nsitemap_priority = 0.9\nsitemap_changefreq = \"weekly\"\n+++\n",
+        );
+        write_file(content.path(), "writing/inherited.md", "+++\ntitle = \"Inherited\"\n+++\n");
+        let mut config = test_config();
+        config.sitemap = SitemapConfig { priority: Some(0.5), changefreq: Some("monthly".to_string()) };
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let writing = &sections["writing"];
+        let page = |slug: &str| writing.pages.iter().find(|page| page.slug == slug).unwrap();
+
+        assert_eq!(page("custom").sitemap_priority, Some(0.9));
+        assert_eq!(page("custom").sitemap_changefreq.as_deref(), Some("weekly"));
+        // The section's priority wins over the config's; its invalid changefreq is dropped once,
+        // on the section itself, so pages fall through to the config
+        assert_eq!(page("inherited").sitemap_priority, Some(0.8));
+        assert_eq!(page("inherited").sitemap_changefreq.as_deref(), Some("monthly"));
+        assert_eq!(writing.meta.sitemap_changefreq, None);
+    }
+
+    #[test]
+    fn test_sitemap_section_hints_are_validated_and_priorities_keep_their_digits() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/_index.md",
+            "+++\ntitle = \"Writing\"\nsitemap_priority = 1.5\nsitemap_changefreq = \"Weekly\"\n+++\n",
+        );
+        write_file(content.path(), "writing/post.md", "+++\ntitle = \"Post\"\nsitemap_priority = 0.85\n+++\n");
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+
+        let entries = collect_sitemap_entries(&config, &sections);
+        let section = entries.iter().find(|entry| entry.loc == "https://example.com/writing/").unwrap();
+        assert_eq!(section.priority, None);
+        assert_eq!(section.changefreq.as_deref(), Some("weekly"));
+        let xml = build_sitemap(&entries);
+        assert!(xml.contains("<priority>0.85</priority>"), "{}", xml);
+
+        assert_eq!(format_sitemap_priority(1.0), "1.0");
+        assert_eq!(format_sitemap_priority(0.5), "0.5");
+        assert_eq!(format_sitemap_priority(0.125), "0.125");
+    }
+
+    #[test]
+    fn test_invalid_sitemap_hints_are_rejected() {
+        let mut warnings = Vec::new();
+        assert_eq!(valid_sitemap_priority(1.5, "test.md", &mut warnings), None);
+        assert_eq!(valid_sitemap_priority(-0.1, "test.md", &mut warnings), None);
+        assert_eq!(valid_sitemap_priority(0.3, "test.md", &mut warnings), Some(0.3));
+        assert_eq!(valid_sitemap_changefreq("sometimes", "test.md", &mut warnings), None);
+        assert_eq!(valid_sitemap_changefreq("Daily", "test.md", &mut warnings).as_deref(), Some("daily"));
+        assert_eq!(warnings, vec![
+            Warning::InvalidSitemapPriority { source: "test.md".to_string(), priority: 1.5 },
+            Warning::InvalidSitemapPriority { source: "test.md".to_string(), priority: -0.1 },
+            Warning::InvalidSitemapChangefreq { source: "test.md".to_string(), changefreq: "sometimes".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_summary_variants_from_markdown() {
+        let (html, text) = summary_variants(
+            "Notes on **IPv6** & [routing](https://example.com)",
+            &MarkdownSettings::default(),
+        );
+
+        assert_eq!(
+            html.trim(),
+            r#"<p>Notes on <strong>IPv6</strong> &amp; <a href="https://example.com">routing</a></p>"#
+        );
+        assert_eq!(text, "Notes on IPv6 & routing");
+        assert_eq!(strip_html("<p>in<em>line</em></p><p>next</p>"), "inline next");
+    }
+
+    #[test]
+    fn test_summary_explicit_or_generated_from_first_paragraph() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/explicit.md",
+            "+++\ntitle = \"Explicit\"\nsummary = \"Hand-*written* & <b>kept</b>\"\n+++\nFirst paragraph.",
+        );
+        write_file(
+            content.path(),
+            "writing/auto.md",
+            "+++\ntitle = \"Auto\"\n+++\n## Intro\n\nThe *first* paragraph & more.\n\nThe second one.",
+        );
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        let page = |slug: &str| sections["writing"].pages.iter().find(|p| p.slug == slug).unwrap();
+
+        // `summary` is HTML for every source; `summary_text` is the plain variant
+        let explicit = page("explicit");
+        assert_eq!(
+            explicit.summary.as_deref().map(str::trim),
+            Some("<p>Hand-<em>written</em> &amp; <b>kept</b></p>")
+        );
+        assert_eq!(explicit.summary_text.as_deref(), Some("Hand-written & kept"));
+        let auto = page("auto");
+        assert_eq!(auto.summary.as_deref().map(str::trim), Some("<p>The first paragraph &amp; more.</p>"));
+        assert_eq!(auto.summary_text.as_deref(), Some("The first paragraph & more."));
+        assert_eq!(auto_summary(&format!("<p>{}</p>", "word ".repeat(100))).unwrap().chars().count(), 160);
+    }
+
+    #[test]
+    fn test_more_marker_splits_excerpt_outside_code() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/post.md",
+            "+++\ntitle = \"Post\"\n+++\nIntro with **bold**.\n\n<!-- more -->\n\nThe rest.",
+        );
+        write_file(
+            content.path(),
+            "writing/code.md",
+            "+++\ntitle = \"Code\"\n+++\nLead in.\n\n```html\n<!-- more -->\n```\n\nAfter.",
+        );
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        let page = |slug: &str| sections["writing"].pages.iter().find(|p| p.slug == slug).unwrap();
+
+        let post = page("post");
+        assert_eq!(post.summary.as_deref().map(str::trim), Some("<p>Intro with <strong>bold</strong>.</p>"));
+        assert_eq!(post.summary_text.as_deref(), Some("Intro with bold."));
+        assert!(post.content.contains("The rest."));
+        assert!(!post.summary.as_deref().unwrap().contains("The rest."));
+
+        let code = page("code");
+        assert_eq!(code.summary.as_deref().map(str::trim), Some("<p>Lead in.</p>"));
+        assert_eq!(code.summary_text.as_deref(), Some("Lead in."));
+    }
+
+    #[test]
+    fn test_tag_links_slugify_and_merge_case() {
+        let names = vec!["Rust Lang".to_string(), "rust lang".to_string(), "C++".to_string(), "  ".to_string()];
+        let links = term_links(&names, &TAGS, &test_config());
+
+        let slugs: Vec<&str> = links.iter().map(|l| l.slug.as_str()).collect();
+        assert_eq!(slugs, vec!["rust-lang", "c"]);
+        assert_eq!(links[0].name, "Rust Lang");
+        assert_eq!(links[0].permalink, "https://example.com/tags/rust-lang/");
+        assert_eq!(links[0].relative_path, "tags/rust-lang/index.html");
+    }
+
+    #[test]
+    fn test_collect_tags_groups_listed_pages() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/a.md", "+++\ntitle = \"A\"\ndate = 2024-01-01\ntags = [\"Rust\", \"Web\"]\n+++\nA");
+        write_file(content.path(), "writing/b.md", "+++\ntitle = \"B\"\ndate = 2024-02-01\ntags = [\"rust\"]\n+++\nB");
+        write_file(
+            content.path(),
+            "notes/hidden.md",
+            "+++\ntitle = \"Hidden\"\nvisibility = \"unlisted\"\ntags = [\"Rust\"]\n+++\nC",
+        );
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        let tags = collect_tags(&sections);
+
+        assert_eq!(tags.keys().collect::<Vec<_>>(), vec!["rust", "web"]);
+        let rust: Vec<&str> = tags["rust"].pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(rust, vec!["B", "A"]);
+        assert_eq!(tags["web"].pages.len(), 1);
+    }
+
+    #[test]
+    fn test_tag_feeds_list_exactly_the_tags_pages() {
+        let content = tempfile::tempdir().unwrap();
+        for day in 1..=3 {
+            write_file(
+                content.path(),
+                &format!("writing/rust-{}.md", day),
+                &format!("+++\ntitle = \"Rust {}\"\ndate = 2024-01-0{}\ntags = [\"Rust\"]\n+++\nBody", day, day),
+            );
+        }
+        write_file(content.path(), "writing/web.md", "+++\ntitle = \"Web\"\ntags = [\"Web\"]\n+++\nBody");
+        let mut config = test_config();
+        config.taxonomies.feeds = true;
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "tag.html",
+            concat!(
+                r#"{% for feed in feeds %}<link rel="alternate" type="{{ feed.mime | safe }}" "#,
+                r#"href="{{ path_prefix | safe }}{{ feed.relative_path | safe }}">{% endfor %}"#
+            ),
+        )
+        .unwrap();
+        render_taxonomy(&tera, &config, &TAGS, &collect_tags(&sections), &[], built_at(), &disk).unwrap();
+
+        let read = |path: &str| fs::read_to_string(output.path().join(path)).unwrap();
+        let rss = read("tags/rust/rss.xml");
+        let doc = roxmltree::Document::parse(&rss).unwrap();
+        let titles: Vec<&str> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("item"))
+            .filter_map(|item| item.children().find(|n| n.has_tag_name("title")).and_then(|n| n.text()))
+            .collect();
+        assert_eq!(titles, vec!["Rust 3", "Rust 2", "Rust 1"]);
+        assert!(rss.contains(r#"<atom:link href="https://example.com/tags/rust/rss.xml" rel="self""#), "{}", rss);
+        assert!(rss.contains("<link>https://example.com/tags/rust/</link>"), "{}", rss);
+        let atom = read("tags/rust/atom.xml");
+        assert_eq!(atom.matches("<entry>").count(), 3);
+        assert!(!atom.contains("Web"));
+
+        let listing = read("tags/rust/index.html");
+        let rss_link = r#"<link rel="alternate" type="application/rss+xml" href="../../tags/rust/rss.xml">"#;
+        assert!(listing.contains(rss_link), "{}", listing);
+        assert!(listing.contains(r#"type="application/atom+xml" href="../../tags/rust/atom.xml""#), "{}", listing);
+
+        // Off by default
+        let plain = tempfile::tempdir().unwrap();
+        let plain_disk = Output::disk(plain.path());
+        render_taxonomy(&tera, &test_config(), &TAGS, &collect_tags(&sections), &[], built_at(), &plain_disk).unwrap();
+        assert!(!plain.path().join("tags/rust/rss.xml").exists());
+    }
+
+    #[test]
+    fn test_categories_render_separately_from_tags() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/a.md",
+            "+++\ntitle = \"A\"\ndate = 2024-01-01\ncategory = \"Systems Programming\"\ntags = [\"Rust\"]\n+++\nA",
+        );
+        write_file(content.path(), "writing/b.md", "+++\ntitle = \"B\"\ndate = 2024-02-01\ncategory = \"Systems Programming\"\n+++\nB");
+        write_file(content.path(), "writing/c.md", "+++\ntitle = \"C\"\ncategory = \"Rust\"\n+++\nC");
+
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let a = sections["writing"].pages.iter().find(|p| p.title == "A").unwrap();
+        assert_eq!(a.tags[0].permalink, "https://example.com/tags/rust/");
+        let category = a.category.as_ref().unwrap();
+        assert_eq!(category.slug, "systems-programming");
+        assert_eq!(category.relative_path, "categories/systems-programming/index.html");
+
+        let categories = collect_categories(&sections);
+        assert_eq!(categories.keys().collect::<Vec<_>>(), vec!["rust", "systems-programming"]);
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("categories.html", "{% for c in categories %}{{ c.slug }} {% endfor %}"),
+            (
+                "category.html",
+                "{{ category.name }}:{% for p in category.pages %}{{ p.title }}{% endfor %} {{ categories_index_path | safe }}",
+            ),
+            ("tag.html", "tag {{ tag.name }}"),
+        ])
+        .unwrap();
+        render_taxonomy(&tera, &config, &CATEGORIES, &categories, &[], built_at(), &disk).unwrap();
+        render_taxonomy(&tera, &config, &TAGS, &collect_tags(&sections), &[], built_at(), &disk).unwrap();
+
+        let read = |path: &str| fs::read_to_string(output.path().join(path)).unwrap();
+        assert_eq!(read("categories/index.html"), "rust systems-programming ");
+        assert_eq!(read("categories/systems-programming/index.html"), "Systems Programming:BA categories/index.html");
+        assert_eq!(read("categories/rust/index.html"), "Rust:C categories/index.html");
+        assert_eq!(read("tags/rust/index.html"), "tag Rust");
+        assert!(!output.path().join("tags/index.html").exists());
+        assert!(missing_taxonomy_templates(&tera, &CATEGORIES, &categories).is_empty());
+        let warnings = missing_taxonomy_templates(&tera, &TAGS, &collect_tags(&sections));
+        assert_eq!(warnings, vec![Warning::MissingTaxonomyTemplate {
+            taxonomy: "tags".to_string(),
+            template: "tags.html".to_string(),
+            skipped: "the tag index".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_archive_groups_by_year_and_month() {
+        let content = tempfile::tempdir().unwrap();
+        let post = |title: &str, date: &str| format!("+++\ntitle = \"{}\"\ndate = {}\n+++\n", title, date);
+        write_file(content.path(), "writing/a.md", &post("A", "2023-11-02"));
+        write_file(content.path(), "writing/b.md", &post("B", "2023-11-20"));
+        write_file(content.path(), "writing/c.md", &post("C", "2023-03-14"));
+        write_file(content.path(), "writing/d.md", &post("D", "2024-01-05"));
+        write_file(content.path(), "writing/undated.md", "+++\ntitle = \"Undated\"\n+++\n");
+
+        let mut config = test_config();
+        config.extra.insert("archive_by_year".to_string(), toml::Value::Boolean(true));
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let archive = collect_archive(&sections["writing"].pages);
+        assert_eq!(archive.keys().collect::<Vec<_>>(), vec![&2023, &2024]);
+        assert_eq!(archive[&2023].len(), 2);
+        assert_eq!(archive[&2023][&11].len(), 2);
+        assert_eq!(archive[&2024][&1].len(), 1);
+
+        let years = archive_years(&archive, &config);
+        let counts: Vec<(i32, usize)> = years.iter().map(|y| (y.year, y.page_count)).collect();
+        assert_eq!(counts, vec![(2024, 1), (2023, 3)]);
+        let months: Vec<&str> = years[1].months.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(months, vec!["November", "March"]);
+        let titles: Vec<&str> = years[1].months[0].pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["B", "A"]);
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        let mut tera = Tera::default();
+        tera.add_raw_template("archive.html", "{% for y in years %}{{ y.year }}:{{ y.page_count }} {% endfor %}")
+            .unwrap();
+        render_archive(&tera, &config, &sections, &[], built_at(), &disk).unwrap();
+        let read = |path: &str| fs::read_to_string(output.path().join(path)).unwrap();
+        assert_eq!(read("archive/index.html"), "2024:1 2023:3 ");
+        assert_eq!(read("archive/2023/index.html"), "2023:3 ");
+        let source = content.path().join("writing/undated.md").display().to_string();
+        assert_eq!(undated_archive_pages(&tera, &sections), vec![Warning::UndatedArchivePage { source }]);
+        assert!(undated_archive_pages(&Tera::default(), &sections).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_request_path_maps_directories_and_rejects_traversal() {
+        let output = tempfile::tempdir().unwrap();
+        write_file(output.path(), "index.html", "home");
+        write_file(output.path(), "writing/post/index.html", "post");
+        write_file(output.path(), "css/main.css", "body {}");
+
+        let resolve = |url: &str| resolve_request_path(output.path(), url);
+        assert_eq!(resolve("/"), Some(output.path().join("index.html")));
+        assert_eq!(resolve("/writing/post/?ref=feed"), Some(output.path().join("writing/post/index.html")));
+        assert_eq!(resolve("/css/main.css"), Some(output.path().join("css/main.css")));
+        assert_eq!(resolve("/missing/"), None);
+        assert_eq!(resolve("/../index.html"), None);
+        assert_eq!(content_type(Path::new("a/b.css")), "text/css; charset=utf-8");
+    }
+
+    #[test]
+    fn test_cli_path_defaults_and_overrides() {
+        let cli = Cli::try_parse_from(["site"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.options.paths, SitePaths {
+            config: PathBuf::from(CONFIG_PATH),
+            content_dir: None,
+            output_dir: PathBuf::from(OUTPUT_DIR),
+            templates_dir: PathBuf::from(TEMPLATES_DIR),
+            static_dir: PathBuf::from(STATIC_DIR),
+        });
+
+        let cli = Cli::try_parse_from([
+            "site", "serve", "--port", "8080", "--config", "alt.toml", "--content-dir", "posts",
+            "--output-dir", "dist", "--templates", "themes/plain", "--static-dir", "assets", "--diff",
+        ])
+        .unwrap();
+        assert!(matches!(cli.command, Some(Command::Serve { port: 8080, open: false, in_memory: false })));
+        assert!(cli.options.diff);
+        assert_eq!(cli.options.paths, SitePaths {
+            config: PathBuf::from("alt.toml"),
+            content_dir: Some(PathBuf::from("posts")),
+            output_dir: PathBuf::from("dist"),
+            templates_dir: PathBuf::from("themes/plain"),
+            static_dir: PathBuf::from("assets"),
+        });
+    }
+
+    #[test]
+    fn test_serve_open_flag_and_url() {
+        let cli = Cli::try_parse_from(["site", "serve", "--open"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Serve { port: DEFAULT_SERVE_PORT, open: true, in_memory: false })));
+        assert!(Cli::try_parse_from(["site", "--open"]).is_err());
+
+        assert_eq!(serve_url(DEFAULT_SERVE_PORT), "http://127.0.0.1:1111/");
+        assert_eq!(serve_url(8080), "http://127.0.0.1:8080/");
+    }
+
+    #[test]
+    fn test_in_memory_build_is_served_without_writing_output() {
+        let site = tempfile::tempdir().unwrap();
+        write_file(site.path(), "site.toml", "base_url = \"https://example.com\"\ntitle = \"Test Site\"\ndescription = \"\"\n");
+        write_file(site.path(), "content/writing/post.md", "+++\ntitle = \"Post\"\n+++\nHello");
+        for (name, body) in [
+            ("index.html", "home"),
+            ("section.html", "{{ section.title }}"),
+            ("page.html", "<h1>{{ page.title }}</h1>{{ page.content | safe }}"),
+            ("404.html", "missing"),
+        ] {
+            write_file(site.path(), &format!("templates/{}", name), body);
+        }
+        write_file(site.path(), "static/css/main.css", "body {}");
+
+        let path = |rel: &str| site.path().join(rel).to_str().unwrap().to_string();
+        let cli = Cli::try_parse_from([
+            "site", "serve", "--in-memory", "--config", &path("site.toml"), "--content-dir", &path("content"),
+            "--output-dir", &path("public"), "--templates", &path("templates"), "--static-dir", &path("static"),
+        ])
+        .unwrap();
+        let Some(Command::Serve { in_memory: true, .. }) = cli.command else {
+            panic!("expected serve --in-memory");
+        };
+        let options = BuildOptions { in_memory: true, ..cli.options };
+        let static_dir = site.path().join("static");
+        let server = Served::Memory { files: RwLock::default(), static_dir: static_dir.clone() };
+        server.update(build_site(&options).unwrap());
+
+        let served = |url: &str| {
+            read_served_file(&server, url).map(|(path, bytes)| (path, String::from_utf8(bytes).unwrap()))
+        };
+        assert!(!site.path().join("public").exists());
+        let page = "<h1>Post</h1><p>Hello</p>\n".to_string();
+        assert_eq!(served("/writing/post/"), Some((PathBuf::from("writing/post/index.html"), page.clone())));
+        assert_eq!(served("/css/main.css"), Some((static_dir.join("css/main.css"), "body {}".to_string())));
+        assert_eq!(served("/missing/"), None);
+        assert_eq!(read_output_file(&server, "404.html").map(|(_, bytes)| bytes), Some(b"missing".to_vec()));
+
+        // A build that fails keeps the last good one on the server
+        write_file(site.path(), "templates/page.html", "{{ page.missing_field }}");
+        assert!(build_site(&options).map(|output| server.update(output)).is_err());
+        assert_eq!(served("/writing/post/"), Some((PathBuf::from("writing/post/index.html"), page)));
+    }
+
+    #[test]
+    fn test_parallel_load_and_render_are_deterministic() {
+        let content = tempfile::tempdir().unwrap();
+        for day in 1..=28 {
+            write_file(
+                content.path(),
+                &format!("writing/post-{:02}.md", day),
+                &format!("+++\ntitle = \"Post {:02}\"\ndate = 2024-03-{:02}\n+++\nBody {}", day, day, day),
+            );
+        }
+        let dirs = vec![content.path().to_path_buf()];
+        let config = test_config();
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{{ page.title }}").unwrap();
+
+        let mut outputs = Vec::new();
+        for _ in 0..3 {
+            let (_, sections, _) = load_content(&dirs, &config, &Tera::default()).unwrap();
+            let titles: Vec<String> = sections["writing"].pages.iter().map(|p| p.title.clone()).collect();
+            let expected: Vec<String> = (1..=28).rev().map(|day| format!("Post {:02}", day)).collect();
+            assert_eq!(titles, expected);
+
+            let output = tempfile::tempdir().unwrap();
+
+            let disk = Output::disk(output.path());
+            render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+            let rendered = fs::read_to_string(output.path().join("writing/post-07/index.html")).unwrap();
+            assert_eq!(rendered, "Post 07");
+            outputs.push(snapshot_output(output.path()).unwrap());
+        }
+        assert_eq!(outputs[0].len(), 28);
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[1], outputs[2]);
+    }
+
+    #[test]
+    fn test_nested_keys_and_urls_use_forward_slashes() {
+        let nested: PathBuf = ["writing", "rust", "async"].iter().collect();
+        assert_eq!(url_key(&nested), "writing/rust/async");
+        assert_eq!(url_key(Path::new("")), "");
+
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/rust/async/post.md", "+++\ntitle = \"Post\"\n+++\nBody");
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+
+        let section = &sections["writing/rust/async"];
+        let page = &section.pages[0];
+        assert_eq!(page.permalink, "https://example.com/writing/rust/async/post/");
+        assert_eq!(page.relative_path, "writing/rust/async/post/index.html");
+        for key in sections.keys() {
+            assert!(!key.contains('\\'), "backslash in section key {:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_output_collisions_are_errors() {
+        let page = |source: &str| PageData {
+            relative_path: "writing/post/index.html".to_string(),
+            source: source.to_string(),
+            ..PageData::default()
+        };
+        let mut sections = HashMap::new();
+        sections.insert("writing".to_string(), SectionContent {
+            pages: vec![page("a/writing/post.md"), page("b/writing/post.md")],
+            ..SectionContent::default()
+        });
+        let error = check_output_collisions(&Tera::default(), &sections, &test_config(), None).unwrap_err().to_string();
+        assert!(error.contains("a/writing/post.md") && error.contains("b/writing/post.md"), "{}", error);
+
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/rust.md", "+++\ntitle = \"Rust\"\n+++\nPage");
+        write_file(content.path(), "writing/rust/_index.md", "+++\ntitle = \"Rust\"\n+++\nSection");
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        let error = check_output_collisions(&Tera::default(), &sections, &test_config(), None).unwrap_err().to_string();
+        assert!(error.contains("section 'writing/rust'") && error.contains("rust.md"), "{}", error);
+    }
+
+    #[test]
+    fn test_output_collisions_cover_every_generated_file() {
+        let collision = |files: &[(&str, &str)], config: &Config| {
+            let content = tempfile::tempdir().unwrap();
+            for (path, contents) in files {
+                write_file(content.path(), path, contents);
+            }
+            let mut tera = Tera::default();
+            for template in ["tags.html", "tag.html", "archive.html"] {
+                tera.add_raw_template(template, "").unwrap();
+            }
+            let (_, sections, _) = load_content(&[content.path().to_path_buf()], config, &tera).unwrap();
+            check_output_collisions(&tera, &sections, config, None).map_err(|error| error.to_string())
+        };
+        let post = |front_matter: &str| format!("+++\ntitle = \"Post\"\ndate = 2024-03-01\n{}+++\nBody", front_matter);
+
+        // A second listing page
+        let paginated = [
+            ("writing/_index.md", "+++\npaginate_by = 1\n+++\n".to_string()),
+            ("writing/a.md", post("")),
+            ("writing/b.md", post("aliases = [\"/writing/page/2/\"]\n")),
+        ];
+        let paginated: Vec<(&str, &str)> = paginated.iter().map(|(path, body)| (*path, body.as_str())).collect();
+        let error = collision(&paginated, &test_config()).unwrap_err();
+        assert!(error.contains("page 2 of section 'writing' and an alias"), "{}", error);
+        assert!(error.ends_with("both write writing/page/2/index.html"), "{}", error);
+
+        // A tag listing, the archive, and an error page
+        let tag_page = post("tags = [\"Rust\"]\naliases = [\"/tags/rust/\"]\n");
+        let error = collision(&[("writing/a.md", &tag_page)], &test_config()).unwrap_err();
+        assert!(error.contains("tag 'Rust'"), "{}", error);
+        let error = collision(&[("writing/a.md", &post("aliases = [\"/archive/\"]\n"))], &test_config()).unwrap_err();
+        assert!(error.contains("and the archive both write"), "{}", error);
+        let error = collision(&[("writing/a.md", &post("aliases = [\"/404.html\"]\n"))], &test_config()).unwrap_err();
+        assert!(error.contains("the 404 page"), "{}", error);
+
+        // Plain variants only count when they're built
+        let plain_alias = post("aliases = [\"/writing/a/plain.html\"]\n");
+        collision(&[("writing/a.md", &plain_alias)], &test_config()).unwrap();
+        let config = Config { plain_pages: true, ..test_config() };
+        let error = collision(&[("writing/a.md", &plain_alias)], &config).unwrap_err();
+        assert!(error.contains("the plain variant of page"), "{}", error);
+
+        // Site feeds and section data files, the latter only with `section_json`
+        let rss_listing = [("rss/_index.md", "+++\noutput_filename = \"feed.xml\"\n+++\n")];
+        let error = collision(&rss_listing, &test_config()).unwrap_err();
+        assert_eq!(error, "section 'rss' and the RSS feed both write rss/feed.xml");
+        let data_file = [("writing/_index.md", "+++\noutput_filename = \"index.json\"\n+++\n")];
+        collision(&data_file, &test_config()).unwrap();
+        let mut config = test_config();
+        config.extra.insert("section_json".to_string(), toml::Value::Boolean(true));
+        let error = collision(&data_file, &config).unwrap_err();
+        assert!(error.ends_with("both write writing/index.json"), "{}", error);
+
+        // Copied static files, except robots.txt, which replaces the generated one
+        let static_dir = tempfile::tempdir().unwrap();
+        write_file(static_dir.path(), "robots.txt", "User-agent: *");
+        write_file(static_dir.path(), "sitemap.xml", "<urlset/>");
+        let error = check_output_collisions(&Tera::default(), &HashMap::new(), &test_config(), Some(static_dir.path()))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "the sitemap and the static file sitemap.xml both write sitemap.xml");
+        fs::remove_file(static_dir.path().join("sitemap.xml")).unwrap();
+        check_output_collisions(&Tera::default(), &HashMap::new(), &test_config(), Some(static_dir.path())).unwrap();
+    }
+
+    #[test]
+    fn test_paginate_chunks_and_links() {
+        let pages: Vec<PageData> = (1..=5)
+            .map(|i| PageData { title: format!("Post {}", i), ..PageData::default() })
+            .collect();
+
+        let pagers = paginate(&pages, 2, "writing", &test_config());
+        assert_eq!(pagers.len(), 3);
+        assert_eq!(pagers.iter().map(|p| p.pages.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(pagers[2].pages[0].title, "Post 5");
+        assert_eq!(pagers[0].relative_path, "writing/index.html");
+        assert_eq!(pagers[0].previous, None);
+        assert_eq!(pagers[0].next.as_deref(), Some("writing/page/2/index.html"));
+        assert_eq!(pagers[1].previous.as_deref(), Some("writing/index.html"));
+        assert_eq!(pagers[2].relative_path, "writing/page/3/index.html");
+        assert_eq!(pagers[2].next, None);
+        assert_eq!(pagers[2].last, "writing/page/3/index.html");
+        assert!(pagers.iter().all(|p| p.number_pagers == 3 && p.total_pages == 5));
+
+        let single = paginate(&pages, 5, "writing", &test_config());
+        assert_eq!(single.len(), 1);
+        assert!(single[0].previous.is_none() && single[0].next.is_none());
+
+        let empty = paginate(&[], 10, "writing", &test_config());
+        assert_eq!(empty.len(), 1);
+        assert!(empty[0].pages.is_empty());
+    }
+
+    #[test]
+    fn test_minify_html_preserves_preformatted_content() {
+        let pre = "<pre><code>fn main() {\n    println!(\"hi\");\n}\n</code></pre>";
+        let script = "<script>\n  let   x = 1;\n</script>";
+        let style = "<style>\n  body  { margin: 0; }\n</style>";
+        let html = format!(
+            "<!DOCTYPE html>\n<html>\n  <head>\n    {}\n  </head>\n  <body>\n    <!-- note -->\n    <p>\n      Hello   world\n    </p>\n    {}\n    {}\n  </body>\n</html>\n",
+            style, pre, script
+        );
+
+        let minified = minify_html(&html);
+        assert!(minified.contains(pre), "{}", minified);
+        // Script and style bodies come through byte for byte, whatever their case or attributes
+        assert!(minified.contains(script), "{}", minified);
+        assert!(minified.contains(style), "{}", minified);
+        assert!(!minified.contains("note"));
+        assert!(minified.contains("Hello world"));
+        assert!(minified.len() < html.len());
+
+        let module = "<SCRIPT type=\"module\">\n  const  a = '</p>  x';\n\n</SCRIPT>";
+        let minified = minify_html(&format!("<p>\n  hi\n</p>\n{}", module));
+        assert!(minified.contains("\n  const  a = '</p>  x';\n\n</"), "{}", minified);
+    }
+
+    #[test]
+    fn test_skip_unchanged_assets_leaves_current_copies_alone() {
+        let static_dir = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(static_dir.path(), "img/photo.jpg", "jpeg bytes");
+        write_file(static_dir.path(), "css/main.css", "body {}");
+        assert_eq!(copy_static_assets(static_dir.path(), &disk, false, true).unwrap().1, 2);
+
+        // Date the copy well after the source so a rewrite would be visible
+        let dest = output.path().join("img/photo.jpg");
+        let marked = std::time::SystemTime::now() + Duration::from_secs(3600);
+        fs::File::options().write(true).open(&dest).unwrap().set_modified(marked).unwrap();
+
+        // The count is the site's assets either way, not how many were copied
+        assert_eq!(copy_static_assets(static_dir.path(), &disk, false, true).unwrap().1, 2);
+        assert_eq!(fs::metadata(&dest).unwrap().modified().unwrap(), marked);
+
+        write_file(static_dir.path(), "css/main.css", "body { margin: 0 }");
+        assert_eq!(copy_static_assets(static_dir.path(), &disk, false, true).unwrap().1, 2);
+        assert_eq!(fs::read_to_string(output.path().join("css/main.css")).unwrap(), "body { margin: 0 }");
+        assert_eq!(fs::metadata(&dest).unwrap().modified().unwrap(), marked);
+
+        // Without the option every file is copied again
+        assert_eq!(copy_static_assets(static_dir.path(), &disk, false, false).unwrap().1, 2);
+        assert_ne!(fs::metadata(&dest).unwrap().modified().unwrap(), marked);
+
+        // Current fingerprinted files keep the hash the manifest recorded rather than being hashed again
+        let (manifest, _) = copy_static_assets(static_dir.path(), &disk, true, true).unwrap();
+        let hashed = manifest["img/photo.jpg"].clone();
+        let mut recorded = manifest.clone();
+        recorded.insert("img/photo.jpg".to_string(), "img/photo.recorded.jpg".to_string());
+        write_file(output.path(), ASSET_MANIFEST_PATH, &serde_json::to_string(&recorded).unwrap());
+        write_file(output.path(), "img/photo.recorded.jpg", "jpeg bytes");
+        let (manifest, _) = copy_static_assets(static_dir.path(), &disk, true, true).unwrap();
+        assert_eq!(manifest["img/photo.jpg"], "img/photo.recorded.jpg");
+        assert_eq!(manifest["css/main.css"], fingerprinted_path("css/main.css", b"body { margin: 0 }"));
+
+        // A changed source is hashed again
+        write_file(static_dir.path(), "img/photo.jpg", "new jpeg bytes");
+        let (manifest, _) = copy_static_assets(static_dir.path(), &disk, true, true).unwrap();
+        assert_eq!(manifest["img/photo.jpg"], fingerprinted_path("img/photo.jpg", b"new jpeg bytes"));
+        assert_ne!(manifest["img/photo.jpg"], hashed);
+    }
+
+    #[test]
+    fn test_fingerprinted_assets_and_asset_url() {
+        assert_eq!(
+            fingerprinted_path("css/main.css", b"body {}"),
+            fingerprinted_path("css/main.css", b"body {}")
+        );
+        assert_ne!(
+            fingerprinted_path("css/main.css", b"body {}"),
+            fingerprinted_path("css/main.css", b"body { margin: 0 }")
+        );
+
+        let static_dir = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(static_dir.path(), "css/main.css", "body {}");
+        write_file(static_dir.path(), "favicon.ico", "icon");
+
+        let (manifest, copied) = copy_static_assets(static_dir.path(), &disk, true, false).unwrap();
+        assert_eq!(copied, 2);
+        let hashed = format!("css/main.{}.css", &content_hash(b"body {}")[..8]);
+        assert_eq!(manifest["css/main.css"], hashed);
+        assert_eq!(manifest["favicon.ico"], format!("favicon.{}.ico", &content_hash(b"icon")[..8]));
+        assert!(output.path().join(&hashed).is_file());
+        assert!(output.path().join("css/main.css").is_file());
+
+        let written: AssetManifest =
+            serde_json::from_str(&fs::read_to_string(output.path().join(ASSET_MANIFEST_PATH)).unwrap()).unwrap();
+        assert_eq!(written, manifest);
+
+        let mut tera = Tera::default();
+        tera.add_raw_template("t", r#"{{ asset_url(path="css/main.css") }} {{ asset_url(path="js/none.js") }}"#)
+            .unwrap();
+        register_asset_url(&mut tera, manifest);
+        let rendered = tera.render("t", &TeraContext::new()).unwrap();
+        assert_eq!(rendered, format!("{} js/none.js", hashed));
+    }
+
+    #[test]
+    fn test_robots_txt_references_sitemap() {
+        let mut config = test_config();
+        let static_dir = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        render_robots(&config, static_dir.path(), &disk).unwrap();
+        let robots = fs::read_to_string(output.path().join(ROBOTS_PATH)).unwrap();
+        assert!(robots.contains("User-agent: *"));
+        assert!(robots.lines().any(|line| line == "Sitemap: https://example.com/sitemap.xml"));
+
+        // A robots.txt from an earlier build is regenerated with the new settings
+        config.extra.insert("robots".to_string(), toml::Value::String("User-agent: *\nDisallow: /".to_string()));
+        render_robots(&config, static_dir.path(), &disk).unwrap();
+        assert_eq!(fs::read_to_string(output.path().join(ROBOTS_PATH)).unwrap(), "User-agent: *\nDisallow: /");
+
+        // One from the static directory wins, and copy_static_assets already put it in place
+        write_file(static_dir.path(), ROBOTS_PATH, "User-agent: bot\n");
+        write_file(output.path(), ROBOTS_PATH, "User-agent: bot\n");
+        render_robots(&config, static_dir.path(), &disk).unwrap();
+        assert_eq!(fs::read_to_string(output.path().join(ROBOTS_PATH)).unwrap(), "User-agent: bot\n");
+    }
+
+    #[test]
+    fn test_prev_next_follow_section_order() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\nsort_by = \"title\"\n+++\n");
+        for title in ["Charlie", "Alpha", "Bravo"] {
+            write_file(
+                content.path(),
+                &format!("writing/{}.md", title.to_lowercase()),
+                &format!("+++\ntitle = \"{}\"\n+++\nBody", title),
+            );
+        }
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        let pages = &sections["writing"].pages;
+        let title = |link: &Option<PageLink>| link.as_ref().map(|l| l.title.clone());
+
+        assert_eq!(pages.iter().map(|p| p.title.as_str()).collect::<Vec<_>>(), vec!["Alpha", "Bravo", "Charlie"]);
+        assert_eq!((title(&pages[0].prev), title(&pages[0].next)), (None, Some("Bravo".to_string())));
+        assert_eq!(
+            (title(&pages[1].prev), title(&pages[1].next)),
+            (Some("Alpha".to_string()), Some("Charlie".to_string()))
+        );
+        assert_eq!((title(&pages[2].prev), title(&pages[2].next)), (Some("Bravo".to_string()), None));
+        assert_eq!(pages[1].next.as_ref().unwrap().permalink, "https://example.com/writing/charlie/");
+    }
+
+    #[test]
+    fn test_permalink_pattern_with_dates_and_fallback() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 7);
+        let pattern = "/{section}/{year}/{month}/{slug}/";
+        assert_eq!(
+            expand_permalink(pattern, "writing", "post", date).unwrap().as_deref(),
+            Some("writing/2024/03/post")
+        );
+        assert_eq!(expand_permalink(pattern, "", "post", date).unwrap().as_deref(), Some("2024/03/post"));
+        assert_eq!(expand_permalink(pattern, "writing", "post", None).unwrap(), None);
+        assert!(expand_permalink("/{category}/{slug}/", "writing", "post", date).is_err());
+
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/dated.md", "+++\ntitle = \"Dated\"\ndate = 2024-03-07\n+++\nBody");
+        write_file(content.path(), "writing/undated.md", "+++\ntitle = \"Undated\"\n+++\nBody");
+        let mut config = test_config();
+        config.extra.insert("permalink".to_string(), toml::Value::String(pattern.to_string()));
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let page = |slug: &str| sections["writing"].pages.iter().find(|p| p.slug == slug).unwrap();
+        assert_eq!(page("dated").permalink, "https://example.com/writing/2024/03/dated/");
+        assert_eq!(page("dated").relative_path, "writing/2024/03/dated/index.html");
+        assert_eq!(page("undated").permalink, "https://example.com/writing/undated/");
+        assert_eq!(page("undated").relative_path, "writing/undated/index.html");
+        check_path_consistency(&sections, &config.base_url).unwrap();
+    }
+
+    #[test]
+    fn test_page_context_social_meta() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/post.md",
+            "+++\ntitle = \"Post\"\nsummary = \"Short *summary*\"\nimage = \"images/cover.png\"\n+++\nBody",
+        );
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let page = &sections["writing"].pages[0];
+
+        let context = page_context(&config, &[], built_at(), "writing", page, "../../").into_json();
+        let meta = &context["meta"];
+        assert_eq!(meta["og:url"], page.permalink.as_str());
+        assert_eq!(meta["og:url"], "https://example.com/writing/post/");
+        assert_eq!(meta["og:description"], "Short summary");
+        assert_eq!(meta["og:image"], "https://example.com/images/cover.png");
+        assert_eq!(meta["twitter:card"], "summary_large_image");
+
+        let described = PageData { description: Some("Described".to_string()), ..page.clone() };
+        assert_eq!(social_meta(&described)["og:description"], "Described");
+        assert_eq!(absolute_url("https://cdn.example.com/a.png", &config.base_url), "https://cdn.example.com/a.png");
+    }
+
+    #[test]
+    fn test_search_index_is_plain_text_json() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/one.md",
+            "+++\ntitle = \"One\"\n+++\nSome **bold** [link](/x/) &amp; `code`\n\n<script>alert(1)</script>\n",
+        );
+        write_file(content.path(), "notes/two.md", "+++\ntitle = \"Two\"\nsummary = \"Sum\"\n+++\n## Heading\n\nText");
+        write_file(content.path(), "notes/hidden.md", "+++\ntitle = \"Hidden\"\nvisibility = \"unlisted\"\n+++\nText");
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        render_search_index(&sections, &disk).unwrap();
+        let json = fs::read_to_string(output.path().join(SEARCH_INDEX_PATH)).unwrap();
+        let entries: Vec<SearchEntry> = serde_json::from_str(&json).unwrap();
+
+        let titles: Vec<&str> = entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, vec!["Two", "One"]);
+        assert_eq!(entries[0].summary.as_deref(), Some("Sum"));
+        assert_eq!(entries[1].content, "Some bold link & code");
+        for entry in &entries {
+            assert!(!entry.content.contains('<') && !entry.content.contains('>'), "{}", entry.content);
+        }
+        assert_eq!(strip_html(r#"<a title="a > b">x</a> &#x2F;&#47; &copy;"#), "x // &copy;");
+    }
+
+    #[test]
+    fn test_section_json_round_trips() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/old.md", "+++\ntitle = \"Old\"\ndate = 2024-03-01\n+++\nFirst paragraph.");
+        write_file(content.path(), "writing/new.md", "+++\ntitle = \"New\"\ndate = 2025-01-31\nsummary = \"Sum\"\n+++\nBody");
+        write_file(content.path(), "writing/hidden.md", "+++\ntitle = \"Hidden\"\nvisibility = \"unlisted\"\n+++\nBody");
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        render_section_json(&config, &sections, &disk).unwrap();
+        let json = fs::read_to_string(output.path().join("writing").join(SECTION_JSON_FILE)).unwrap();
+        assert!(json.contains("\"date\": \"2025-01-31\""), "{}", json);
+
+        let section: SectionJson = serde_json::from_str(&json).unwrap();
+        assert_eq!(section.title, "Writing");
+        assert_eq!(section.permalink, "https://example.com/writing/");
+        assert_eq!(section.pages.len(), 2);
+        assert_eq!(section.pages[0].title, "New");
+        assert_eq!(section.pages[0].date, NaiveDate::from_ymd_opt(2025, 1, 31));
+        assert_eq!(section.pages[0].summary.as_deref(), Some("Sum"));
+        assert_eq!(section.pages[1].permalink, "https://example.com/writing/old/");
+    }
+
+    #[test]
+    fn test_math_passes_through_for_katex() {
+        let settings = MarkdownSettings {
+            math: true,
+            ..MarkdownSettings::default()
+        };
+        let html = markdown_to_html("Area is $a^2$ and $x_1 * y_1$.", &settings);
+        assert!(html.contains("<span class=\"math inline\">a^2</span>"), "{}", html);
+        assert!(html.contains("<span class=\"math inline\">x_1 * y_1</span>"), "{}", html);
+
+        let html = markdown_to_html("$$\n\\sum_{i=1}^n i < n^2\n$$\n", &settings);
+        assert!(html.contains("<span class=\"math display\">\\sum_{i=1}^n i &lt; n^2</span>"), "{}", html);
+
+        let html = markdown_to_html("Costs $5 or $10, not `$x$`.", &settings);
+        assert!(html.contains("Costs $5 or $10, not <code>$x$</code>."), "{}", html);
+
+        let plain = markdown_to_html("$a_1 * b_1$", &MarkdownSettings::default());
+        assert!(!plain.contains("math"), "{}", plain);
+    }
+
+    #[test]
+    fn test_internal_link_check_finds_dangling_links() {
+        let output = tempfile::tempdir().unwrap();
+        write_file(output.path(), "writing/kept/index.html", "kept");
+        write_file(
+            output.path(),
+            "writing/post/index.html",
+            r##"<a href="../kept/index.html#top">good</a> <a href="&#x2F;writing&#x2F;kept&#x2F;">good</a>
+               <a href="/writing/renamed/">dangling</a> <a href="https://example.com/writing/kept/">own</a>
+               <a href="https://other.example/missing/">external</a> <a href="mailto:me@example.com">mail</a>
+               <a href="#section">anchor</a>"##,
+        );
+
+        let broken = find_broken_internal_links(output.path(), "https://example.com").unwrap();
+        assert_eq!(broken, vec![("writing/post/index.html".to_string(), "/writing/renamed/".to_string())]);
+        assert!(check_internal_links(output.path(), "https://example.com").is_err());
+
+        assert_eq!(internal_link_exists(output.path(), "index.html", "../../etc/passwd", ""), Some(false));
+    }
+
+    #[test]
+    fn test_subpath_base_url_reaches_permalinks_feeds_and_link_checks() {
+        assert_eq!(base_path("https://example.com/blog"), "/blog");
+        assert_eq!(base_path("https://example.com/a/b"), "/a/b");
+        assert_eq!(base_path("https://example.com"), "");
+        assert_eq!(base_path(""), "");
+
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/post.md", "+++\ntitle = \"Post\"\ndate = 2025-01-01\n+++\nBody");
+        let mut config = test_config();
+        config.base_url = "https://example.com/blog".to_string();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+
+        let post = &sections["writing"].pages[0];
+        assert_eq!(post.permalink, "https://example.com/blog/writing/post/");
+        assert_eq!(post.relative_path, "writing/post/index.html");
+        assert_eq!(section_paths("writing", &config), (
+            "https://example.com/blog/writing/".to_string(),
+            "writing/index.html".to_string(),
+        ));
+        check_path_consistency(&sections, &config.base_url).unwrap();
+        let rss = build_rss_feed(&config, &FeedChannel::site(&config, RSS_FEED_PATH), &feed_pages(&config, &sections));
+        assert!(rss.contains("<link>https://example.com/blog/writing/post/</link>"));
+        assert_eq!(build_json_feed(&config, &[]).feed_url, "https://example.com/blog/feed.json");
+        let sitemap = build_sitemap(&collect_sitemap_entries(&config, &sections));
+        assert!(sitemap.contains("<loc>https://example.com/blog/</loc>"), "{}", sitemap);
+
+        let output = tempfile::tempdir().unwrap();
+        write_file(output.path(), "writing/post/index.html", "post");
+        let exists = |href: &str| internal_link_exists(output.path(), "index.html", href, &config.base_url);
+        assert_eq!(exists("/blog/writing/post/"), Some(true));
+        assert_eq!(exists("/blog/writing/gone/"), Some(false));
+        assert_eq!(exists("/writing/post/"), None);
+        assert_eq!(exists("https://example.com/blog/writing/post/"), Some(true));
+        assert_eq!(exists("writing/post/"), Some(true));
+        // Another site whose URL merely starts with the base URL is external, not `roll/`
+        write_file(output.path(), "roll/index.html", "roll");
+        assert_eq!(exists("https://example.com/blogroll/"), None);
+
+        let context = build_base_context(&config, &[], built_at(), "");
+        assert_eq!(context.get("base_path"), Some(&serde_json::json!("/blog")));
+    }
+
+    #[test]
+    fn test_smart_punctuation_is_opt_in_and_skips_code() {
+        let md = "\"Quoted\" -- and --- dashes `\"code\" --`\n\n```\n\"block\" --\n```\n";
+
+        let plain = markdown_to_html(md, &MarkdownSettings::default());
+        assert!(plain.contains("\"Quoted\" -- and --- dashes"), "{}", plain);
+
+        let settings = MarkdownSettings {
+            features: MarkdownConfig {
+                smart_punctuation: true,
+                ..MarkdownConfig::default()
+            },
+            ..MarkdownSettings::default()
+        };
+        let smart = markdown_to_html(md, &settings);
+        assert!(smart.contains("“Quoted” – and — dashes"), "{}", smart);
+        assert!(smart.contains("<code>\"code\" --</code>"), "{}", smart);
+        assert!(smart.contains("\"block\" --\n</code></pre>"), "{}", smart);
+    }
+
+    #[test]
+    fn test_markdown_extensions_follow_config() {
+        let md = "| a | b |\n|---|---|\n| 1 | 2 |\n\n## Custom {#custom-id}\n";
+
+        let default = markdown_to_html(md, &MarkdownSettings::default());
+        assert!(default.contains("<table>"), "{}", default);
+        assert!(default.contains("{#custom-id}"), "{}", default);
+
+        let mut config = test_config();
+        config.markdown = toml::from_str("tables = false\nheading_attributes = true").unwrap();
+        let html = markdown_to_html(md, &MarkdownSettings::from_config(&config));
+        assert!(!html.contains("<table>"), "{}", html);
+        assert!(html.contains("| a | b |"), "{}", html);
+        assert!(html.contains("<h2 id=\"custom-id\">"), "{}", html);
+        assert!(MarkdownSettings::from_config(&config).features.footnotes);
+    }
+
+    #[test]
+    fn test_sanitize_html_strips_scripts_only_when_enabled() {
+        let md = "## Title\n\n<script>alert(1)</script>\n\n<p onclick=\"alert(1)\">Hi</p>\n\n- [x] done\n";
+
+        let raw = markdown_to_html(md, &MarkdownSettings::default());
+        assert!(raw.contains("<script>alert(1)</script>"), "{}", raw);
+        assert!(raw.contains("onclick"), "{}", raw);
+
+        let settings = MarkdownSettings {
+            sanitize_html: true,
+            ..MarkdownSettings::default()
+        };
+        let clean = markdown_to_html(md, &settings);
+        assert!(!clean.contains("script") && !clean.contains("alert"), "{}", clean);
+        assert!(!clean.contains("onclick"), "{}", clean);
+        assert!(clean.contains("<p>Hi</p>"), "{}", clean);
+        assert!(clean.contains("<h2 id=\"title\">"), "{}", clean);
+        assert!(clean.contains("type=\"checkbox\""), "{}", clean);
+    }
+
+    #[test]
+    fn test_emoji_shortcodes_expand_outside_code() {
+        let settings = MarkdownSettings {
+            features: MarkdownConfig {
+                emoji: true,
+                ..MarkdownConfig::default()
+            },
+            ..MarkdownSettings::default()
+        };
+        let md = "Launch :rocket: but not :notanemoji: at 10:30:00 `:rocket:`\n\n```\n:rocket:\n```\n";
+        let html = markdown_to_html(md, &settings);
+        assert!(html.contains("Launch 🚀 but not :notanemoji: at 10:30:00 <code>:rocket:</code>"), "{}", html);
+        assert!(html.contains("<code>:rocket:\n</code>"), "{}", html);
+
+        let plain = markdown_to_html(":rocket:", &MarkdownSettings::default());
+        assert!(plain.contains(":rocket:"), "{}", plain);
+    }
+
+    #[test]
+    fn test_task_lists_render_checkboxes() {
+        let html = markdown_to_html("- [ ] todo\n- [x] done\n- plain\n", &MarkdownSettings::default());
+
+        assert!(html.contains(r#"<li><input disabled="" type="checkbox"/>"#), "{}", html);
+        assert!(html.contains(r#"<li><input disabled="" type="checkbox" checked=""/>"#), "{}", html);
+        assert_eq!(html.matches("type=\"checkbox\"").count(), 2);
+        assert!(html.contains("<li>plain</li>"));
+        assert!(!html.contains("[ ]"));
+    }
+
+    #[test]
+    fn test_slug_override_sets_output_path() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(content.path(), "writing/my-file.md", "+++\ntitle = \"Mine\"\nslug = \"Custom\"\n+++\nBody");
+        write_file(content.path(), "writing/custom.md", "+++\ntitle = \"Clash\"\n+++\nBody");
+
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let page = sections["writing"].pages.iter().find(|p| p.title == "Mine").unwrap();
+        assert_eq!(page.slug, "custom");
+        assert_eq!(page.permalink, "https://example.com/writing/custom/");
+        assert_eq!(page.relative_path, "writing/custom/index.html");
+        assert!(check_output_collisions(&Tera::default(), &sections, &test_config(), None).is_err());
+
+        let (_, mut sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        sections.get_mut("writing").unwrap().pages.retain(|p| p.title == "Mine");
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{{ page.title }}").unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+        assert_eq!(fs::read_to_string(output.path().join("writing/custom/index.html")).unwrap(), "Mine");
+        assert!(!output.path().join("writing/my-file").exists());
+    }
+
+    #[test]
+    fn test_aliases_write_redirect_stubs() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(
+            content.path(),
+            "writing/new-name.md",
+            "+++\ntitle = \"New\"\naliases = [\"/blog/old-name/\", \"/old.html\"]\n+++\nBody",
+        );
+        write_file(content.path(), "writing/ancien.md", "+++\nlang = \"fr\"\naliases = [\"/vieux/\"]\n+++\nCorps");
+        let config = Config { lang: "en-GB".to_string(), ..test_config() };
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        check_output_collisions(&Tera::default(), &sections, &test_config(), None).unwrap();
+
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{{ page.title }}").unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+
+        let stub = fs::read_to_string(output.path().join("blog/old-name/index.html")).unwrap();
+        assert!(stub.contains(r#"<meta http-equiv="refresh" content="0; url=https://example.com/writing/new-name/">"#));
+        assert!(stub.contains(r#"<link rel="canonical" href="https://example.com/writing/new-name/">"#));
+        assert!(output.path().join("old.html").is_file());
+        // Stubs carry the page's language, or the site's
+        assert!(stub.contains(r#"<html lang="en-GB">"#), "{}", stub);
+        let stub = fs::read_to_string(output.path().join("vieux/index.html")).unwrap();
+        assert!(stub.contains(r#"<html lang="fr">"#), "{}", stub);
+
+        write_file(content.path(), "writing/other.md", "+++\naliases = [\"/writing/new-name/\"]\n+++\nBody");
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        assert!(check_output_collisions(&Tera::default(), &sections, &test_config(), None).is_err());
+
+        assert!(alias_output_path("old/").is_err());
+        assert!(alias_output_path("/../escape/").is_err());
+    }
+
+    #[test]
+    fn test_humanize_slug_title_cases_words() {
+        assert_eq!(humanize_slug("intro", &[]), "Intro");
+        assert_eq!(humanize_slug("my-first-post", &[]), "My First Post");
+        assert_eq!(humanize_slug("SHOUTY_title--here", &[]), "Shouty Title Here");
+        assert_eq!(humanize_slug("ipv6-api-notes", &[]), "Ipv6 Api Notes");
+        let acronyms = vec!["IPv6".to_string(), "API".to_string()];
+        assert_eq!(humanize_slug("ipv6-api-notes", &acronyms), "IPv6 API Notes");
+
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/deep-dives/my-first-post.md", "+++\n+++\nBody");
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        assert_eq!(sections["writing/deep-dives"].pages[0].title, "My First Post");
+        let links = section_links(&sections["writing"].subsections, &sections, &config);
+        assert_eq!(links[0].title, "Deep Dives");
+    }
+
+    #[test]
+    fn test_home_section_is_configurable() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(content.path(), "blog/_index.md", "+++\ntitle = \"Blog\"\n+++\n");
+        write_file(content.path(), "blog/post.md", "+++\ntitle = \"Blog Post\"\n+++\nBody");
+        write_file(content.path(), "writing/essay.md", "+++\ntitle = \"Essay\"\n+++\nBody");
+
+        let mut config = test_config();
+        config.extra.insert("home_section".to_string(), toml::Value::String("blog".to_string()));
+        let (root, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "index.html",
+            "{% for page in writing_pages %}{{ page.title }};{% endfor %}{{ sections.blog.title }}/{{ sections.writing.pages | length }}",
+        )
+        .unwrap();
+        render_home(&tera, &config, &sections, &[], built_at(), &disk, &root).unwrap();
+
+        let home = fs::read_to_string(output.path().join("index.html")).unwrap();
+        assert_eq!(home, "Blog Post;Blog/1");
+    }
+
+    #[test]
+    fn test_base_context_has_nav_entry_per_section() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\nweight = 2\n+++\n");
+        write_file(content.path(), "about/_index.md", "+++\ntitle = \"About\"\nnav_order = 1\n+++\n");
+        write_file(content.path(), "projects/_index.md", "+++\ntitle = \"Projects\"\n+++\n");
+        write_file(content.path(), "projects/rust/_index.md", "+++\ntitle = \"Rust\"\n+++\n");
+
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let nav = nav_sections(&sections, &config);
+        let context = build_base_context(&config, &nav, built_at(), "../").into_json();
+
+        let titles: Vec<&str> = context["nav_sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|section| section["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, vec!["About", "Writing", "Projects"]);
+        assert_eq!(context["nav_sections"][1]["relative_path"], "writing/index.html");
+        assert!(context["nav_sections"][0].get("pages").is_none());
+    }
+
+    #[test]
+    fn test_shortcodes_expand_outside_code() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("shortcodes/video.html", "<div class=\"video\" data-id=\"{{ id }}\" data-start=\"{{ start }}\"></div>\n"),
+            ("shortcodes/note.html", "<div class=\"note {{ kind }}\">{{ body | safe }}</div>"),
+        ])
+        .unwrap();
+        let body = "Intro {{ video(id=\"abc\", start=30) }}\n\n\
+                    {% note(kind='tip') %}Use **this**{% end %}\n\n\
+                    `{{ video(id=\"code\") }}` and {{ unknown(x=1) }} and {{ config.title }}\n\n\
+                    ```\n{{ video(id=\"fenced\") }}\n```\n";
+
+        let (expanded, outputs) = expand_shortcodes(body, &tera, &test_config()).unwrap();
+        let expanded = restore_shortcodes(expanded, &outputs);
+
+        assert!(expanded.starts_with("Intro <div class=\"video\" data-id=\"abc\" data-start=\"30\"></div>\n"), "{}", expanded);
+        assert!(expanded.contains("<div class=\"note tip\">Use **this**</div>"), "{}", expanded);
+        assert!(expanded.contains("`{{ video(id=\"code\") }}` and {{ unknown(x=1) }} and {{ config.title }}"), "{}", expanded);
+        assert!(expanded.contains("```\n{{ video(id=\"fenced\") }}\n```"), "{}", expanded);
+
+        let unclosed = expand_shortcodes("{% note(kind='tip') %}open", &tera, &test_config());
+        assert!(unclosed.is_err());
+    }
+
+    #[test]
+    fn test_markdown_templating_leaves_shortcode_output_alone() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("shortcodes/mustache.html", "<code>{% raw %}{{ name }}{% endraw %}</code>").unwrap();
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/post.md",
+            "+++\ntitle = \"Post\"\n+++\nOn {{ config.title }}: {{ mustache() }} and {{ page.title }}",
+        );
+        let config = Config { markdown_templating: true, ..test_config() };
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &tera).unwrap();
+        let post = &sections["writing"].pages[0];
+        assert!(post.content.contains("On Test Site: <code>{{ name }}</code> and Post"), "{}", post.content);
+    }
+
+    fn render_with_filters(template: &str, context: &TeraContext) -> tera::Result<String> {
+        let mut tera = Tera::default();
+        register_filters(&mut tera);
+        tera.add_raw_template("test.html", template)?;
+        tera.render("test.html", context)
+    }
+
+    #[test]
+    fn test_date_format_filter() {
+        let page = PageData {
+            date: NaiveDate::from_ymd_opt(2025, 3, 7),
+            ..PageData::default()
+        };
+        let mut context = TeraContext::new();
+        context.insert("page", &page);
+        context.insert("undated", &PageData::default());
+
+        let rendered = render_with_filters(
+            r#"{{ page.date | date_format(fmt="%B %-d, %Y") }}|{{ undated.date | date_format(fmt="%Y") }}|"#,
+            &context,
+        )
+        .unwrap();
+        assert_eq!(rendered, "March 7, 2025||");
+        assert!(render_with_filters(r#"{{ page.date | date_format(fmt="%Q") }}"#, &context).is_err());
+    }
+
+    #[test]
+    fn test_slugify_filter() {
+        let mut context = TeraContext::new();
+        context.insert("title", "Hello, World: Part 2!");
+        let rendered = render_with_filters("{{ title | slugify }}", &context).unwrap();
+        assert_eq!(rendered, "hello-world-part-2");
+    }
+
+    #[test]
+    fn test_truncate_words_filter() {
+        let mut context = TeraContext::new();
+        context.insert("text", "one  two\nthree four");
+        let rendered = render_with_filters(
+            "{{ text | truncate_words(n=2) }}|{{ text | truncate_words(n=4) }}",
+            &context,
+        )
+        .unwrap();
+        assert_eq!(rendered, "one two…|one two three four");
+    }
+
+    #[test]
+    fn test_template_error_names_template_and_cause() {
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("partials/broken.html", "{{ page.missing_field }}"),
+            ("layout.html", "<main>{% include \"partials/broken.html\" %}</main>"),
+        ])
+        .unwrap();
+        let mut context = TeraContext::new();
+        context.insert("page", &PageData::default());
+
+        let error = render_template_to_file(
+            &tera,
+            &test_config(),
+            "layout.html",
+            &context,
+            &disk,
+            &output.path().join("index.html"),
+            "page writing/post",
+        )
+        .unwrap_err();
+
+        let message = format!("{:#}", error);
+        assert!(message.starts_with("rendering page writing/post with template layout.html"), "{}", message);
+        assert!(message.contains("page.missing_field"), "{}", message);
+        assert!(message.contains("partials/broken.html"), "{}", message);
+    }
+
+    #[test]
+    fn test_plain_pages_render_without_chrome() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(content.path(), "writing/post.md", "+++\ntitle = \"Plain Post\"\n+++\nSome *emphasis*.");
+
+        let mut config = test_config();
+        config.plain_pages = true;
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "<nav>CHROME</nav>{{ page.content | safe }}").unwrap();
+        add_builtin_templates(&mut tera, &config).unwrap();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+
+        let page = fs::read_to_string(output.path().join("writing/post/index.html")).unwrap();
+        let plain = fs::read_to_string(output.path().join("writing/post/plain.html")).unwrap();
+        assert!(plain.contains("<h1>Plain Post</h1>"), "{}", plain);
+        assert!(plain.contains(&sections["writing"].pages[0].content), "{}", plain);
+        assert!(page.contains("<em>emphasis</em>") && plain.contains("<em>emphasis</em>"));
+        assert!(!plain.contains("CHROME"));
+
+        // A site template replaces the built-in one
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![("page.html", ""), ("plain.html", "custom {{ page.title }}")]).unwrap();
+        add_builtin_templates(&mut tera, &config).unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+        assert_eq!(fs::read_to_string(output.path().join("writing/post/plain.html")).unwrap(), "custom Plain Post");
+
+        assert_eq!(plain_output_path("writing/post.html"), "writing/post.plain.html");
+    }
+
+    #[test]
+    fn test_menu_is_sorted_and_prefixed_in_context() {
+        let config: Config = toml::from_str(
+            r#"
+base_url = "https://example.com"
+title = "Test Site"
+description = ""
+
+[[menu]]
+name = "Unweighted"
+url = "https://github.com/example"
+
+[[menu]]
+name = "Writing"
+url = "/writing/"
+weight = 2
+
+[[menu.children]]
+name = "Rust"
+url = "/writing/rust/"
+
+[[menu]]
+name = "Home"
+url = "/"
+weight = 1
+"#,
+        )
+        .unwrap();
+
+        let context = build_base_context(&config, &[], built_at(), "../../").into_json();
+        let names: Vec<&str> = context["menu"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["Home", "Writing", "Unweighted"]);
+        assert_eq!(context["menu"][0]["url"], "../../");
+        assert_eq!(context["menu"][1]["url"], "../../writing/");
+        assert_eq!(context["menu"][1]["children"][0]["url"], "../../writing/rust/");
+        assert_eq!(context["menu"][2]["url"], "https://github.com/example");
+
+        let home = menu_links(&config.menu, "");
+        assert_eq!((home[0].url.as_str(), home[1].url.as_str()), ("./", "writing/"));
+        assert_eq!(menu_url("//cdn.example.com/x", "../"), "//cdn.example.com/x");
+    }
+
+    #[test]
+    fn test_configured_error_pages_are_rendered() {
+        let output = tempfile::tempdir().unwrap();
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("404.html", "missing {{ status_code }} {{ body_class }}"),
+            ("500.html", "broken {{ status_code }} {{ config.title }}"),
+        ])
+        .unwrap();
+
+        let config = test_config();
+        render_error_pages(&tera, &config, &[], built_at(), &Output::disk(output.path())).unwrap();
+        assert_eq!(fs::read_to_string(output.path().join("404.html")).unwrap(), "missing 404 error-404");
+
+        let mut config: Config = toml::from_str(
+            r#"
+base_url = "https://example.com"
+title = "Test Site"
+description = ""
+
+[error_pages.404]
+path = "/404/"
+
+[error_pages.500]
+"#,
+        )
+        .unwrap();
+        let output = tempfile::tempdir().unwrap();
+        render_error_pages(&tera, &config, &[], built_at(), &Output::disk(output.path())).unwrap();
+        assert_eq!(fs::read_to_string(output.path().join("404/index.html")).unwrap(), "missing 404 error-404");
+        assert_eq!(fs::read_to_string(output.path().join("500.html")).unwrap(), "broken 500 Test Site");
+        assert!(!output.path().join("404.html").exists());
+
+        config.error_pages.insert("200".to_string(), ErrorPageConfig::default());
+        assert!(config.error_pages().is_err());
+        assert!(error_page_output_path("../outside.html").is_err());
+    }
+
+    #[test]
+    fn test_missing_templates_are_reported_before_rendering() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/ok.md", "+++\ntitle = \"Ok\"\n+++\nBody");
+        write_file(content.path(), "writing/typo.md", "+++\ntitle = \"Typo\"\ntemplate = \"typo.html\"\n+++\nBody");
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+
+        let mut tera = Tera::default();
+        for name in ["index.html", "404.html", "section.html", "page.html"] {
+            tera.add_raw_template(name, "").unwrap();
+        }
+        let error = check_templates_exist(&tera, &test_config(), &sections).unwrap_err().to_string();
+        assert!(error.contains("typo.html (requested by page "), "{}", error);
+        assert!(error.contains("typo.md)"), "{}", error);
+        assert!(!error.contains("ok.md"), "{}", error);
+
+        tera.add_raw_template("typo.html", "").unwrap();
+        check_templates_exist(&tera, &test_config(), &sections).unwrap();
+    }
+
+    #[test]
+    fn test_page_template_precedence() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/_index.md",
+            "+++\ntitle = \"Writing\"\ntemplate = \"page.html\"\ndefault_template = \"essay.html\"\n+++\n",
+        );
+        write_file(content.path(), "writing/essay.md", "+++\ntitle = \"Essay\"\n+++\n");
+        write_file(content.path(), "writing/custom.md", "+++\ntitle = \"Custom\"\ntemplate = \"custom.html\"\n+++\n");
+        write_file(
+            content.path(),
+            "projects/_index.md",
+            "+++\ntitle = \"Projects\"\ntemplate = \"landing.html\"\n+++\n",
+        );
+        write_file(content.path(), "projects/tool.md", "+++\ntitle = \"Tool\"\n+++\n");
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+
+        let template_of = |section: &str, slug: &str| {
+            let section = &sections[section];
+            let page = section.pages.iter().find(|page| page.slug == slug).unwrap();
+            page_template(page, section).to_string()
+        };
+        // The page's own template wins over the section default
+        assert_eq!(template_of("writing", "custom"), "custom.html");
+        // Pages without one use the section's default_template, not the section's own template
+        assert_eq!(template_of("writing", "essay"), "essay.html");
+        assert_eq!(section_template(&sections["writing"]), "page.html");
+        // Without a default_template, pages fall back to page.html
+        assert_eq!(template_of("projects", "tool"), "page.html");
+        assert_eq!(section_template(&sections["projects"]), "landing.html");
+    }
+
+    #[test]
+    fn test_section_output_filename_renders_feed_xml() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "podcast/_index.md",
+            concat!(
+                "+++\ntitle = \"Podcast\"\ntemplate = \"podcast.xml\"\n",
+                "output_filename = \"feed.xml\"\npaginate_by = 1\n+++\n"
+            ),
+        );
+        write_file(content.path(), "podcast/one.md", "+++\ntitle = \"Tom & Jerry\"\ndate = 2024-01-01\n+++\nOne");
+        write_file(content.path(), "podcast/two.md", "+++\ntitle = \"Two\"\ndate = 2024-01-02\n+++\nTwo");
+        let mut config = test_config();
+        config.extra.insert("minify".to_string(), toml::Value::Boolean(true));
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        check_output_collisions(&Tera::default(), &sections, &config, None).unwrap();
+        assert!(nav_sections(&sections, &config).is_empty());
+        let crumb = &sections["podcast"].pages[0].breadcrumbs[1];
+        assert_eq!(
+            (crumb.title.as_str(), crumb.permalink.as_str()),
+            ("Podcast", "https://example.com/podcast/feed.xml")
+        );
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "podcast.xml",
+            concat!(
+                "<rss>\n  <link>{{ section.permalink | safe }}</link>\n",
+                "{% for page in section.pages %}  <title>{{ page.title }}</title>\n{% endfor %}</rss>\n"
+            ),
+        )
+        .unwrap();
+        render_sections(&tera, &config, &sections, &[], built_at(), &disk).unwrap();
+
+        // Rendered through Tera (with XML escaping) but not minified, and never paginated
+        assert_eq!(
+            fs::read_to_string(output.path().join("podcast/feed.xml")).unwrap(),
+            concat!(
+                "<rss>\n  <link>https://example.com/podcast/feed.xml</link>\n",
+                "  <title>Two</title>\n  <title>Tom &amp; Jerry</title>\n</rss>\n"
+            )
+        );
+        assert!(!output.path().join("podcast/index.html").exists());
+        assert!(!output.path().join("podcast/page").exists());
+        let sitemap = collect_sitemap_entries(&config, &sections);
+        assert!(sitemap.iter().all(|entry| !entry.loc.ends_with("/podcast/") && !entry.loc.ends_with("feed.xml")));
+
+        let mut sections = sections;
+        sections.get_mut("podcast").unwrap().meta.output_filename = Some("../feed.xml".to_string());
+        let error = check_output_collisions(&Tera::default(), &sections, &config, None).unwrap_err().to_string();
+        assert!(error.contains("must be a plain file name"), "{}", error);
+    }
+
+    fn incremental_fixture(content: &Path) -> (Tera, HashMap<String, SectionContent>) {
+        write_file(content, "writing/one.md", "+++\ntitle = \"One\"\n+++\nFirst");
+        write_file(content, "writing/two.md", "+++\ntitle = \"Two\"\ntemplate = \"essay.html\"\n+++\nSecond");
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base.html", "{% block content %}{% endblock content %}"),
+            ("page.html", "{% extends \"base.html\" %}"),
+            ("essay.html", "{{ page.title }}"),
+            ("partials/footer.html", ""),
+        ])
+        .unwrap();
+        let (_, sections, _) = load_content(&[content.to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        (tera, sections)
+    }
+
+    #[test]
+    fn test_incremental_build_skips_unchanged_pages() {
+        let content = tempfile::tempdir().unwrap();
+        let (tera, sections) = incremental_fixture(content.path());
+        let previous = build_manifest(&tera, &sections, "site".to_string()).unwrap();
+
+        let current = build_manifest(&tera, &sections, "site".to_string()).unwrap();
+        assert_eq!(pages_to_render(&previous, &current, &tera), Some(HashSet::new()));
+
+        write_file(content.path(), "writing/two.md", "+++\ntitle = \"Two\"\ntemplate = \"essay.html\"\n+++\nRevised");
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        let current = build_manifest(&tera, &sections, "site".to_string()).unwrap();
+        let dirty = pages_to_render(&previous, &current, &tera).unwrap();
+        assert_eq!(dirty, HashSet::from(["writing/two/index.html".to_string()]));
+
+        let reconfigured = build_manifest(&tera, &sections, "new config".to_string()).unwrap();
+        assert_eq!(pages_to_render(&previous, &reconfigured, &tera), None);
+    }
+
+    #[test]
+    fn test_incremental_build_template_change_invalidates_dependents() {
+        let content = tempfile::tempdir().unwrap();
+        let (tera, sections) = incremental_fixture(content.path());
+        let previous = build_manifest(&tera, &sections, "site".to_string()).unwrap();
+        let touched = |name: &str| {
+            let mut current = previous.clone();
+            current.templates.insert(name.to_string(), Some(1));
+            current
+        };
+
+        let dirty = pages_to_render(&previous, &touched("essay.html"), &tera).unwrap();
+        assert_eq!(dirty, HashSet::from(["writing/two/index.html".to_string()]));
+
+        // A parent template reaches every page that extends it
+        let dirty = pages_to_render(&previous, &touched("base.html"), &tera).unwrap();
+        assert_eq!(dirty, HashSet::from(["writing/one/index.html".to_string()]));
+
+        // Includes can't be traced to pages, so they rebuild everything
+        assert_eq!(pages_to_render(&previous, &touched("partials/footer.html"), &tera), None);
+    }
+
+    #[test]
+    fn test_incremental_build_removes_every_stale_output() {
+        let static_dir = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        write_file(static_dir.path(), "css/main.css", "body {}");
+        copy_static_assets(static_dir.path(), &Output::disk(output.path()), false, false).unwrap();
+        // Left over from the previous build: a tag, a deleted asset, and a file to keep
+        for stale in ["tags/gone/index.html", "tags/gone/rss.xml", "img/deleted.png", "writing/page/3/index.html"] {
+            write_file(output.path(), stale, "old");
+        }
+        write_file(output.path(), "CNAME", "example.com");
+        write_file(output.path(), "writing/post/index.html", "old post");
+
+        let build = Output::disk(output.path());
+        build.write(&output.path().join("writing/index.html"), "listing").unwrap();
+        // Unchanged assets are kept without being copied again
+        copy_static_assets(static_dir.path(), &build, false, true).unwrap();
+        // A page the build didn't re-render
+        build.record(&output.path().join("writing/post/index.html"));
+        remove_stale_outputs(output.path(), &build.written.lock().unwrap(), &[PathBuf::from("CNAME")]).unwrap();
+
+        let mut left: Vec<String> = WalkDir::new(output.path())
+            .min_depth(1)
+            .into_iter()
+            .map(|entry| url_key(entry.unwrap().path().strip_prefix(output.path()).unwrap()))
+            .collect();
+        left.sort();
+        assert_eq!(left, vec![
+            "CNAME",
+            "css",
+            "css/main.css",
+            "writing",
+            "writing/index.html",
+            "writing/post",
+            "writing/post/index.html",
+        ]);
+    }
+
+    #[test]
+    fn test_missing_content_directory_is_an_error() {
+        let missing = tempfile::tempdir().unwrap().path().join("content");
+        let error = load_content(&[missing], &test_config(), &Tera::default()).unwrap_err();
+        assert!(error.to_string().contains("does not exist"), "{}", error);
+    }
+
+    #[test]
+    fn test_empty_content_directory_builds_home_only() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        let config = test_config();
+        let (root, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        assert!(sections.is_empty());
+        assert_eq!(root.title, "Test Site");
+
+        let mut tera = Tera::default();
+        tera.add_raw_template("index.html", "{{ section.title }}:{{ writing_pages | length }}").unwrap();
+        render_home(&tera, &config, &sections, &[], built_at(), &disk, &root).unwrap();
+        assert_eq!(fs::read_to_string(output.path().join("index.html")).unwrap(), "Test Site:0");
+    }
+
+    #[test]
+    fn test_page_lang_overrides_site_default() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/bonjour.md", "+++\ntitle = \"Bonjour\"\nlang = \"fr\"\n+++\nSalut");
+        write_file(content.path(), "writing/hello.md", "+++\ntitle = \"Hello\"\n+++\nHi");
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+
+        let lang_of = |slug: &str| {
+            let page = sections["writing"].pages.iter().find(|p| p.slug == slug).unwrap();
+            page_context(&config, &[], built_at(), "writing", page, "../../").into_json()["lang"].clone()
+        };
+        assert_eq!(lang_of("bonjour"), "fr");
+        assert_eq!(lang_of("hello"), "en");
+        assert_eq!(build_base_context(&config, &[], built_at(), "").into_json()["lang"], "en");
+    }
+
+    #[test]
+    fn test_pretty_and_ugly_url_styles() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\npaginate_by = 1\n+++\n");
+        write_file(content.path(), "writing/one.md", "+++\ntitle = \"One\"\n+++\nBody");
+        write_file(content.path(), "writing/two.md", "+++\ntitle = \"Two\"\n+++\nBody");
+
+        let pretty = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &pretty, &Tera::default()).unwrap();
+        let one = sections["writing"].pages.iter().find(|p| p.slug == "one").unwrap();
+        assert_eq!(one.relative_path, "writing/one/index.html");
+        assert_eq!(one.permalink, "https://example.com/writing/one/");
+        assert_eq!(section_paths("writing", &pretty).1, "writing/index.html");
+        assert_eq!(pager_path("writing", 2, &pretty), "writing/page/2/index.html");
+
+        let mut ugly = test_config();
+        ugly.ugly_urls = true;
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &ugly, &Tera::default()).unwrap();
+        let one = sections["writing"].pages.iter().find(|p| p.slug == "one").unwrap();
+        assert_eq!(one.relative_path, "writing/one.html");
+        assert_eq!(one.permalink, "https://example.com/writing/one.html");
+        check_path_consistency(&sections, &ugly.base_url).unwrap();
+        check_output_collisions(&Tera::default(), &sections, &ugly, None).unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("section.html", "{{ path_prefix | safe }}|{{ paginator.next | default(value='') | safe }}"),
+            ("page.html", "{{ path_prefix | safe }}"),
+            ("index.html", "home"),
+        ])
+        .unwrap();
+        render_sections(&tera, &ugly, &sections, &[], built_at(), &disk).unwrap();
+        render_pages(&tera, &ugly, &sections, &[], built_at(), &disk, None).unwrap();
+        let (root, _, _) = load_content(&[content.path().to_path_buf()], &ugly, &Tera::default()).unwrap();
+        render_home(&tera, &ugly, &sections, &[], built_at(), &disk, &root).unwrap();
+
+        let read = |path: &str| fs::read_to_string(output.path().join(path)).unwrap();
+        assert_eq!(read("writing.html"), "|writing/page/2.html");
+        assert_eq!(read("writing/page/2.html"), "../../|");
+        assert_eq!(read("writing/one.html"), "../");
+        assert_eq!(read("index.html"), "home");
+        assert!(!output.path().join("writing/index.html").exists());
+    }
+
+    #[test]
+    fn test_render_functions_return_counts() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/one.md", "+++\ntitle = \"One\"\n+++\nBody");
+        write_file(content.path(), "writing/two.md", "+++\ntitle = \"Two\"\n+++\nBody");
+        write_file(content.path(), "projects/_index.md", "+++\ntitle = \"Projects\"\n+++\n");
+        write_file(content.path(), "projects/tool.md", "+++\ntitle = \"Tool\"\n+++\nBody");
+        let static_dir = tempfile::tempdir().unwrap();
+        write_file(static_dir.path(), "css/main.css", "body {}");
+
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![("section.html", "section"), ("page.html", "page")]).unwrap();
+
+        assert_eq!(render_sections(&tera, &config, &sections, &[], built_at(), &disk).unwrap(), 2);
+        assert_eq!(render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap(), 3);
+        let only = HashSet::from(["writing/one/index.html".to_string()]);
+        assert_eq!(render_pages(&tera, &config, &sections, &[], built_at(), &disk, Some(&only)).unwrap(), 1);
+        assert_eq!(copy_static_assets(static_dir.path(), &disk, false, false).unwrap().1, 1);
+        assert_eq!(snapshot_output(output.path()).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_duplicate_section_indexes_are_reported() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        write_file(first.path(), "Notes/_index.md", "+++\ntitle = \"Upper\"\n+++\n");
+        write_file(first.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(second.path(), "notes/_index.markdown", "+++\ntitle = \"Lower\"\n+++\n");
+        // Same relative path in a later directory is an override, not a second definition
+        write_file(second.path(), "writing/_index.md", "+++\ntitle = \"Writing Again\"\n+++\n");
+
+        let dirs = [first.path().to_path_buf(), second.path().to_path_buf()];
+        let files = content_files(&dirs, &test_config().markdown_extensions).unwrap();
+        let warnings = duplicate_section_indexes(&files);
+        let upper = first.path().join("Notes/_index.md").display().to_string();
+        let lower = second.path().join("notes/_index.markdown").display().to_string();
+        assert_eq!(warnings, vec![Warning::DuplicateSectionIndex {
+            section: "notes".to_string(),
+            sources: vec![upper.clone(), lower.clone()],
+        }]);
+        let message = warnings[0].to_string();
+        assert!(message.contains(&format!("({}, {})", upper, lower)), "{}", message);
+
+        let (_, sections, _) = load_content(&dirs, &test_config(), &Tera::default()).unwrap();
+        assert_eq!(sections["notes"].meta.title.as_deref(), Some("Lower"));
+    }
+
+    #[test]
+    fn test_markdown_extension_variants_are_loaded() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.markdown", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/post.markdown", "+++\ntitle = \"Long\"\n+++\nBody");
+        write_file(content.path(), "notes/post.md", "+++\ntitle = \"Short\"\n+++\nBody");
+        write_file(content.path(), "notes/other.MDOWN", "+++\ntitle = \"Other\"\n+++\nBody");
+        write_file(content.path(), "notes/readme.txt", "not markdown");
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        assert_eq!(sections["writing"].meta.title.as_deref(), Some("Writing"));
+        let long = &sections["writing"].pages[0];
+        let short = sections["notes"].pages.iter().find(|p| p.title == "Short").unwrap();
+        assert_eq!((long.slug.as_str(), short.slug.as_str()), ("post", "post"));
+        assert_eq!(long.permalink, "https://example.com/writing/post/");
+        assert_eq!(sections["notes"].pages.len(), 2);
+
+        let output = tempfile::tempdir().unwrap();
+
+        let disk = Output::disk(output.path());
+        let copied =
+            copy_content_assets(&[content.path().to_path_buf()], &default_markdown_extensions(), &disk).unwrap();
+        assert_eq!(copied, 1);
+        assert!(output.path().join("notes/readme.txt").is_file());
+    }
+
+    #[test]
+    fn test_images_get_lazy_loading_and_dimensions() {
+        let html = markdown_to_html("![A *cat*](cat.png \"Tabby\")", &MarkdownSettings::default());
+        assert_eq!(
+            html.trim(),
+            r#"<p><img src="cat.png" alt="A cat" title="Tabby" loading="lazy" decoding="async" /></p>"#
+        );
+
+        let off = MarkdownSettings {
+            images: ImagesConfig { lazy_loading: false, dimensions: false },
+            ..MarkdownSettings::default()
+        };
+        assert!(!markdown_to_html("![Cat](cat.png)", &off).contains("loading"));
+
+        let content = tempfile::tempdir().unwrap();
+        // PNG signature and IHDR header for a 640x480 image
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0]);
+        fs::create_dir_all(content.path().join("writing")).unwrap();
+        fs::write(content.path().join("writing/cat.png"), png).unwrap();
+        write_file(content.path(), "writing/post.md", "+++\ntitle = \"Post\"\n+++\n![Cat](cat.png) ![Gone](missing.png)");
+
+        let mut config: Config = toml::from_str(
+            "base_url = \"https://example.com\"\ntitle = \"T\"\ndescription = \"\"\n[images]\ndimensions = true\n",
+        )
+        .unwrap();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let html = &sections["writing"].pages[0].content;
+        assert!(html.contains(r#"<img src="cat.png" alt="Cat" width="640" height="480" loading="lazy""#), "{}", html);
+        assert!(html.contains(r#"<img src="missing.png" alt="Gone" loading="lazy""#), "{}", html);
+
+        // Attributes survive the sanitizer and compose with absolute image URLs
+        config.sanitize_html = true;
+        config.absolute_image_urls = true;
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let html = &sections["writing"].pages[0].content;
+        assert!(html.contains(r#"src="https://example.com/writing/cat.png""#), "{}", html);
+        assert!(html.contains(r#"width="640""#) && html.contains(r#"loading="lazy""#), "{}", html);
+    }
+
+    #[test]
+    fn test_relative_images_become_absolute_urls() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "Writing/post.md",
+            "+++\ntitle = \"Post\"\n+++\n![Diagram](diagram.png) ![Nested](./img/a%20b.png) ![Up](../shared/logo.svg)\n\n\
+             ![Root](/static/x.png) ![External](https://cdn.example.org/y.png) ![Inline](data:image/png;base64,AAAA)",
+        );
+
+        let mut config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        assert!(sections["writing"].pages[0].content.contains(r#"src="diagram.png""#));
+
+        config.absolute_image_urls = true;
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let html = &sections["writing"].pages[0].content;
+        for src in [
+            "https://example.com/writing/diagram.png",
+            "https://example.com/writing/img/a%20b.png",
+            "https://example.com/shared/logo.svg",
+            "/static/x.png",
+            "https://cdn.example.org/y.png",
+            "data:image/png;base64,AAAA",
+        ] {
+            assert!(html.contains(&format!("src=\"{}\"", src)), "{} missing from {}", src, html);
+        }
+    }
+
+    #[test]
+    fn test_content_assets_are_copied_next_to_pages() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/post.md", "+++\ntitle = \"Post\"\n+++\n![Diagram](image.png)");
+        write_file(content.path(), "writing/image.png", "png");
+        write_file(content.path(), "writing/.DS_Store", "junk");
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+
+        let extensions = default_markdown_extensions();
+        assert_eq!(copy_content_assets(&[content.path().to_path_buf()], &extensions, &disk).unwrap(), 1);
+        assert_eq!(fs::read_to_string(output.path().join("writing/image.png")).unwrap(), "png");
+        assert!(!output.path().join("writing/post.md").exists());
+        assert!(!output.path().join("writing/.DS_Store").exists());
+    }
+
+    #[test]
+    fn test_strict_mode_fails_on_duplicate_slugs() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "notes/_index.md", "+++\ntitle = \"Notes\"\n+++\n");
+        write_file(content.path(), "writing/setup.md", "+++\ntitle = \"Setup\"\ndate = 2024-01-01\n+++\n");
+        write_file(content.path(), "notes/setup.md", "+++\ntitle = \"Setup\"\ndate = 2024-01-02\n+++\n");
+        let (_, sections, _) =
+            load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+
+        let warnings = validate_content(&sections);
+        let source = |rel: &str| content.path().join(rel).display().to_string();
+        assert_eq!(
+            warnings,
+            vec![Warning::DuplicateSlug {
+                slug: "setup".to_string(),
+                sources: vec![source("notes/setup.md"), source("writing/setup.md")],
+            }]
+        );
+        assert!(report_content_warnings(&warnings, false).is_ok());
+        let err = report_content_warnings(&warnings, true).unwrap_err().to_string();
+        assert!(err.contains("1 content warning(s)"), "{}", err);
+        assert!(err.contains("Duplicate slug 'setup' in 2 files"), "{}", err);
+    }
+
+    #[test]
+    fn test_duplicate_slugs_are_sorted_by_slug_and_file() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/setup.md", "+++\ntitle = \"Setup\"\n+++\n");
+        write_file(content.path(), "projects/setup.md", "+++\ntitle = \"Setup\"\n+++\n");
+        write_file(content.path(), "notes/setup.md", "+++\ntitle = \"Setup\"\n+++\n");
+        write_file(content.path(), "writing/about.md", "+++\ntitle = \"About\"\n+++\n");
+        write_file(content.path(), "notes/about-me.md", "+++\ntitle = \"About\"\nslug = \"about\"\n+++\n");
+        write_file(content.path(), "notes/unique.md", "+++\ntitle = \"Unique\"\n+++\n");
+        let (_, mut sections, _) =
+            load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        // The same file reached twice is still only one location
+        let again = sections["notes"].pages[0].clone();
+        sections.get_mut("notes").unwrap().pages.push(again);
+
+        let source = |rel: &str| content.path().join(rel).display().to_string();
+        let expected = vec![
+            ("about".to_string(), vec![source("notes/about-me.md"), source("writing/about.md")]),
+            (
+                "setup".to_string(),
+                vec![source("notes/setup.md"), source("projects/setup.md"), source("writing/setup.md")],
+            ),
+        ];
+        assert_eq!(duplicate_slugs(&sections), expected);
+    }
+
+    #[test]
+    fn test_kept_files_survive_a_clean() {
+        let output = tempfile::tempdir().unwrap();
+        write_file(output.path(), "CNAME", "nathanprice.dev");
+        write_file(output.path(), ".well-known/security.txt", "Contact: me");
+        write_file(output.path(), "writing/old/index.html", "stale");
+
+        let keep = [PathBuf::from("CNAME"), PathBuf::from(".well-known/security.txt"), PathBuf::from(".nojekyll")];
+        clean_output_dir(output.path(), &keep).unwrap();
+
+        assert_eq!(fs::read_to_string(output.path().join("CNAME")).unwrap(), "nathanprice.dev");
+        assert_eq!(fs::read_to_string(output.path().join(".well-known/security.txt")).unwrap(), "Contact: me");
+        assert!(!output.path().join("writing").exists());
+        assert!(!output.path().join(".nojekyll").exists());
+    }
+
+    #[test]
+    fn test_breadcrumbs_for_nested_page() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/rust-notes/post.md", "+++\ntitle = \"Post\"\n+++\nBody");
+
+        let config = test_config();
+        let (root, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let crumb = |title: &str, permalink: &str| Crumb { title: title.to_string(), permalink: permalink.to_string() };
+        let trail = vec![
+            crumb("Test Site", "https://example.com/"),
+            crumb("Writing", "https://example.com/writing/"),
+            crumb("Rust Notes", "https://example.com/writing/rust-notes/"),
+            crumb("Post", "https://example.com/writing/rust-notes/post/"),
+        ];
+
+        assert_eq!(sections["writing/rust-notes"].pages[0].breadcrumbs, trail);
+        let section = section_data("writing/rust-notes", &sections["writing/rust-notes"], &sections, &config);
+        assert_eq!(section.breadcrumbs, trail[..3]);
+        assert_eq!(root.breadcrumbs, trail[..1]);
+    }
+
+    #[test]
+    fn test_load_content_merges_multiple_directories() {
+        let blog = tempfile::tempdir().unwrap();
+        let notes = tempfile::tempdir().unwrap();
+        write_file(blog.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(blog.path(), "writing/first.md", "+++\ntitle = \"First\"\n+++\nBlog");
+        write_file(blog.path(), "writing/shared.md", "+++\ntitle = \"Old\"\n+++\nBlog");
+        write_file(notes.path(), "writing/second.md", "+++\ntitle = \"Second\"\n+++\nNotes");
+        write_file(notes.path(), "writing/shared.md", "+++\ntitle = \"New\"\n+++\nNotes");
+        write_file(notes.path(), "notes/idea.md", "+++\ntitle = \"Idea\"\n+++\nNotes");
+
+        let dirs = vec![blog.path().to_path_buf(), notes.path().to_path_buf()];
+        let (_, sections, _) = load_content(&dirs, &test_config(), &Tera::default()).unwrap();
+
+        let mut writing: Vec<&str> = sections["writing"].pages.iter().map(|p| p.title.as_str()).collect();
+        writing.sort();
+        assert_eq!(writing, vec!["First", "New", "Second"]);
+        assert_eq!(sections["writing"].meta.title.as_deref(), Some("Writing"));
+        assert_eq!(sections["notes"].pages.len(), 1);
+    }
+
+    #[test]
+    fn test_later_content_dir_overrides_a_page_with_the_same_slug() {
+        let shared = tempfile::tempdir().unwrap();
+        let site = tempfile::tempdir().unwrap();
+        write_file(shared.path(), "writing/hello-world.md", "+++\ntitle = \"Shared\"\nslug = \"hello\"\n+++\nShared");
+        write_file(shared.path(), "writing/only-shared.md", "+++\ntitle = \"Only Shared\"\n+++\nShared");
+        write_file(site.path(), "writing/hello.md", "+++\ntitle = \"Local\"\n+++\nLocal");
+
+        let config: Config = toml::from_str(&format!(
+            "base_url = \"https://example.com\"\ntitle = \"T\"\ndescription = \"\"\ncontent_dirs = [{:?}, {:?}]\n",
+            shared.path(),
+            site.path()
+        ))
+        .unwrap();
+        assert_eq!(config.content, vec![shared.path().to_path_buf(), site.path().to_path_buf()]);
+
+        let (_, sections, warnings) = load_content(&config.content, &config, &Tera::default()).unwrap();
+        let mut titles: Vec<&str> = sections["writing"].pages.iter().map(|p| p.title.as_str()).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["Local", "Only Shared"]);
+        assert_eq!(warnings, vec![Warning::ContentOverride {
+            source: site.path().join("writing/hello.md").display().to_string(),
+            previous: shared.path().join("writing/hello-world.md").display().to_string(),
+            output: Some("writing/hello/index.html".to_string()),
+        }]);
+        check_output_collisions(&Tera::default(), &sections, &config, None).unwrap();
+    }
+
+    #[test]
+    fn test_build_diff_reports_removed_page() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(content.path(), "writing/keep.md", "+++\ntitle = \"Keep\"\n+++\nKeep");
+        write_file(content.path(), "writing/drop.md", "+++\ntitle = \"Drop\"\n+++\nDrop");
+
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{{ page.title }}").unwrap();
+        let config = test_config();
+        let dirs = vec![content.path().to_path_buf()];
+
+        let (_, sections, _) = load_content(&dirs, &config, &Tera::default()).unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+        let before = snapshot_output(output.path()).unwrap();
+
+        fs::remove_file(content.path().join("writing/drop.md")).unwrap();
+        fs::remove_dir_all(output.path()).unwrap();
+        let (_, sections, _) = load_content(&dirs, &config, &Tera::default()).unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+        let after = snapshot_output(output.path()).unwrap();
+
+        let diff = diff_output_manifests(&before, &after);
+        assert_eq!(diff.removed, vec!["writing/drop/index.html"]);
+        assert!(diff.added.is_empty());
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_manifests_are_kept_per_output_directory() {
+        let site = tempfile::tempdir().unwrap();
+        write_file(site.path(), "site.toml", "base_url = \"https://example.com\"\ntitle = \"Test Site\"\ndescription = \"\"\n");
+        write_file(site.path(), "content/writing/one.md", "+++\ntitle = \"One\"\n+++\n");
+        for (name, body) in [
+            ("index.html", "home"),
+            ("section.html", "{{ section.title }}"),
+            ("page.html", "{{ page.title }}"),
+            ("404.html", "missing"),
+        ] {
+            write_file(site.path(), &format!("templates/{}", name), body);
+        }
+        fs::create_dir_all(site.path().join("static")).unwrap();
+
+        let path = |rel: &str| site.path().join(rel).to_str().unwrap().to_string();
+        let options = |output_dir: &str| {
+            let args = [
+                "site", "--diff", "--config", &path("site.toml"), "--content-dir", &path("content"),
+                "--output-dir", &path(output_dir), "--templates", &path("templates"), "--static-dir", &path("static"),
+            ];
+            Cli::try_parse_from(args).unwrap().options
+        };
+        let manifest = |output_dir: &str| {
+            let path = options(output_dir).paths.cache_dir().join(OUTPUT_MANIFEST_FILE);
+            load_output_manifest(&path).unwrap().unwrap()
+        };
+
+        build_site(&options("a")).unwrap();
+        write_file(site.path(), "content/writing/two.md", "+++\ntitle = \"Two\"\n+++\n");
+        build_site(&options("b")).unwrap();
+
+        // Building into b neither reads nor replaces a's manifest
+        assert!(options("a").paths.cache_dir().starts_with(site.path().join(CACHE_DIR)));
+        assert_ne!(options("a").paths.cache_dir(), options("b").paths.cache_dir());
+        assert!(!manifest("a").contains_key("writing/two/index.html"));
+        assert!(manifest("b").contains_key("writing/two/index.html"));
+    }
+
+    #[test]
+    fn test_incremental_builds_into_different_outputs_dont_share_a_manifest() {
+        let site = tempfile::tempdir().unwrap();
+        write_file(site.path(), "site.toml", "base_url = \"https://example.com\"\ntitle = \"Test Site\"\ndescription = \"\"\n");
+        write_file(site.path(), "content/writing/post.md", "+++\ntitle = \"Post\"\n+++\nFirst");
+        for (name, body) in [
+            ("index.html", "home"),
+            ("section.html", "{{ section.title }}"),
+            ("page.html", "{{ page.content | safe }}"),
+            ("404.html", "missing"),
+        ] {
+            write_file(site.path(), &format!("templates/{}", name), body);
+        }
+        fs::create_dir_all(site.path().join("static")).unwrap();
+
+        let path = |rel: &str| site.path().join(rel).to_str().unwrap().to_string();
+        let options = |output_dir: &str| {
+            let args = [
+                "site", "--incremental", "--config", &path("site.toml"), "--content-dir", &path("content"),
+                "--output-dir", &path(output_dir), "--templates", &path("templates"), "--static-dir", &path("static"),
+            ];
+            Cli::try_parse_from(args).unwrap().options
+        };
+        let post = |output_dir: &str| fs::read_to_string(site.path().join(output_dir).join("writing/post/index.html"));
+
+        build_site(&options("b")).unwrap();
+        write_file(site.path(), "content/writing/post.md", "+++\ntitle = \"Post\"\n+++\nSecond");
+        build_site(&options("a")).unwrap();
+        // b was last built from the first version, whatever a has seen since
+        build_site(&options("b")).unwrap();
+        assert_eq!(post("a").unwrap(), "<p>Second</p>\n");
+        assert_eq!(post("b").unwrap(), "<p>Second</p>\n");
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(b"hello"), content_hash(b"hello"));
+        assert_ne!(content_hash(b"hello"), content_hash(b"hello!"));
+        assert_eq!(content_hash(b""), "cbf29ce484222325");
+    }
+
+    #[test]
+    fn test_markdown_templating_renders_config_values() {
+        let config = test_config();
+        let body = "Welcome to {{ config.title }}!\n\n```\n{{ not_evaluated }}\n```\n\nInline `{{ kept }}` code.\n";
+
+        let rendered = render_markdown_template(body, &FrontMatter::default(), &config).unwrap();
+
+        assert!(rendered.contains("Welcome to Test Site!"));
+        assert!(rendered.contains("```\n{{ not_evaluated }}\n```"));
+        assert!(rendered.contains("`{{ kept }}`"));
+    }
+
+    #[test]
+    fn test_markdown_segments_split_code_from_text() {
+        let segments = markdown_segments("a `b` c\n~~~\nd\n~~~\ne");
+
+        assert_eq!(segments, vec![
+            MarkdownSegment::Text("a "),
+            MarkdownSegment::Code("`b`"),
+            MarkdownSegment::Text(" c\n"),
+            MarkdownSegment::Code("~~~\nd\n~~~\n"),
+            MarkdownSegment::Text("e"),
+        ]);
+    }
+
+    fn sort_fixture_page(slug: &str, weight: Option<i64>, date: Option<(i32, u32, u32)>) -> PageData {
+        PageData {
+            title: slug.to_string(),
+            slug: slug.to_string(),
+            weight,
+            date: date.map(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d).unwrap()),
+            ..PageData::default()
+        }
+    }
+
+    #[test]
+    fn test_multi_key_sort_weight_then_date_desc() {
+        let mut pages = vec![
+            sort_fixture_page("unweighted", None, Some((2025, 6, 1))),
+            sort_fixture_page("heavy-old", Some(2), Some((2024, 1, 1))),
+            sort_fixture_page("light-old", Some(1), Some((2024, 1, 1))),
+            sort_fixture_page("light-new", Some(1), Some((2025, 1, 1))),
+            sort_fixture_page("light-undated", Some(1), None),
+        ];
+
+        let fields = parse_sort_spec("weight,date:desc").unwrap();
+        sort_pages(&mut pages, &fields);
+
+        let order: Vec<&str> = pages.iter().map(|p| p.slug.as_str()).collect();
+        assert_eq!(order, vec!["light-new", "light-old", "light-undated", "heavy-old", "unweighted"]);
+    }
+
+    #[test]
+    fn test_parse_sort_spec_rejects_unknown_keys() {
+        assert_eq!(parse_sort_spec("date").unwrap(), vec![SortField { key: SortKey::Date, descending: true }]);
+        assert_eq!(
+            parse_sort_spec("title:desc, slug").unwrap(),
+            vec![
+                SortField { key: SortKey::Title, descending: true },
+                SortField { key: SortKey::Slug, descending: false },
+            ]
+        );
+        let err = parse_sort_spec("weight,popularity").unwrap_err();
+        assert!(err.to_string().contains("unknown sort key 'popularity'"));
+        assert!(parse_sort_spec("date:sideways").is_err());
+    }
+
+    #[test]
+    fn test_front_matter_iso_date() {
+        let (fm, _) = parse_front_matter("+++\ndate = 2025-01-15\n+++\n").unwrap();
+        assert_eq!(fm.date, Some(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()));
+
+        let (fm, _) = parse_front_matter("+++\ndate = \"2025-01-15\"\n+++\n").unwrap();
+        assert_eq!(fm.date, Some(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()));
+    }
+
+    #[test]
+    fn test_front_matter_loose_dates_coerce() {
+        let expected = Some(NaiveDate::from_ymd_opt(2025, 1, 5).unwrap());
+        for input in [
+            "date = 2025-1-5",
+            "date = 2025-1-5 # moved from the old blog",
+            "date = \"2025-1-5\"",
+            "date = \"Jan 5 2025\"",
+            "date = \"January 5, 2025\"",
+        ] {
+            let (fm, _) = parse_front_matter(&format!("+++\n{}\n+++\n", input)).unwrap();
+            assert_eq!(fm.date, expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_front_matter_unparseable_date_names_value() {
+        let err = parse_front_matter("+++\ndate = \"sometime soon\"\n+++\n").unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("invalid date 'sometime soon'"), "{}", message);
+    }
+
+    #[test]
+    fn test_front_matter_date_accepts_datetimes() {
+        let published = |input: &str| {
+            let (fm, _) = parse_front_matter(input).unwrap();
+            (fm.date, fm.published.map(|published| published.to_utc().to_rfc3339()))
+        };
+        let day = NaiveDate::from_ymd_opt(2025, 3, 1);
+
+        assert_eq!(published("+++\ndate = 2025-03-01\n+++\n"), (day, Some("2025-03-01T00:00:00+00:00".to_string())));
+        assert_eq!(
+            published("+++\ndate = 2025-03-01T09:30:00+02:00\n+++\n"),
+            (day, Some("2025-03-01T07:30:00+00:00".to_string()))
+        );
+        assert_eq!(published("+++\ndate = 2025-03-01 18:45\n+++\n"), (day, Some("2025-03-01T18:45:00+00:00".to_string())));
+        assert_eq!(
+            published("---\ndate: 2025-03-01T09:30:00Z\n---\n"),
+            (day, Some("2025-03-01T09:30:00+00:00".to_string()))
+        );
+        assert_eq!(published("+++\ntitle = \"Undated\"\n+++\n"), (None, None));
+    }
+
+    #[test]
+    fn test_same_day_pages_sort_by_time() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/morning.md", "+++\ntitle = \"Morning\"\ndate = 2025-03-01T09:00:00Z\n+++\n");
+        write_file(content.path(), "writing/evening.md", "+++\ntitle = \"Evening\"\ndate = 2025-03-01T18:00:00Z\n+++\n");
+        write_file(content.path(), "writing/day.md", "+++\ntitle = \"Day\"\ndate = 2025-03-01\n+++\n");
+        write_file(content.path(), "writing/older.md", "+++\ntitle = \"Older\"\ndate = 2025-02-28T23:00:00Z\n+++\n");
+
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+        let pages = &sections["writing"].pages;
+        let titles: Vec<&str> = pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Evening", "Morning", "Day", "Older"]);
+        assert!(pages.iter().take(3).all(|p| p.date == NaiveDate::from_ymd_opt(2025, 3, 1)));
+
+        let feed = feed_pages(&test_config(), &sections);
+        let rss = build_rss_feed(&test_config(), &FeedChannel::site(&test_config(), RSS_FEED_PATH), &feed);
+        assert!(rss.contains("<pubDate>Sat, 1 Mar 2025 18:00:00 +0000</pubDate>"), "{}", rss);
+        assert!(rss.contains("<pubDate>Sat, 1 Mar 2025 00:00:00 +0000</pubDate>"), "{}", rss);
+
+        let mut same_day = pages[0].clone();
+        same_day.updated = NaiveDate::from_ymd_opt(2025, 3, 1);
+        assert_eq!(same_day.last_modified_at().unwrap().to_rfc3339(), "2025-03-01T18:00:00+00:00");
+        same_day.updated = NaiveDate::from_ymd_opt(2025, 3, 2);
+        assert_eq!(same_day.last_modified_at().unwrap().to_rfc3339(), "2025-03-02T00:00:00+00:00");
+
+        let mut context = TeraContext::new();
+        context.insert("page", &pages[0]);
+        let rendered = render_with_filters(
+            r#"{{ page.date | date_format(fmt="%B %-d, %Y") }} {{ page.datetime | date_format(fmt="%H:%M") }}"#,
+            &context,
+        )
+        .unwrap();
+        assert_eq!(rendered, "March 1, 2025 18:00");
+    }
 
-        let mut dest_dir = output_dir.to_path_buf();
-        if !key.is_empty() {
-            dest_dir.push(key);
+    fn related_fixture() -> HashMap<String, SectionContent> {
+        let mut writing = SectionContent {
+            pages: vec![
+                sort_fixture_page("a", None, Some((2025, 3, 1))),
+                sort_fixture_page("b", None, Some((2025, 2, 1))),
+                sort_fixture_page("c", None, Some((2025, 1, 1))),
+                sort_fixture_page("d", None, None),
+            ],
+            ..SectionContent::default()
+        };
+        for page in writing.pages.iter_mut() {
+            page.relative_path = format!("writing/{}/index.html", page.slug);
         }
+        writing.pages[0].related_refs = vec!["c".to_string(), "notes/idea".to_string(), "missing".to_string()];
 
-        let depth = calculate_path_depth(key, false);
-        let path_prefix = path_prefix_for_depth(depth);
+        let mut notes = SectionContent {
+            pages: vec![sort_fixture_page("idea", None, None)],
+            ..SectionContent::default()
+        };
+        notes.pages[0].relative_path = "notes/idea/index.html".to_string();
 
-        if template == "page.html" {
-            let page = PageData {
-                title: section_content
-                    .meta
-                    .title
-                    .clone()
-                    .unwrap_or_else(|| key.clone()),
-                date: section_content.meta.date,
-                summary: section_content.meta.summary.clone(),
-                content: section_content.body_html.clone(),
-                permalink: format!("{}/{}/", config.base_url, key),
-                relative_path: format!("{}/index.html", key),
-                template: section_content.meta.template.clone(),
-                slug: key.clone(),
-            };
+        HashMap::from([("writing".to_string(), writing), ("notes".to_string(), notes)])
+    }
 
-            let mut context = build_base_context(config, &path_prefix);
-            context.insert("page", &page);
+    fn related_slugs(sections: &HashMap<String, SectionContent>, section: &str, index: usize) -> Vec<String> {
+        sections[section].pages[index]
+            .related
+            .iter()
+            .map(|link| link.title.clone())
+            .collect()
+    }
 
-            render_template_to_file(
-                tera,
-                &template,
-                &context,
-                &dest_dir.join("index.html"),
-                &format!("section page {}", key),
-            )?;
-            continue;
-        }
+    #[test]
+    fn test_related_by_section_picks_recent_siblings() {
+        let mut sections = related_fixture();
+        compute_related(&mut sections, RelatedBy::Section, 2);
+
+        assert_eq!(related_slugs(&sections, "writing", 0), vec!["b", "c"]);
+        assert_eq!(related_slugs(&sections, "writing", 3), vec!["a", "b"]);
+        assert!(related_slugs(&sections, "notes", 0).is_empty());
+    }
+
+    #[test]
+    fn test_related_by_explicit_resolves_references() {
+        let mut sections = related_fixture();
+        let warnings = compute_related(&mut sections, RelatedBy::Explicit, 1);
+
+        // Explicit lists keep their order, skip unknown entries, and aren't capped
+        assert_eq!(related_slugs(&sections, "writing", 0), vec!["c", "idea"]);
+        assert!(related_slugs(&sections, "writing", 1).is_empty());
+        assert_eq!(warnings, vec![Warning::UnknownRelatedPage {
+            page: "writing/a/index.html".to_string(),
+            reference: "missing".to_string(),
+        }]);
+    }
 
-        let section = SectionData {
-            title: section_content
-                .meta
-                .title
-                .clone()
-                .unwrap_or_else(|| key.clone()),
-            description: section_content.meta.description.clone(),
-            pages: section_content.pages.clone(),
-            content: section_content.body_html.clone(),
+    #[test]
+    fn test_related_by_tags_ranks_shared_tags_then_recency() {
+        let content = tempfile::tempdir().unwrap();
+        let post = |title: &str, date: &str, tags: &str| {
+            format!("+++\ntitle = \"{}\"\ndate = {}\ntags = [{}]\n+++\n", title, date, tags)
         };
+        write_file(content.path(), "writing/main.md", &post("Main", "2025-01-01", r#""rust", "web", "cli""#));
+        write_file(content.path(), "writing/two.md", &post("Two", "2024-01-01", r#""Rust", "cli""#));
+        write_file(content.path(), "writing/old.md", &post("Old", "2023-01-01", r#""web""#));
+        write_file(content.path(), "writing/new.md", &post("New", "2024-06-01", r#""rust""#));
+        write_file(content.path(), "notes/cli.md", &post("Note", "2024-03-01", r#""cli", "misc""#));
+        write_file(content.path(), "writing/other.md", &post("Other", "2025-02-01", r#""go""#));
+        write_file(content.path(), "writing/untagged.md", "+++\ntitle = \"Untagged\"\n+++\n");
 
-        let mut context = build_base_context(config, &path_prefix);
-        context.insert("section", &section);
+        let mut config = test_config();
+        config.related_by = Some(RelatedBy::Tags);
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let related = |section: &str, title: &str| -> Vec<String> {
+            let page = sections[section].pages.iter().find(|p| p.title == title).unwrap();
+            page.related.iter().map(|link| link.title.clone()).collect()
+        };
 
-        render_template_to_file(
-            tera,
-            &template,
-            &context,
-            &dest_dir.join("index.html"),
-            &format!("section {}", key),
-        )?;
+        assert_eq!(related("writing", "Main"), vec!["Two", "New", "Note"]);
+        assert_eq!(related("notes", "Note"), vec!["Main", "Two"]);
+        assert!(related("writing", "Other").is_empty());
+        assert!(related("writing", "Untagged").is_empty());
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_unlisted_page_renders_but_is_not_listed() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/public.md", "+++\ntitle = \"Public\"\n+++\nBody");
+        write_file(content.path(), "writing/hidden.md", "+++\ntitle = \"Hidden\"\nvisibility = \"unlisted\"\n+++\nBody");
+        write_file(content.path(), "writing/wip.md", "+++\ntitle = \"WIP\"\nvisibility = \"draft\"\n+++\nBody");
 
-fn render_pages(
-    tera: &Tera,
-    config: &Config,
-    sections: &HashMap<String, SectionContent>,
-    output_dir: &Path,
-) -> Result<()> {
-    for (key, section) in sections.iter() {
-        for page in &section.pages {
-            let page_template = page
-                .template
-                .clone()
-                .or_else(|| section.meta.template.clone())
-                .unwrap_or_else(|| "page.html".to_string());
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{{ page.title }}").unwrap();
+        tera.add_raw_template(
+            "section.html",
+            "{% for page in section.pages %}[{{ page.title }}]{% endfor %}",
+        )
+        .unwrap();
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        render_sections(&tera, &config, &sections, &[], built_at(), &disk).unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+
+        let listing = fs::read_to_string(output.path().join("writing/index.html")).unwrap();
+        assert_eq!(listing, "[Public]");
+        assert!(output.path().join("writing/hidden/index.html").exists());
+        assert!(!output.path().join("writing/wip/index.html").exists());
+    }
 
-            let depth = calculate_path_depth(key, true);
-            let path_prefix = path_prefix_for_depth(depth);
+    #[test]
+    fn test_parse_front_matter_yaml() {
+        let input = "---\ntitle: \"YAML Post\"\ndate: 2025-03-01\n---\nYAML body";
 
-            let mut context = build_base_context(config, &path_prefix);
-            context.insert("page", page);
+        let (fm, body) = parse_front_matter(input).unwrap();
 
-            let mut dest_dir = output_dir.to_path_buf();
-            if !key.is_empty() {
-                dest_dir.push(key);
-            }
-            dest_dir.push(&page.slug);
+        assert_eq!(fm.title, Some("YAML Post".to_string()));
+        assert_eq!(fm.date, Some(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()));
+        assert_eq!(body.trim(), "YAML body");
+    }
 
-            render_template_to_file(
-                tera,
-                &page_template,
-                &context,
-                &dest_dir.join("index.html"),
-                &format!("page {}", page.title),
-            )?;
+    #[test]
+    fn test_front_matter_extra_keys_reach_templates() {
+        let content = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        write_file(
+            content.path(),
+            "writing/post.md",
+            "+++\ntitle = \"Post\"\ndate = 2025-03-01\nfoo = \"bar\"\n\n[extra]\nmood = \"sunny\"\n+++\nBody",
+        );
+
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{{ page.extra.foo }} {{ page.extra.mood }}").unwrap();
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+
+        let rendered = fs::read_to_string(output.path().join("writing/post/index.html")).unwrap();
+        assert_eq!(rendered, "bar sunny");
+        let page = &sections["writing"].pages[0];
+        assert_eq!(page.date, NaiveDate::from_ymd_opt(2025, 3, 1));
+        assert!(!page.extra.contains_key("date"));
+    }
+
+    #[test]
+    fn test_parse_front_matter_yaml_extra_skips_nulls() {
+        let (fm, _) = parse_front_matter("---\ntitle: Post\nfoo: bar\nempty: ~\n---\nBody").unwrap();
+        assert_eq!(fm.extra.get("foo").and_then(toml::Value::as_str), Some("bar"));
+        assert!(!fm.extra.contains_key("empty"));
+        assert!(!fm.extra.contains_key("title"));
+    }
+
+    #[test]
+    fn test_unknown_front_matter_keys_suggest_known_ones() {
+        let (fm, _) =
+            parse_front_matter("+++\ntitel = \"Typo\"\nfoo = \"bar\"\n\n[extra]\nmood = \"sunny\"\n+++\nBody").unwrap();
+        assert_eq!(unknown_front_matter_keys(&fm), vec![("foo", None), ("titel", Some("title"))]);
+
+        let (fm, _) = parse_front_matter("+++\ntitle = \"Fine\"\n\n[extra]\ntitel = \"custom\"\n+++\n").unwrap();
+        assert!(unknown_front_matter_keys(&fm).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_front_matter_key_warns_unless_strict() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/post.md", "+++\ntitel = \"Typo\"\n+++\nBody");
+
+        let mut config = test_config();
+        let (_, sections, loaded) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        assert_eq!(sections["writing"].pages[0].title, "Post");
+        assert_eq!(loaded.len(), 1);
+        assert!(matches!(&loaded[0], Warning::UnknownFrontMatterKey { key, .. } if key == "titel"));
+
+        // Typos are warned about; other unknown keys pass quietly
+        let path = content.path().join("writing/post.md");
+        let (meta, _) = parse_front_matter("+++\ntitel = \"Typo\"\ncolour = \"red\"\n+++\nBody").unwrap();
+        let warnings = check_front_matter_keys(&meta, &path, false).unwrap();
+        assert_eq!(warnings, vec![Warning::UnknownFrontMatterKey {
+            source: path.display().to_string(),
+            key: "titel".to_string(),
+            suggestion: "title".to_string(),
+        }]);
+        assert!(warnings[0].to_string().contains("Unknown front matter key `titel`"), "{}", warnings[0]);
+
+        config.strict_front_matter = true;
+        let err = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("`titel` (did you mean `title`?)"), "{}", message);
+    }
+
+    #[test]
+    fn test_parse_front_matter_yaml_error_has_context() {
+        let err = parse_front_matter("---\ntitle: [unclosed\n---\nBody").unwrap_err();
+        assert!(format!("{:#}", err).contains("parsing frontmatter YAML"));
+    }
+
+    #[test]
+    fn test_nested_sections_link_subsections() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/post.md", "+++\ntitle = \"Post\"\n+++\n");
+        // Section with an index but no pages
+        write_file(content.path(), "writing/rust/_index.md", "+++\ntitle = \"Rust\"\n+++\n");
+        // Three levels deep with no _index.md in writing/deep
+        write_file(content.path(), "writing/deep/er/post.md", "+++\ntitle = \"Deep\"\n+++\n");
+
+        let (root, sections, _) =
+            load_content(&[content.path().to_path_buf()], &test_config(), &Tera::default()).unwrap();
+
+        assert_eq!(sections["writing"].subsections, vec!["writing/deep", "writing/rust"]);
+        assert_eq!(sections["writing/deep"].subsections, vec!["writing/deep/er"]);
+        assert!(sections["writing/rust"].pages.is_empty());
+        assert!(sections["writing/rust"].subsections.is_empty());
+        assert_eq!(root.subsections.len(), 1);
+        assert_eq!(root.subsections[0].title, "Writing");
+        assert_eq!(root.subsections[0].permalink, "https://example.com/writing/");
+    }
+
+    #[test]
+    fn test_rss_feed_items_and_absolute_links() {
+        let mut config = test_config();
+        config.feed_limit = 2;
+        let pages: Vec<PageData> = ["c", "b", "a"]
+            .iter()
+            .map(|slug| PageData {
+                title: format!("Post {}", slug),
+                permalink: format!("https://example.com/writing/{}/", slug),
+                content: "<p>Body &amp; more</p>".to_string(),
+                date: NaiveDate::from_ymd_opt(2025, 1, 15),
+                ..PageData::default()
+            })
+            .collect();
+        let sections = HashMap::from([(
+            FEED_SECTION.to_string(),
+            SectionContent { pages, ..SectionContent::default() },
+        )]);
+
+        let xml = build_rss_feed(&config, &FeedChannel::site(&config, RSS_FEED_PATH), &feed_pages(&config, &sections));
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        let items: Vec<_> = doc.descendants().filter(|n| n.has_tag_name("item")).collect();
+
+        assert_eq!(items.len(), 2);
+        for item in &items {
+            let link = item.children().find(|n| n.has_tag_name("link")).unwrap().text().unwrap();
+            assert!(link.starts_with("https://example.com/"), "{}", link);
+            let description = item.children().find(|n| n.has_tag_name("description")).unwrap();
+            assert_eq!(description.text(), Some("Body & more"));
         }
+        assert!(xml.contains("<pubDate>Wed, 15 Jan 2025 00:00:00 +0000</pubDate>"));
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_atom_feed_root_and_entries() {
+        let mut config = test_config();
+        config.feed_limit = 2;
+        let pages: Vec<PageData> = [("c", 20), ("b", 10), ("a", 5)]
+            .iter()
+            .map(|(slug, day)| PageData {
+                title: format!("Post {}", slug),
+                permalink: format!("https://example.com/writing/{}/", slug),
+                content: "<p>Body</p>".to_string(),
+                date: NaiveDate::from_ymd_opt(2025, 1, *day),
+                ..PageData::default()
+            })
+            .collect();
+        let sections = HashMap::from([(
+            FEED_SECTION.to_string(),
+            SectionContent { pages, ..SectionContent::default() },
+        )]);
 
-fn render_404(tera: &Tera, config: &Config, output_dir: &Path) -> Result<()> {
-    let context = build_base_context(config, "");
+        let channel = FeedChannel::site(&config, ATOM_FEED_PATH);
+        let xml = build_atom_feed(&config, &channel, &feed_pages(&config, &sections));
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        let root = doc.root_element();
+        assert!(root.has_tag_name(("http://www.w3.org/2005/Atom", "feed")));
+        assert_eq!(root.tag_name().namespace(), Some("http://www.w3.org/2005/Atom"));
 
-    render_template_to_file(
-        tera,
-        "404.html",
-        &context,
-        &output_dir.join("404.html"),
-        "404 page",
-    )
-}
+        let entries: Vec<_> = root.children().filter(|n| n.has_tag_name("entry")).collect();
+        assert_eq!(entries.len(), 2);
+        let child_text = |node: roxmltree::Node, name: &str| {
+            node.children().find(|n| n.has_tag_name(name)).and_then(|n| n.text()).map(str::to_string)
+        };
+        assert_eq!(child_text(root, "updated").as_deref(), Some("2025-01-20T00:00:00+00:00"));
+        assert_eq!(child_text(entries[0], "id").as_deref(), Some("https://example.com/writing/c/"));
+        assert_eq!(child_text(entries[1], "updated").as_deref(), Some("2025-01-10T00:00:00+00:00"));
+        assert_eq!(child_text(entries[0], "content").as_deref(), Some("<p>Body</p>"));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // Without dated pages the feed's time is fixed rather than the time of the build
+        let empty = build_atom_feed(&config, &channel, &[]);
+        assert!(empty.contains("<updated>1970-01-01T00:00:00+00:00</updated>"), "{}", empty);
+    }
 
     #[test]
-    fn test_parse_front_matter_with_all_fields() {
-        let input = r#"+++
-title = "Test Post"
-description = "A test"
-date = "2025-01-15"
-summary = "Summary here"
-template = "custom.html"
-sort_by = "date"
-+++
-Content here"#;
+    fn test_json_feed_keys_and_items() {
+        let mut config = test_config();
+        config.feed_limit = 2;
+        let pages: Vec<PageData> = [("c", 20), ("b", 10), ("a", 5)]
+            .iter()
+            .map(|(slug, day)| PageData {
+                title: format!("Post {}", slug),
+                permalink: format!("https://example.com/writing/{}/", slug),
+                content: "<p>Body</p>".to_string(),
+                date: NaiveDate::from_ymd_opt(2025, 1, *day),
+                ..PageData::default()
+            })
+            .collect();
+        let sections = HashMap::from([(
+            FEED_SECTION.to_string(),
+            SectionContent { pages, ..SectionContent::default() },
+        )]);
+        let output = tempfile::tempdir().unwrap();
+        let disk = Output::disk(output.path());
+        render_json_feed(&config, &sections, &disk).unwrap();
 
-        let result = parse_front_matter(input);
-        assert!(result.is_ok());
-        let (fm, body) = result.unwrap();
+        let raw = fs::read_to_string(output.path().join(JSON_FEED_PATH)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        for key in ["version", "title", "home_page_url", "feed_url", "items"] {
+            assert!(value.get(key).is_some(), "missing {}", key);
+        }
+        let feed: JsonFeed = serde_json::from_value(value).unwrap();
+        assert_eq!(feed.version, "https://jsonfeed.org/version/1.1");
+        assert_eq!(feed.feed_url, "https://example.com/feed.json");
+        assert_eq!(feed.items.len(), 2);
+        assert_eq!(feed.items[0].id, "https://example.com/writing/c/");
+        assert_eq!(feed.items[0].content_html, "<p>Body</p>");
+        assert_eq!(feed.items[1].date_published.as_deref(), Some("2025-01-10T00:00:00+00:00"));
+    }
 
-        assert_eq!(fm.title, Some("Test Post".to_string()));
-        assert_eq!(fm.description, Some("A test".to_string()));
-        assert_eq!(fm.date, Some(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()));
-        assert_eq!(fm.summary, Some("Summary here".to_string()));
-        assert_eq!(fm.template, Some("custom.html".to_string()));
-        assert_eq!(body.trim(), "Content here");
+    #[test]
+    fn test_sitemap_covers_pages_sections_and_home() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(content.path(), "writing/one.md", "+++\ndate = \"2025-01-02\"\nsitemap_priority = 0.9\n+++\n");
+        write_file(content.path(), "writing/two.md", "+++\ntitle = \"Two\"\n+++\n");
+        write_file(content.path(), "writing/rust/intro.md", "+++\ntitle = \"Intro\"\n+++\n");
+        write_file(content.path(), "about/_index.md", "+++\ntitle = \"About\"\n+++\n");
+
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let entries = collect_sitemap_entries(&config, &sections);
+
+        let page_count: usize = sections.values().map(|s| s.pages.len()).sum();
+        assert_eq!(entries.len(), page_count + sections.len() + 1);
+
+        let mut locs: Vec<&str> = entries.iter().map(|e| e.loc.as_str()).collect();
+        locs.sort();
+        locs.dedup();
+        assert_eq!(locs.len(), entries.len());
+        assert!(locs.contains(&"https://example.com/writing/"));
+        assert!(locs.contains(&"https://example.com/writing/rust/"));
+
+        let xml = build_sitemap(&entries);
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        assert_eq!(doc.descendants().filter(|n| n.has_tag_name("url")).count(), entries.len());
+        assert!(xml.contains(
+            "<loc>https://example.com/writing/one/</loc>\n    <lastmod>2025-01-02</lastmod>\n    <priority>0.9</priority>"
+        ));
     }
 
     #[test]
-    fn test_parse_front_matter_minimal() {
-        let input = r#"+++
-title = "Minimal"
-+++
-Body"#;
+    fn test_updated_date_drives_lastmod_and_feed_updated() {
+        let content = tempfile::tempdir().unwrap();
+        write_file(
+            content.path(),
+            "writing/revised.md",
+            "+++\ntitle = \"Revised\"\ndate = 2024-01-01\nupdated = 2025-03-01\n+++\n",
+        );
+        write_file(content.path(), "writing/recent.md", "+++\ntitle = \"Recent\"\ndate = 2025-02-01\n+++\n");
+        write_file(content.path(), "writing/legacy.md", "+++\ntitle = \"Legacy\"\ndate = 2023-01-01\nlast_modified = 2023-06-01\n+++\n");
 
-        let result = parse_front_matter(input);
-        assert!(result.is_ok());
-        let (fm, body) = result.unwrap();
+        let config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        // Default sorting still follows the publish date
+        let titles: Vec<&str> = sections["writing"].pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Recent", "Revised", "Legacy"]);
 
-        assert_eq!(fm.title, Some("Minimal".to_string()));
-        assert_eq!(fm.description, None);
-        assert_eq!(body.trim(), "Body");
+        let xml = build_sitemap(&collect_sitemap_entries(&config, &sections));
+        assert!(xml.contains("<loc>https://example.com/writing/revised/</loc>\n    <lastmod>2025-03-01</lastmod>"), "{}", xml);
+        assert!(xml.contains("<loc>https://example.com/writing/recent/</loc>\n    <lastmod>2025-02-01</lastmod>"), "{}", xml);
+        assert!(xml.contains("<loc>https://example.com/writing/legacy/</loc>\n    <lastmod>2023-06-01</lastmod>"), "{}", xml);
+        assert!(xml.contains("<loc>https://example.com/writing/</loc>\n    <lastmod>2025-03-01</lastmod>"), "{}", xml);
+
+        let channel = FeedChannel::site(&config, ATOM_FEED_PATH);
+        let atom = build_atom_feed(&config, &channel, &feed_pages(&config, &sections));
+        assert!(atom.contains("<updated>2025-03-01T00:00:00+00:00</updated>\n    <published>2024-01-01T00:00:00+00:00</published>"));
+
+        let mut pages = sections["writing"].pages.clone();
+        sort_pages(&mut pages, &parse_sort_spec("updated").unwrap());
+        let titles: Vec<&str> = pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Revised", "Recent", "Legacy"]);
+    }
+
+    fn load_draft_fixture(drafts: bool) -> HashMap<String, SectionContent> {
+        let content = tempfile::tempdir().unwrap();
+        write_file(content.path(), "writing/done.md", "+++\ntitle = \"Done\"\n+++\n");
+        write_file(content.path(), "writing/wip.md", "+++\ntitle = \"WIP\"\ndraft = true\n+++\n");
+        write_file(content.path(), "notes/_index.md", "+++\ntitle = \"Notes\"\ndraft = true\n+++\n");
+        write_file(content.path(), "notes/idea.md", "+++\ntitle = \"Idea\"\n+++\n");
+
+        let mut config = test_config();
+        config.drafts = drafts;
+        load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap().1
     }
 
     #[test]
-    fn test_parse_front_matter_no_frontmatter() {
-        let input = "Just content, no frontmatter";
+    fn test_drafts_hidden_by_default() {
+        let sections = load_draft_fixture(false);
 
-        let result = parse_front_matter(input);
-        assert!(result.is_ok());
-        let (fm, body) = result.unwrap();
+        let titles: Vec<&str> = sections["writing"].pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Done"]);
+        assert!(!sections.contains_key("notes"));
+    }
 
-        assert_eq!(fm.title, None);
-        assert_eq!(body, input);
+    #[test]
+    fn test_drafts_flag_adds_draft_pages_to_the_build() {
+        let site = tempfile::tempdir().unwrap();
+        write_file(site.path(), "site.toml", "base_url = \"https://example.com\"\ntitle = \"Test Site\"\ndescription = \"\"\n");
+        write_file(site.path(), "content/writing/done.md", "+++\ntitle = \"Done\"\n+++\n");
+        write_file(site.path(), "content/writing/wip.md", "+++\ntitle = \"WIP\"\ndraft = true\n+++\n");
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{% if config.extra.drafts_enabled %}DRAFTS {% endif %}{{ page.title }}")
+            .unwrap();
+
+        let build = |flags: &[&str]| {
+            let config_path = site.path().join("site.toml");
+            let content_dir = site.path().join("content");
+            let args = [
+                "nathanprice-site",
+                "--config",
+                config_path.to_str().unwrap(),
+                "--content-dir",
+                content_dir.to_str().unwrap(),
+            ];
+            let cli = Cli::try_parse_from(args.iter().chain(flags)).unwrap();
+            let config = cli.options.load_config().unwrap();
+            let (_, sections, _) = load_content(&config.content, &config, &Tera::default()).unwrap();
+            let output = tempfile::tempdir().unwrap();
+            let disk = Output::disk(output.path());
+            let rendered = render_pages(&tera, &config, &sections, &[], built_at(), &disk, None).unwrap();
+            (rendered, fs::read_to_string(output.path().join("writing/done/index.html")).unwrap())
+        };
+
+        assert_eq!(build(&[]), (1, "Done".to_string()));
+        assert_eq!(build(&["--drafts"]), (2, "DRAFTS Done".to_string()));
     }
 
     #[test]
-    fn test_markdown_to_html_basic() {
-        let md = "# Heading\n\nParagraph with **bold**";
-        let html = markdown_to_html(md);
+    fn test_strict_fails_on_warnings_raised_while_loading() {
+        let site = tempfile::tempdir().unwrap();
+        write_file(site.path(), "site.toml", "base_url = \"https://example.com\"\ntitle = \"Test Site\"\ndescription = \"\"\n");
+        write_file(site.path(), "content/writing/_index.md", "+++\ntitle = \"Writing\"\n+++\n");
+        write_file(
+            site.path(),
+            "content/writing/post.md",
+            "+++\ntitle = \"Post\"\ndate = 2025-01-01\nsitemap_priority = 1.5\n+++\nHello",
+        );
+        for (name, body) in [
+            ("index.html", "home"),
+            ("section.html", "{{ section.title }}"),
+            ("page.html", "{{ page.title }}"),
+            ("404.html", "missing"),
+        ] {
+            write_file(site.path(), &format!("templates/{}", name), body);
+        }
+        fs::create_dir_all(site.path().join("static")).unwrap();
 
-        assert!(html.contains("<h1>"));
-        assert!(html.contains("<strong>"));
-        assert!(html.contains("Heading"));
+        let path = |rel: &str| site.path().join(rel).to_str().unwrap().to_string();
+        let options = |flags: &[&str]| {
+            let args = [
+                "site", "--config", &path("site.toml"), "--content-dir", &path("content"), "--output-dir",
+                &path("public"), "--templates", &path("templates"), "--static-dir", &path("static"),
+            ];
+            Cli::try_parse_from(args.iter().chain(flags)).unwrap().options
+        };
+
+        assert!(!options(&[]).load_config().unwrap().strict_front_matter);
+        assert!(options(&["--strict"]).load_config().unwrap().strict_front_matter);
+        build_site(&options(&[])).unwrap();
+        let error = build_site(&options(&["--strict"])).unwrap_err().to_string();
+        assert!(error.contains("1 content warning(s) with --strict"), "{}", error);
+        assert!(error.contains("Ignoring sitemap_priority 1.5 in"), "{}", error);
+
+        // Site-wide sitemap hints are checked with the content
+        write_file(site.path(), "content/writing/post.md", "+++\ntitle = \"Post\"\ndate = 2025-01-01\n+++\nHello");
+        let site_toml = "base_url = \"https://example.com\"\ntitle = \"Test Site\"\ndescription = \"\"\n";
+        write_file(site.path(), "site.toml", &format!("{}[sitemap]\nchangefreq = \"sometimes\"\n", site_toml));
+        build_site(&options(&[])).unwrap();
+        let error = build_site(&options(&["--strict"])).unwrap_err().to_string();
+        assert!(error.contains("1 content warning(s) with --strict"), "{}", error);
+        let expected = format!("Ignoring sitemap_changefreq 'sometimes' in {}", path("site.toml"));
+        assert!(error.contains(&expected), "{}", error);
+
+        // So are pages that can't follow the permalink pattern
+        let permalink = "[extra]\npermalink = \"/{section}/{year}/{slug}/\"\n";
+        write_file(site.path(), "site.toml", &format!("{}{}", site_toml, permalink));
+        write_file(site.path(), "content/writing/undated.md", "+++\ntitle = \"Undated\"\n+++\n");
+        build_site(&options(&[])).unwrap();
+        let error = build_site(&options(&["--strict"])).unwrap_err().to_string();
+        let expected = "undated.md has no date for permalink pattern '/{section}/{year}/{slug}/'";
+        assert!(error.contains(expected), "{}", error);
+        assert!(error.contains("; using writing/undated/"), "{}", error);
     }
 
     #[test]
-    fn test_path_depth_calculation() {
-        assert_eq!(calculate_path_depth("", false), 0);
-        assert_eq!(calculate_path_depth("", true), 1);
-        assert_eq!(calculate_path_depth("section", false), 1);
-        assert_eq!(calculate_path_depth("section", true), 2);
-        assert_eq!(calculate_path_depth("section/nested", false), 2);
-        assert_eq!(calculate_path_depth("section/nested", true), 3);
+    fn test_keep_files_must_stay_inside_the_output() {
+        let site = tempfile::tempdir().unwrap();
+        let load = |keep_files: &str| {
+            let config = format!("base_url = \"\"\ntitle = \"\"\ndescription = \"\"\nkeep_files = {}\n", keep_files);
+            write_file(site.path(), "site.toml", &config);
+            load_config(&site.path().join("site.toml")).map_err(|error| error.to_string())
+        };
+
+        assert_eq!(load(r#"["CNAME", "./.well-known/security.txt"]"#).unwrap().keep_files.len(), 2);
+        for outside in [r#"["/etc/passwd"]"#, r#"["../secrets.txt"]"#, r#"["CNAME", "assets/../../x"]"#] {
+            let error = load(outside).unwrap_err();
+            assert!(error.contains("must be a path inside the output directory"), "{}", error);
+        }
     }
 
     #[test]
-    fn test_path_prefix_generation() {
-        assert_eq!(path_prefix_for_depth(0), "");
-        assert_eq!(path_prefix_for_depth(1), "../");
-        assert_eq!(path_prefix_for_depth(2), "../../");
-        assert_eq!(path_prefix_for_depth(3), "../../../");
+    fn test_base_url_flag_overrides_permalinks() {
+        let site = tempfile::tempdir().unwrap();
+        write_file(site.path(), "site.toml", "base_url = \"https://example.com\"\ntitle = \"Test Site\"\ndescription = \"\"\n");
+        write_file(site.path(), "content/writing/post.md", "+++\ntitle = \"Post\"\n+++\n");
+
+        let permalink = |flags: &[&str]| {
+            let config_path = site.path().join("site.toml");
+            let content_dir = site.path().join("content");
+            let args = [
+                "nathanprice-site",
+                "--config",
+                config_path.to_str().unwrap(),
+                "--content-dir",
+                content_dir.to_str().unwrap(),
+            ];
+            let cli = Cli::try_parse_from(args.iter().chain(flags)).unwrap();
+            let config = cli.options.load_config().unwrap();
+            let (_, sections, _) = load_content(&config.content, &config, &Tera::default()).unwrap();
+            sections["writing"].pages[0].permalink.clone()
+        };
+
+        assert_eq!(permalink(&[]), "https://example.com/writing/post/");
+        assert_eq!(
+            permalink(&["--base-url", "https://pr-42.preview.example.com/"]),
+            "https://pr-42.preview.example.com/writing/post/"
+        );
+        assert_eq!(permalink(&["--base-url", ""]), "/writing/post/");
     }
 
     #[test]
-    fn test_relative_path_generation_root() {
-        let parent_key = "";
-        let slug = "about";
+    fn test_drafts_included_when_enabled() {
+        let sections = load_draft_fixture(true);
 
-        let relative_path = if parent_key.is_empty() {
-            format!("{}/index.html", slug)
-        } else {
-            format!("{}/{}/index.html", parent_key, slug)
+        let wip = sections["writing"].pages.iter().find(|p| p.title == "WIP").unwrap();
+        assert!(wip.draft);
+        let done = sections["writing"].pages.iter().find(|p| p.title == "Done").unwrap();
+        assert!(!done.draft);
+        assert!(sections["notes"].pages[0].draft);
+    }
+
+    #[test]
+    fn test_sort_by_weight_breaks_ties_by_title() {
+        let mut pages = vec![
+            sort_fixture_page("zeta", Some(1), None),
+            sort_fixture_page("unweighted", None, None),
+            sort_fixture_page("beta", Some(2), None),
+            sort_fixture_page("Alpha", Some(1), None),
+            sort_fixture_page("another-unweighted", None, None),
+        ];
+        sort_pages(&mut pages, &parse_sort_spec("weight").unwrap());
+
+        let order: Vec<&str> = pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(order, vec!["Alpha", "zeta", "beta", "another-unweighted", "unweighted"]);
+    }
+
+    #[test]
+    fn test_sort_by_title() {
+        let mut pages = vec![
+            sort_fixture_page("b", None, None),
+            sort_fixture_page("C", None, None),
+            sort_fixture_page("a", None, None),
+        ];
+        sort_pages(&mut pages, &parse_sort_spec("title").unwrap());
+
+        let order: Vec<&str> = pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(order, vec!["a", "b", "C"]);
+    }
+
+    #[test]
+    fn test_sort_by_date_asc_keeps_undated_last() {
+        let mut pages = vec![
+            sort_fixture_page("undated", None, None),
+            sort_fixture_page("new", None, Some((2025, 6, 1))),
+            sort_fixture_page("old", None, Some((2024, 6, 1))),
+        ];
+        sort_pages(&mut pages, &parse_sort_spec("date_asc").unwrap());
+
+        let order: Vec<&str> = pages.iter().map(|p| p.slug.as_str()).collect();
+        assert_eq!(order, vec!["old", "new", "undated"]);
+
+        sort_pages(&mut pages, &parse_sort_spec("date").unwrap());
+        let order: Vec<&str> = pages.iter().map(|p| p.slug.as_str()).collect();
+        assert_eq!(order, vec!["new", "old", "undated"]);
+    }
+
+    #[test]
+    fn test_rust_code_block_is_highlighted() {
+        let settings = MarkdownSettings {
+            highlight_code: true,
+            ..MarkdownSettings::default()
         };
+        let html = markdown_to_html("```rust\nfn main() {}\n```\n", &settings);
 
-        assert_eq!(relative_path, "about/index.html");
-        assert!(!relative_path.starts_with('/'));
+        assert!(html.contains(r#"<pre class="code" data-lang="rust">"#), "{}", html);
+        assert!(html.contains(r#"<span class="storage type function rust">fn</span>"#), "{}", html);
     }
 
     #[test]
-    fn test_relative_path_generation_nested() {
-        let parent_key = "writing";
-        let slug = "my-post";
+    fn test_unknown_language_falls_back_to_plain_code() {
+        let settings = MarkdownSettings {
+            highlight_code: true,
+            ..MarkdownSettings::default()
+        };
+        let md = "```notalanguage\n<b>x</b>\n```\n";
 
-        let relative_path = if parent_key.is_empty() {
-            format!("{}/index.html", slug)
-        } else {
-            format!("{}/{}/index.html", parent_key, slug)
+        assert_eq!(
+            markdown_to_html(md, &settings),
+            markdown_to_html(md, &MarkdownSettings::default())
+        );
+        assert!(syntax_theme_css(DEFAULT_HIGHLIGHT_THEME).unwrap().contains(".storage"));
+    }
+
+    #[test]
+    fn test_footnotes_are_numbered_with_backrefs() {
+        let md = "First[^b] and second[^a], then first again[^b].\n\n[^a]: Note A.\n[^b]: Note B.\n[^unused]: Never cited.\n";
+        let html = markdown_to_html(md, &MarkdownSettings::default());
+
+        // Numbers follow first use, not definition order
+        assert!(html.contains(r##"First<sup class="footnote-reference" id="fnref-1"><a href="#fn-1">1</a></sup>"##), "{}", html);
+        assert!(html.contains(r##"second<sup class="footnote-reference" id="fnref-2"><a href="#fn-2">2</a></sup>"##), "{}", html);
+        assert!(html.contains(r##"again<sup class="footnote-reference" id="fnref-1-2"><a href="#fn-1">1</a></sup>"##), "{}", html);
+
+        let notes = &html[html.find(r#"<section class="footnotes">"#).unwrap()..];
+        let first = notes.find(r#"<li id="fn-1">"#).unwrap();
+        let second = notes.find(r#"<li id="fn-2">"#).unwrap();
+        assert!(first < second && notes[first..second].contains("Note B."), "{}", notes);
+        assert!(notes.contains(r##"<a href="#fnref-1" class="footnote-backref" aria-label="Back to reference 1">↩</a>"##));
+        assert!(notes.contains(r##"<a href="#fnref-1-2" class="footnote-backref" aria-label="Back to reference 1">↩<sup>2</sup></a></p>"##));
+        assert!(notes.contains(r##"Note A. <a href="#fnref-2" class="footnote-backref""##));
+        assert!(!html.contains("Never cited"));
+    }
+
+    #[test]
+    fn test_heading_ids_and_anchors() {
+        let html = markdown_to_html("## My Heading\n\n### Intro\n\n### Intro\n", &MarkdownSettings::default());
+
+        assert!(html.contains(r##"<h2 id="my-heading">My Heading <a class="anchor" href="#my-heading""##), "{}", html);
+        assert!(html.contains(r#"<h3 id="intro">"#));
+        assert!(html.contains(r#"<h3 id="intro-1">"#));
+        assert!(html.contains("</h2>"));
+    }
+
+    #[test]
+    fn test_heading_anchor_symbol_and_placement() {
+        let render = |symbol: &str, position: AnchorPosition| {
+            let settings = MarkdownSettings {
+                anchors: AnchorsConfig { symbol: symbol.to_string(), position },
+                ..MarkdownSettings::default()
+            };
+            markdown_to_html("## Setup\n", &settings)
+        };
+        let link = |symbol: &str| {
+            format!(r##"<a class="anchor" href="#setup" aria-label="Link to this section">{}</a>"##, symbol)
         };
 
-        assert_eq!(relative_path, "writing/my-post/index.html");
+        assert_eq!(render("¶", AnchorPosition::After), format!("<h2 id=\"setup\">Setup {}</h2>\n", link("¶")));
+        assert_eq!(render("#", AnchorPosition::Before), format!("<h2 id=\"setup\">{} Setup</h2>\n", link("#")));
+        let svg = r#"<svg class="icon" aria-hidden="true"><use href="/icons.svg#link"></use></svg>"#;
+        assert_eq!(render(svg, AnchorPosition::After), format!("<h2 id=\"setup\">Setup {}</h2>\n", link(svg)));
+        assert_eq!(render("", AnchorPosition::After), format!("<h2 id=\"setup\">Setup {}</h2>\n", link("")));
+        assert_eq!(render("none", AnchorPosition::Before), "<h2 id=\"setup\">Setup</h2>\n");
+
+        let config: Config = toml::from_str(
+            "base_url = \"\"\ntitle = \"\"\ndescription = \"\"\n[anchors]\nsymbol = \"¶\"\nposition = \"before\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.anchors, AnchorsConfig { symbol: "¶".to_string(), position: AnchorPosition::Before });
+        assert_eq!(test_config().anchors, AnchorsConfig::default());
+    }
+
+    #[test]
+    fn test_unique_heading_id_suffixes() {
+        let mut used = HashSet::new();
+        assert_eq!(unique_heading_id("intro", &mut used), "intro");
+        assert_eq!(unique_heading_id("intro", &mut used), "intro-1");
+        assert_eq!(unique_heading_id("intro-1", &mut used), "intro-1-1");
+        assert_eq!(unique_heading_id("intro", &mut used), "intro-2");
+    }
+
+    #[test]
+    fn test_toc_front_matter_limits_depth_or_disables_it() {
+        let content = tempfile::tempdir().unwrap();
+        let body = "## Setup\n\n### Install\n\n## Usage\n";
+        write_file(content.path(), "writing/shallow.md", &format!("+++\ntitle = \"Shallow\"\ntoc_depth = 2\n+++\n{}", body));
+        write_file(content.path(), "writing/none.md", &format!("+++\ntitle = \"None\"\ntoc = false\n+++\n{}", body));
+        write_file(content.path(), "writing/full.md", &format!("+++\ntitle = \"Full\"\n+++\n{}", body));
+
+        let mut config = test_config();
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        let page = |sections: &HashMap<String, SectionContent>, title: &str| {
+            sections["writing"].pages.iter().find(|p| p.title == title).unwrap().clone()
+        };
+
+        let shallow = page(&sections, "Shallow");
+        let ids: Vec<_> = shallow.toc.iter().map(|entry| (entry.level, entry.id.as_str())).collect();
+        assert_eq!(ids, vec![(2, "setup"), (2, "usage")]);
+        assert!(shallow.toc.iter().all(|entry| entry.children.is_empty()));
+
+        let none = page(&sections, "None");
+        assert!(none.toc.is_empty());
+        assert!(none.content.contains(r##"<h3 id="install">Install <a class="anchor" href="#install""##), "{}", none.content);
+
+        assert_eq!(page(&sections, "Full").toc[0].children.len(), 1);
+
+        // Site-wide defaults apply where front matter is silent
+        config.toc = false;
+        let (_, sections, _) = load_content(&[content.path().to_path_buf()], &config, &Tera::default()).unwrap();
+        assert!(page(&sections, "Full").toc.is_empty());
+        assert!(page(&sections, "Shallow").toc.is_empty());
+    }
+
+    #[test]
+    fn test_toc_collects_headings_in_order() {
+        let md = "# Title\n\n## Setup\n\n### Install\n\n#### Linux\n\n##### Too deep\n\n## Usage\n";
+        let rendered = render_markdown(md, &MarkdownSettings::default());
+        let toc = build_toc(&rendered.headings, DEFAULT_TOC_DEPTH);
+
+        fn flatten(entries: &[TocEntry], out: &mut Vec<(u32, String, String)>) {
+            for entry in entries {
+                out.push((entry.level, entry.title.clone(), entry.id.clone()));
+                flatten(&entry.children, out);
+            }
+        }
+        let mut flat = Vec::new();
+        flatten(&toc, &mut flat);
+
+        assert_eq!(flat, vec![
+            (2, "Setup".to_string(), "setup".to_string()),
+            (3, "Install".to_string(), "install".to_string()),
+            (4, "Linux".to_string(), "linux".to_string()),
+            (2, "Usage".to_string(), "usage".to_string()),
+        ]);
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].children[0].children[0].title, "Linux");
+    }
+
+    #[test]
+    fn test_word_count_skips_code_fences() {
+        let body = "# A heading here\n\nOne two three `inline` four.\n\n```rust\nfn main() { let ignored = words; }\n```\n\n- five six\n";
+        assert_eq!(count_words(body), 10);
+        assert_eq!(reading_time_minutes(10), 1);
+        assert_eq!(reading_time_minutes(0), 0);
+        assert_eq!(reading_time_minutes(200), 1);
+        assert_eq!(reading_time_minutes(201), 2);
     }
 }